@@ -24,13 +24,13 @@ fn create_cbor() -> CborOwned {
         let mut v = v.borrow_mut();
         CborBuilder::with_scratch_space(v.as_mut()).encode_dict(|b| {
             b.with_key("type", |b| b.write_str("WorkStopped", None));
-            b.with_key("byWhom", |b| b.write_str(&*name(), None));
+            b.with_key("byWhom", |b| b.write_str(&name(), None));
             b.with_key("pause", |b| b.write_bool(false, None));
             b.with_key("workers", |b| {
                 b.write_array(None, |b| {
-                    b.write_str(&*name(), None);
-                    b.write_str(&*name(), None);
-                    b.write_str(&*name(), None);
+                    b.write_str(&name(), None);
+                    b.write_str(&name(), None);
+                    b.write_str(&name(), None);
                 })
             });
             b.with_key("started", |b| b.write_pos(random(), Some(TAG_EPOCH)));
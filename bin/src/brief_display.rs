@@ -1,5 +1,8 @@
-use cbor_data::{Cbor, ItemKind, TaggedItem, Visitor};
-use std::fmt::{Display, Formatter};
+use cbor_data::{Cbor, ItemKind, PathElement, PathVisitor, TaggedItem, Visitor};
+use std::{
+    fmt::{Display, Formatter},
+    ops::Range,
+};
 
 pub struct BriefDisplay<'a> {
     pub cbor: &'a Cbor,
@@ -11,9 +14,8 @@ pub struct BriefDisplay<'a> {
 
 impl<'a> Display for BriefDisplay<'a> {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
-        self.cbor.visit(&mut BriefDisplayVisitor {
+        self.cbor.visit_paths(&mut BriefDisplayVisitor {
             fmt,
-            depth: 0,
             max_depth: self.max_depth,
             array_length: self.array_length as u64,
             censored_properties: self.censored_properties,
@@ -24,15 +26,19 @@ impl<'a> Display for BriefDisplay<'a> {
 
 struct BriefDisplayVisitor<'a, 'b> {
     fmt: &'a mut Formatter<'b>,
-    depth: usize,
     max_depth: usize,
     array_length: u64,
     censored_properties: &'a [String],
     text_length: usize,
 }
 
-impl<'a, 'b> Visitor<'a, std::fmt::Error> for BriefDisplayVisitor<'a, 'b> {
-    fn visit_simple(&mut self, item: TaggedItem<'a>) -> Result<(), std::fmt::Error> {
+impl<'a, 'b> PathVisitor<'a, std::fmt::Error> for BriefDisplayVisitor<'a, 'b> {
+    fn visit_simple(
+        &mut self,
+        _path: &[PathElement<'a>],
+        _range: Range<usize>,
+        item: TaggedItem<'a>,
+    ) -> Result<(), std::fmt::Error> {
         match item.kind() {
             ItemKind::Str(s) if s.len() > self.text_length => {
                 let s = s.as_cow();
@@ -50,12 +56,12 @@ impl<'a, 'b> Visitor<'a, std::fmt::Error> for BriefDisplayVisitor<'a, 'b> {
 
     fn visit_array_begin(
         &mut self,
+        path: &[PathElement<'a>],
+        _range: Range<usize>,
         array: TaggedItem<'a>,
         size: Option<u64>,
     ) -> Result<bool, std::fmt::Error> {
-        self.depth += 1;
-
-        if self.depth > self.max_depth {
+        if path.len() >= self.max_depth {
             write!(self.fmt, "[…]")?;
             Ok(false)
         } else {
@@ -65,6 +71,7 @@ impl<'a, 'b> Visitor<'a, std::fmt::Error> for BriefDisplayVisitor<'a, 'b> {
 
     fn visit_array_index(
         &mut self,
+        _path: &[PathElement<'a>],
         _array: TaggedItem<'a>,
         index: u64,
     ) -> Result<bool, std::fmt::Error> {
@@ -76,22 +83,26 @@ impl<'a, 'b> Visitor<'a, std::fmt::Error> for BriefDisplayVisitor<'a, 'b> {
         Ok(index < self.array_length)
     }
 
-    fn visit_array_end(&mut self, array: TaggedItem<'a>) -> Result<(), std::fmt::Error> {
-        if self.depth <= self.max_depth {
+    fn visit_array_end(
+        &mut self,
+        path: &[PathElement<'a>],
+        _range: Range<usize>,
+        array: TaggedItem<'a>,
+    ) -> Result<(), std::fmt::Error> {
+        if path.len() < self.max_depth {
             self.fmt.visit_array_end(array)?;
         }
-        self.depth -= 1;
         Ok(())
     }
 
     fn visit_dict_begin(
         &mut self,
+        path: &[PathElement<'a>],
+        _range: Range<usize>,
         dict: TaggedItem<'a>,
         size: Option<u64>,
     ) -> Result<bool, std::fmt::Error> {
-        self.depth += 1;
-
-        if self.depth > self.max_depth {
+        if path.len() >= self.max_depth {
             write!(self.fmt, "{{…}}")?;
             Ok(false)
         } else {
@@ -101,24 +112,30 @@ impl<'a, 'b> Visitor<'a, std::fmt::Error> for BriefDisplayVisitor<'a, 'b> {
 
     fn visit_dict_key(
         &mut self,
+        path: &[PathElement<'a>],
         dict: TaggedItem<'a>,
         key: TaggedItem<'a>,
+        _key_range: Range<usize>,
         is_first: bool,
     ) -> Result<bool, std::fmt::Error> {
-        if let Some(key) = key.decode().as_str() {
-            if self.censored_properties.iter().any(|k| k == key.as_ref()) {
-                write!(self.fmt, "…")?;
-                return Ok(false);
-            }
+        if let Some(PathElement::String(key)) = path.last()
+            && self.censored_properties.iter().any(|k| k == key.as_ref())
+        {
+            write!(self.fmt, "…")?;
+            return Ok(false);
         }
         self.fmt.visit_dict_key(dict, key, is_first)
     }
 
-    fn visit_dict_end(&mut self, dict: TaggedItem<'a>) -> Result<(), std::fmt::Error> {
-        if self.depth <= self.max_depth {
+    fn visit_dict_end(
+        &mut self,
+        path: &[PathElement<'a>],
+        _range: Range<usize>,
+        dict: TaggedItem<'a>,
+    ) -> Result<(), std::fmt::Error> {
+        if path.len() < self.max_depth {
             self.fmt.visit_dict_end(dict)?;
         }
-        self.depth -= 1;
         Ok(())
     }
 }
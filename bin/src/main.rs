@@ -1,6 +1,6 @@
 use crate::brief_display::BriefDisplay;
 use anyhow::Context;
-use cbor_data::Cbor;
+use cbor_data::CborSeqReader;
 use clap::Parser;
 use std::{
     fs::File,
@@ -57,7 +57,7 @@ struct Args {
 fn main() {
     let args = Args::parse();
 
-    let mut input = if args.input == "-" {
+    let input = if args.input == "-" {
         Box::new(stdin()) as Box<dyn std::io::Read>
     } else {
         Box::new(
@@ -76,42 +76,31 @@ fn main() {
         )
     };
 
-    let mut read_buf = Vec::new();
-    read_buf.resize(1048576, 0u8);
-    let read_buf = read_buf.as_mut_slice();
-
-    let mut data_buf = Vec::new();
-
     let mut count = 0;
-    while let Ok(n) = input.read(read_buf) {
-        if n == 0 {
-            break;
-        }
-        data_buf.extend_from_slice(&read_buf[..n]);
-        let mut rest = &data_buf[..];
-        while let Ok((cbor, r)) = Cbor::checked_prefix(rest) {
-            count += 1;
-            if args.string {
-                writeln!(
-                    &mut output,
-                    "{}",
-                    BriefDisplay {
-                        cbor,
-                        max_depth: args.max_depth,
-                        array_length: args.array_length,
-                        censored_properties: &args.censored_properties,
-                        text_length: args.text_length,
-                    }
-                )
-                .unwrap();
+    for item in CborSeqReader::new(input) {
+        match item {
+            Ok(cbor) => {
+                count += 1;
+                if args.string {
+                    writeln!(
+                        &mut output,
+                        "{}",
+                        BriefDisplay {
+                            cbor: &cbor,
+                            max_depth: args.max_depth,
+                            array_length: args.array_length,
+                            censored_properties: &args.censored_properties,
+                            text_length: args.text_length,
+                        }
+                    )
+                    .unwrap();
+                }
+            }
+            Err(e) => {
+                if !args.quiet {
+                    eprintln!("invalid input: {}", e);
+                }
             }
-            rest = r;
-        }
-        if rest.len() < data_buf.len() {
-            let len = rest.len();
-            let start = data_buf.len() - len;
-            data_buf.copy_within(start.., 0);
-            data_buf.truncate(len);
         }
     }
 
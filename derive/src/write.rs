@@ -63,7 +63,7 @@ pub fn code_write(data: Data, attrs: Vec<Attribute>) -> Result<TokenStream, Erro
                         .fields
                         .iter()
                         .enumerate()
-                        .map(|(i, _)| Ident::new(&*format!("b{}", i), Span::call_site()))
+                        .map(|(i, _)| Ident::new(&format!("b{}", i), Span::call_site()))
                         .collect::<Vec<_>>();
                     let pat = match &v.fields {
                         Fields::Named(f) => {
@@ -20,7 +20,7 @@ impl<'a> Bytes<'a> {
     pub fn as_mut(&mut self) -> &mut Vec<u8> {
         match self {
             Bytes::Owned(b) => b,
-            Bytes::Borrowed(b) => *b,
+            Bytes::Borrowed(b) => b,
         }
     }
 }
@@ -132,6 +132,21 @@ pub fn write_info(bytes: &mut Vec<u8>, major: u8, value: u64) -> usize {
     }
 }
 
+/// number of bytes written by [`write_info`] for the given value
+pub fn info_len(value: u64) -> usize {
+    if value < 24 {
+        1
+    } else if value < 0x100 {
+        2
+    } else if value < 0x1_0000 {
+        3
+    } else if value < 0x1_0000_0000 {
+        5
+    } else {
+        9
+    }
+}
+
 pub fn write_lit(bytes: &mut Vec<u8>, value: Literal) {
     match value {
         Literal::L0(v) => bytes.push(MAJOR_LIT << 5 | v),
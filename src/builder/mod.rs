@@ -2,7 +2,7 @@ use crate::CborOwned;
 use std::marker::PhantomData;
 
 mod encoder;
-mod low_level;
+pub(crate) mod low_level;
 mod writer;
 mod writers;
 
@@ -1,7 +1,7 @@
 use super::low_level::*;
 use crate::{
-    canonical::canonicalise, constants::*, ArrayWriter, Cbor, CborBuilder, DictWriter, Literal,
-    ParseError,
+    canonical::canonicalise, constants::*, update, ArrayWriter, Cbor, CborBuilder, DictWriter,
+    Literal, ParseError, PathElement, Update, ValidationLimits,
 };
 
 /// Low-level primitives for emitting CBOR items.
@@ -225,6 +225,7 @@ pub trait Writer: Sized {
             write_indefinite(b, MAJOR_DICT);
             let mut writer = DictWriter::new(b, max_definite);
             let ret = f(&mut writer);
+            writer.finish();
             let max_definite = writer.max_definite();
             finish_array(writer.count(), b, pos, MAJOR_DICT, max_definite);
             ret
@@ -240,6 +241,7 @@ pub trait Writer: Sized {
             canonicalise(
                 bytes,
                 CborBuilder::append_to(b).with_max_definite_size(max_definite),
+                &ValidationLimits::new(),
             )
         })
         .map(|_| self.into_output())
@@ -257,6 +259,34 @@ pub trait Writer: Sized {
     fn write_item(self, item: &Cbor) -> Self::Output {
         self.write_trusting(item.as_slice())
     }
+
+    /// Write the given CBOR item with an update applied to the item at the given path.
+    ///
+    /// Untouched items are copied byte for byte, only the headers of the arrays and dicts along
+    /// the path are rewritten, using definite size encoding according to
+    /// [`max_definite`](#tymethod.max_definite). Returns `None` without writing anything if the
+    /// path does not resolve or the update is not possible, see [`Update`](enum.Update.html).
+    ///
+    /// ```
+    /// # use cbor_data::{index_str, CborBuilder, CborOwned, Update, Writer};
+    /// let cbor = CborOwned::canonical([0x82, 1, 2]).unwrap();
+    /// let three = CborOwned::canonical([3]).unwrap();
+    /// let updated = CborBuilder::new()
+    ///     .with_max_definite_size(Some(2))
+    ///     .write_updated(&cbor, index_str("[-1]"), Update::Insert(&three))
+    ///     .unwrap();
+    /// assert_eq!(updated.to_string(), "[_ 1, 2, 3]");
+    /// ```
+    fn write_updated<'b>(
+        mut self,
+        item: &Cbor,
+        path: impl IntoIterator<Item = PathElement<'b>>,
+        update: Update<'_>,
+    ) -> Option<Self::Output> {
+        let max_definite = self.max_definite();
+        self.bytes(|b| update::update(item, path, update, Some(max_definite), b))?;
+        Some(self.into_output())
+    }
 }
 
 impl<T> Writer for &mut T
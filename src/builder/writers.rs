@@ -1,5 +1,9 @@
 use super::low_level::Bytes;
-use crate::Writer;
+use crate::{
+    canonical::{arrange, Entry},
+    validated::skip,
+    DuplicateKeys, KeyOrder, Writer,
+};
 use std::marker::PhantomData;
 
 /// Builder for an array value, used by `write_array_ret()`.
@@ -64,19 +68,29 @@ impl<'a> Writer for ArrayWriter<'a> {
 /// Builder for a dict value, used by `write_dict_rec()`.
 ///
 /// see [`trait Encoder`](trait.Encoder.html) for usage examples
-pub struct DictWriter<'a>(ArrayWriter<'a>);
+pub struct DictWriter<'a> {
+    writer: ArrayWriter<'a>,
+    /// position of the first key
+    start: usize,
+    key_order: KeyOrder,
+}
 
 impl<'a> DictWriter<'a> {
     pub(crate) fn new(bytes: &'a mut Vec<u8>, max_definite: Option<u64>) -> Self {
-        Self(ArrayWriter::new(bytes, max_definite))
+        let start = bytes.len();
+        Self {
+            writer: ArrayWriter::new(bytes, max_definite),
+            start,
+            key_order: KeyOrder::Preserve,
+        }
     }
 
     pub(crate) fn count(&self) -> u64 {
-        self.0.count
+        self.writer.count
     }
 
     pub fn max_definite(&self) -> Option<u64> {
-        self.0.max_definite
+        self.writer.max_definite
     }
 
     /// Configure the limit above which indefinite size encoding will be used.
@@ -85,7 +99,50 @@ impl<'a> DictWriter<'a> {
     /// compact as indefinite size. Set to 23 to avoid moving bytes around when finishing the array.
     /// Set to `None` to always use indefinite size encoding.
     pub fn set_max_definite_size(&mut self, size: Option<u64>) {
-        self.0.max_definite = size;
+        self.writer.max_definite = size;
+    }
+
+    /// Configure the order of the entries in the finished dict.
+    ///
+    /// The default is [`KeyOrder::Preserve`](enum.KeyOrder.html#variant.Preserve), which keeps
+    /// the entries in the order in which they were written. Sorting takes place when the dict
+    /// is finished, entries with equal keys are kept in the order in which they were written.
+    ///
+    /// ```
+    /// # use cbor_data::{CborBuilder, Writer, Encoder, KeyOrder};
+    /// let cbor = CborBuilder::new().encode_dict(|builder| {
+    ///     builder.set_key_order(KeyOrder::LengthFirst);
+    ///     builder.with_key("bb", |b| b.encode_u64(1));
+    ///     builder.with_key("a", |b| b.encode_u64(2));
+    /// });
+    /// assert_eq!(cbor.to_string(), r#"{"a": 2, "bb": 1}"#);
+    /// ```
+    pub fn set_key_order(&mut self, key_order: KeyOrder) {
+        self.key_order = key_order;
+    }
+
+    /// sort the entries according to the configured key order
+    pub(crate) fn finish(&mut self) {
+        if self.key_order == KeyOrder::Preserve {
+            return;
+        }
+        let bytes = self.writer.bytes.as_mut();
+        let mut entries = Vec::new();
+        let mut pos = self.start;
+        while pos < bytes.len() {
+            let value = bytes.len() - skip(&bytes[pos..]).1.len();
+            let next = bytes.len() - skip(&bytes[value..]).1.len();
+            entries.push(Entry {
+                key: pos,
+                value,
+                input: pos,
+                normal: None,
+                varying: 0,
+            });
+            pos = next;
+        }
+        // repeated keys stay in the order in which they were written
+        arrange(bytes, &entries, self.key_order, DuplicateKeys::Allow).ok();
     }
 
     /// Add one key–value pair to the dict.
@@ -109,8 +166,10 @@ impl<'a> DictWriter<'a> {
         k: impl FnOnce(SingleBuilder<'_, '_>) -> SingleResult,
         v: impl FnOnce(SingleBuilder<'_, '_>) -> SingleResult,
     ) -> &mut Self {
-        k(SingleBuilder(&mut self.0.non_tracking(self.0.max_definite)));
-        v(SingleBuilder(&mut self.0));
+        k(SingleBuilder(
+            &mut self.writer.non_tracking(self.writer.max_definite),
+        ));
+        v(SingleBuilder(&mut self.writer));
         self
     }
 
@@ -118,7 +177,7 @@ impl<'a> DictWriter<'a> {
         &mut self,
         f: impl FnOnce(KeyBuilder<'_, '_>) -> Result<SingleResult, E>,
     ) -> Result<&mut Self, E> {
-        f(KeyBuilder(&mut self.0))?;
+        f(KeyBuilder(&mut self.writer))?;
         Ok(self)
     }
 }
@@ -1,155 +1,672 @@
 use crate::{
-    check::{value_bytes, MkErr},
+    builder::low_level::{finish_array, write_indefinite, write_info, write_lit, write_tags},
+    check::Parser,
     constants::*,
-    error::{ErrorKind, InternalError},
-    reader::{careful_literal, indefinite, integer, major, tags},
-    validated::iterators::BytesIter,
-    ArrayWriter, DictWriter,
-    ErrorKind::*,
-    ParseError,
-    WhileParsing::*,
-    Writer,
+    reader::{careful_literal, float, integer},
+    CborBuilder, CborOwned, DuplicateKeys,
+    ErrorKind::TrailingGarbage,
+    Literal, ParseError, Tags, ValidationLimits, Writer,
 };
+use half::f16;
+use std::{cmp::Ordering, ops::Range};
 
-/// Canonicalise the input bytes into the output Writer
-pub fn canonicalise<W: Writer>(bytes: &[u8], builder: W) -> Result<W::Output, ParseError> {
-    let (rest, cbor) = canonical(bytes, builder).map_err(|e| e.rebase(bytes))?;
-    if rest.is_empty() {
-        Ok(cbor)
-    } else {
-        Err(InternalError::new(rest, TrailingGarbage).rebase(bytes))
+/// Order of the entries within a dict
+///
+/// This is used by [`CborOwned::deterministic_with`](struct.CborOwned.html#method.deterministic_with),
+/// [`Cbor::is_deterministic`](struct.Cbor.html#method.is_deterministic), and
+/// [`DictWriter::set_key_order`](struct.DictWriter.html#method.set_key_order).
+///
+/// ```rust
+/// use cbor_data::{CborOwned, KeyOrder, ValidationLimits};
+///
+/// // {"aa": 1, "b": 2}
+/// let bytes = [0xa2, 0x62, b'a', b'a', 1, 0x61, b'b', 2];
+/// let limits = ValidationLimits::new();
+///
+/// let bytewise = CborOwned::deterministic_with(bytes, KeyOrder::Bytewise, &limits).unwrap();
+/// assert_eq!(bytewise.to_string(), r#"{"b": 2, "aa": 1}"#);
+///
+/// // the RFC 7049 ordering is different for keys with longer headers
+/// let bytes = [0xa2, 0x18, 100, 1, 0x20, 2];
+/// let length_first = CborOwned::deterministic_with(bytes, KeyOrder::LengthFirst, &limits).unwrap();
+/// assert_eq!(length_first.to_string(), "{-1: 2, 100: 1}");
+/// let bytewise = CborOwned::deterministic_with(bytes, KeyOrder::Bytewise, &limits).unwrap();
+/// assert_eq!(bytewise.to_string(), "{100: 1, -1: 2}");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyOrder {
+    /// bytewise lexicographic order of the encoded keys, as per
+    /// [RFC 8949 §4.2.1](https://www.rfc-editor.org/rfc/rfc8949#section-4.2.1)
+    #[default]
+    Bytewise,
+    /// shorter encoded keys first, bytewise lexicographic order among keys of the same length, as per
+    /// [RFC 7049 §3.9](https://www.rfc-editor.org/rfc/rfc7049#section-3.9) (also used by CTAP2)
+    LengthFirst,
+    /// keep the entries in the order in which they were written
+    Preserve,
+}
+
+impl KeyOrder {
+    fn compare(self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            KeyOrder::Bytewise => a.cmp(b),
+            KeyOrder::LengthFirst => a.len().cmp(&b.len()).then_with(|| a.cmp(b)),
+            KeyOrder::Preserve => Ordering::Equal,
+        }
     }
 }
 
-fn canonical<W: Writer>(
+/// Canonicalise the input bytes into the output Writer
+pub fn canonicalise<W: Writer>(
     bytes: &[u8],
     builder: W,
-) -> Result<(&'_ [u8], W::Output), InternalError<'_>> {
-    let (tags, bytes) = tags(bytes).ok_or_else(|| InternalError::new(bytes, InvalidInfo))?;
-    match major(bytes).ok_or_else(|| InternalError::new(bytes, UnexpectedEof(ItemHeader)))? {
-        MAJOR_POS => integer(bytes)
-            .map(|(x, _, r)| (r, builder.write_pos(x, tags)))
-            .header_value(bytes),
-        MAJOR_NEG => integer(bytes)
-            .map(|(x, _, r)| (r, builder.write_neg(x, tags)))
-            .header_value(bytes),
-        MAJOR_BYTES => {
-            if tags.single() == Some(TAG_CBOR_ITEM) {
-                // drop top-level CBOR item encoding wrapper
-                value_bytes(bytes, true, false).and_then(|(b, rest)| {
-                    canonicalise(b.as_ref(), builder)
-                        .map(|out| (rest, out))
-                        .map_err(|e| {
-                            let mut offset = e.offset();
-                            let iter = if bytes[0] & 31 == INDEFINITE_SIZE {
-                                BytesIter::new(&bytes[1..], None)
-                            } else {
-                                BytesIter::new(bytes, Some(1))
-                            };
-                            for slice in iter {
-                                if offset < slice.len() {
-                                    return InternalError::new(&slice[offset..], e.kind());
-                                }
-                                offset -= slice.len();
-                            }
-                            InternalError::new(rest, e.kind())
-                        })
-                })
-            } else {
-                value_bytes(bytes, true, false)
-                    .map(|(b, rest)| (rest, builder.write_bytes(b.as_ref(), tags)))
+    limits: &ValidationLimits,
+) -> Result<W::Output, ParseError> {
+    run(bytes, builder, limits, None)
+}
+
+/// Canonicalise the input bytes into the output Writer, following the core deterministic
+/// encoding requirements of [RFC 8949 §4.2.1](https://www.rfc-editor.org/rfc/rfc8949#section-4.2.1)
+pub fn canonicalise_deterministic<W: Writer>(
+    bytes: &[u8],
+    builder: W,
+    key_order: KeyOrder,
+    limits: &ValidationLimits,
+) -> Result<W::Output, ParseError> {
+    run(bytes, builder, limits, Some(key_order))
+}
+
+fn run<W: Writer>(
+    bytes: &[u8],
+    mut builder: W,
+    limits: &ValidationLimits,
+    deterministic: Option<KeyOrder>,
+) -> Result<W::Output, ParseError> {
+    let max_definite = builder.max_definite();
+    let duplicates = limits.duplicate_keys();
+    builder.bytes(|b| {
+        let start = b.len();
+        let bytes_out = std::mem::take(b);
+        let mut out = match deterministic {
+            Some(key_order) => Canonical::deterministic(bytes_out, key_order, duplicates),
+            None => Canonical::new(bytes_out, max_definite, duplicates),
+        };
+        let result = Parser::new(*limits).parse(bytes, Some(&mut out));
+        *b = out.bytes;
+        match result {
+            Ok(end) if end == bytes.len() => Ok(()),
+            Ok(end) => {
+                b.truncate(start);
+                Err(ParseError::new(end, TrailingGarbage))
+            }
+            Err(stop) => {
+                b.truncate(start);
+                Err(stop.into_error())
             }
         }
-        MAJOR_STR => value_bytes(bytes, true, true).map(|(b, rest)| {
-            let s = unsafe { std::str::from_utf8_unchecked(b.as_ref()) };
-            (rest, builder.write_str(s, tags))
-        }),
-        MAJOR_ARRAY => {
-            let (cbor, result) = builder.write_array_ret(tags, |b| canonicalise_array(bytes, b));
-            result.map(|rest| (rest, cbor))
+    })?;
+    Ok(builder.into_output())
+}
+
+/// Output of the canonical form of the items found by the [`Parser`](../check/struct.Parser.html)
+pub(crate) struct Canonical {
+    bytes: Vec<u8>,
+    max_definite: Option<u64>,
+    deterministic: bool,
+    key_order: KeyOrder,
+    duplicates: DuplicateKeys,
+    /// entries written so far for each open dict, only tracked if they need to be arranged
+    dicts: Vec<Vec<Entry>>,
+    /// number of floats and dicts written so far, whose encoding may differ from the deterministic one
+    varying: usize,
+}
+
+/// Position of a key–value pair in the output, plus the position of the key in the input
+pub(crate) struct Entry {
+    pub key: usize,
+    pub value: usize,
+    pub input: usize,
+    /// deterministic encoding of the key if it differs from the output
+    pub normal: Option<Vec<u8>>,
+    /// number of floats and dicts written before the key
+    pub varying: usize,
+}
+
+impl Canonical {
+    pub fn new(bytes: Vec<u8>, max_definite: Option<u64>, duplicates: DuplicateKeys) -> Self {
+        Self {
+            bytes,
+            max_definite,
+            deterministic: false,
+            key_order: KeyOrder::Preserve,
+            duplicates,
+            dicts: Vec::new(),
+            varying: 0,
         }
-        MAJOR_DICT => {
-            let (cbor, result) = builder.write_dict_ret(tags, |b| canonicalise_dict(bytes, b));
-            result.map(|rest| (rest, cbor))
+    }
+
+    /// write definite lengths only, the shortest lossless float encodings, and sorted dict keys
+    ///
+    /// Duplicate keys are rejected unless they shall be removed.
+    pub fn deterministic(bytes: Vec<u8>, key_order: KeyOrder, duplicates: DuplicateKeys) -> Self {
+        let duplicates = match duplicates {
+            DuplicateKeys::Allow => DuplicateKeys::Reject,
+            d => d,
+        };
+        Self {
+            bytes,
+            max_definite: Some(u64::MAX),
+            deterministic: true,
+            key_order,
+            duplicates,
+            dicts: Vec::new(),
+            varying: 0,
         }
-        MAJOR_LIT => careful_literal(bytes)
-            .map(|(lit, rest)| (rest, builder.write_lit(lit, tags)))
-            .ok_or_else(|| InternalError::new(bytes, ErrorKind::InvalidInfo)),
-        _ => unreachable!(),
     }
-}
 
-fn canonicalise_array<'a>(
-    bytes: &'a [u8],
-    mut builder: &mut ArrayWriter,
-) -> Result<&'a [u8], InternalError<'a>> {
-    // at this point the first byte (indefinite array) has already been written
-    let (len, _, mut bytes) = integer(bytes)
-        .or_else(|| indefinite(bytes))
-        .header_value(bytes)?;
-    if len == u64::MAX {
-        // marker for indefinite size
-        while *bytes
-            .get(0)
-            .ok_or_else(|| InternalError::new(bytes, UnexpectedEof(ArrayItem)))?
-            != STOP_BYTE
-        {
-            bytes = canonical(bytes, &mut builder)?.0;
+    /// forget all output
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.dicts.clear();
+    }
+
+    pub fn tags(&mut self, tags: Tags<'_>) {
+        write_tags(&mut self.bytes, tags);
+    }
+
+    pub fn int(&mut self, tags: Tags<'_>, major: u8, value: u64) {
+        write_tags(&mut self.bytes, tags);
+        write_info(&mut self.bytes, major, value);
+    }
+
+    pub fn literal(&mut self, tags: Tags<'_>, value: Literal) {
+        write_tags(&mut self.bytes, tags);
+        if let Literal::L2(_) | Literal::L4(_) | Literal::L8(_) = value {
+            self.varying += 1;
         }
-        Ok(&bytes[1..])
-    } else {
-        for _ in 0..len {
-            if bytes.is_empty() {
-                return Err(InternalError::new(bytes, UnexpectedEof(ArrayItem)));
+        let value = match value {
+            Literal::L2(v) if self.deterministic => shortest_float(f16::from_bits(v).to_f64()),
+            Literal::L4(v) if self.deterministic => shortest_float(f32::from_bits(v).into()),
+            Literal::L8(v) if self.deterministic => shortest_float(f64::from_bits(v)),
+            value => value,
+        };
+        write_lit(&mut self.bytes, value);
+    }
+
+    /// write the contents of a string, its tags have already been written
+    pub fn string(&mut self, major: u8, value: &[u8]) {
+        write_info(&mut self.bytes, major, value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    /// start an array or dict, returning the position to pass to [`close`](#method.close)
+    pub fn open(&mut self, tags: Tags<'_>, major: u8) -> usize {
+        write_tags(&mut self.bytes, tags);
+        let pos = self.bytes.len();
+        write_indefinite(&mut self.bytes, major);
+        if major == MAJOR_DICT {
+            self.varying += 1;
+        }
+        if major == MAJOR_DICT && (self.deterministic || self.duplicates != DuplicateKeys::Allow) {
+            self.dicts.push(Vec::new());
+        }
+        pos
+    }
+
+    /// the next key of the innermost dict starts at the given input position
+    ///
+    /// This may be called repeatedly for the same key when parsing is resumed.
+    pub fn key(&mut self, input: usize) {
+        let key = self.bytes.len();
+        if let Some(entries) = self.dicts.last_mut() {
+            if entries.last().is_some_and(|e| e.key == key) {
+                return;
             }
-            bytes = canonical(bytes, &mut builder)?.0;
+            entries.push(Entry {
+                key,
+                value: 0,
+                input,
+                normal: None,
+                varying: self.varying,
+            });
         }
-        Ok(bytes)
     }
-}
 
-fn canonicalise_dict<'a>(
-    bytes: &'a [u8],
-    builder: &mut DictWriter,
-) -> Result<&'a [u8], InternalError<'a>> {
-    fn pair<'a>(bytes: &mut &'a [u8], w: &mut DictWriter) -> Result<(), InternalError<'a>> {
-        w.try_write_pair(|key| {
-            let (rest, val) = canonical(bytes, key)?;
-            let (rest, res) = canonical(rest, val)?;
-            *bytes = rest;
-            Ok(res)
-        })?;
+    /// the value for the last key of the innermost dict starts now
+    pub fn value(&mut self) {
+        let value = self.bytes.len();
+        if let Some(entry) = self.dicts.last_mut().and_then(|e| e.last_mut()) {
+            entry.value = value;
+            if entry.varying != self.varying && !self.deterministic {
+                // floats of different width or dicts in different order may have the same value
+                let key = &self.bytes[entry.key..value];
+                entry.normal = canonicalise_deterministic(
+                    key,
+                    CborBuilder::new(),
+                    KeyOrder::Bytewise,
+                    &ValidationLimits::new(),
+                )
+                .ok()
+                .map(CborOwned::into_vec);
+            }
+        }
+    }
+
+    /// finish an array or dict with the given number of items resp. pairs
+    ///
+    /// Returns the input position of the first repeated key if those are rejected.
+    pub fn close(&mut self, pos: usize, major: u8, mut count: u64) -> Result<(), usize> {
+        if major == MAJOR_DICT && (self.deterministic || self.duplicates != DuplicateKeys::Allow) {
+            let entries = self.dicts.pop().unwrap_or_default();
+            count -= arrange(&mut self.bytes, &entries, self.key_order, self.duplicates)?;
+        }
+        finish_array(count, &mut self.bytes, pos, major, self.max_definite);
         Ok(())
     }
+}
+
+/// Arrange the key–value pairs that start at the given entries and extend to the end of `bytes`.
+///
+/// The entries need to be given in the order in which they were written. Entries with equal keys
+/// retain this order, so that the first one is kept when removing duplicates. Returns the number
+/// of removed entries, or the input position of the first repeated key if those are rejected.
+pub(crate) fn arrange(
+    bytes: &mut Vec<u8>,
+    entries: &[Entry],
+    key_order: KeyOrder,
+    duplicates: DuplicateKeys,
+) -> Result<u64, usize> {
+    let start = match entries.first() {
+        Some(entry) => entry.key,
+        None => return Ok(0),
+    };
+    let key = |i: usize| &bytes[entries[i].key..entries[i].value];
+    let normal = |i: usize| entries[i].normal.as_deref().unwrap_or_else(|| key(i));
+    let end = |i: usize| entries.get(i + 1).map_or(bytes.len(), |e| e.key);
+
+    let mut removed = vec![false; entries.len()];
+    if duplicates != DuplicateKeys::Allow {
+        // stable sort, so that repeated keys stay in input order
+        let mut sorted = (0..entries.len()).collect::<Vec<_>>();
+        sorted.sort_by(|a, b| normal(*a).cmp(normal(*b)));
+        for w in sorted.windows(2) {
+            if normal(w[0]) == normal(w[1]) {
+                removed[w[1]] = true;
+            }
+        }
+        if duplicates == DuplicateKeys::Reject {
+            let first = (0..entries.len()).find(|i| removed[*i]);
+            if let Some(i) = first {
+                return Err(entries[i].input);
+            }
+        }
+    }
 
-    // at this point the first byte (indefinite array) has already been written
-    let (len, _, mut bytes) = integer(bytes)
-        .or_else(|| indefinite(bytes))
-        .header_value(bytes)?;
-    if len == u64::MAX {
-        // marker for indefinite size
-        while *bytes
-            .get(0)
-            .ok_or_else(|| InternalError::new(bytes, UnexpectedEof(DictItem)))?
-            != STOP_BYTE
-        {
-            pair(&mut bytes, builder)?;
+    let mut sorted = (0..entries.len()).collect::<Vec<_>>();
+    if key_order != KeyOrder::Preserve {
+        sorted.sort_by(|a, b| key_order.compare(key(*a), key(*b)));
+    }
+    let count = removed.iter().filter(|r| **r).count();
+    if count > 0 || sorted.windows(2).any(|w| w[0] > w[1]) {
+        let mut pairs = Vec::with_capacity(bytes.len() - start);
+        for i in sorted.into_iter().filter(|i| !removed[*i]) {
+            pairs.extend_from_slice(&bytes[entries[i].key..end(i)]);
         }
-        Ok(&bytes[1..])
+        bytes.truncate(start);
+        bytes.extend_from_slice(&pairs);
+    }
+    Ok(count as u64)
+}
+
+/// the shortest float encoding that preserves the given value
+fn shortest_float(value: f64) -> Literal {
+    let half = f16::from_f64(value);
+    if half.to_f64().to_bits() == value.to_bits() {
+        return Literal::L2(half.to_bits());
+    }
+    let single = value as f32;
+    if f64::from(single).to_bits() == value.to_bits() {
+        Literal::L4(single.to_bits())
     } else {
-        for _ in 0..len {
-            if bytes.is_empty() {
-                return Err(InternalError::new(bytes, UnexpectedEof(DictItem)));
+        Literal::L8(value.to_bits())
+    }
+}
+
+/// Check whether the given valid bytes are unchanged by
+/// [`canonicalise_deterministic`](fn.canonicalise_deterministic.html).
+pub(crate) fn is_deterministic(bytes: &[u8], key_order: KeyOrder) -> bool {
+    /// an array or dict whose items are being checked
+    struct Open {
+        /// number of items still to come, counting keys and values separately
+        remaining: u64,
+        dict: bool,
+        key: usize,
+        keys: Vec<Range<usize>>,
+    }
+    let mut stack = Vec::<Open>::new();
+    let mut pos = 0;
+    loop {
+        if let Some(open) = stack.last_mut().filter(|o| o.dict && o.remaining % 2 == 0) {
+            open.key = pos;
+        }
+
+        let mut count = 0;
+        let mut tag = None;
+        while bytes[pos] >> 5 == MAJOR_TAG {
+            let (t, h, _) = integer(&bytes[pos..]).unwrap();
+            if !shortest(t, h.len()) {
+                return false;
             }
-            pair(&mut bytes, builder)?;
+            count += 1;
+            tag = if count == 1 { Some(t) } else { None };
+            pos += h.len();
         }
-        Ok(bytes)
+        let b = &bytes[pos..];
+        let major = b[0] >> 5;
+        let Some((value, h, _)) = integer(b) else {
+            // indefinite size
+            return false;
+        };
+        pos += h.len();
+        match major {
+            MAJOR_POS | MAJOR_NEG => {
+                if !shortest(value, h.len()) {
+                    return false;
+                }
+            }
+            MAJOR_LIT => {
+                if let Some((value, _, _)) = float(b) {
+                    if !same_float(careful_literal(b).unwrap().0, value) {
+                        return false;
+                    }
+                }
+            }
+            MAJOR_BYTES | MAJOR_STR => {
+                // a TAG_CBOR_ITEM byte string would have been replaced by its contents
+                if !shortest(value, h.len()) || (major == MAJOR_BYTES && tag == Some(TAG_CBOR_ITEM))
+                {
+                    return false;
+                }
+                pos += value as usize;
+            }
+            _ => {
+                if !shortest(value, h.len()) {
+                    return false;
+                }
+                if value > 0 {
+                    let dict = major == MAJOR_DICT;
+                    stack.push(Open {
+                        remaining: if dict { value * 2 } else { value },
+                        dict,
+                        key: 0,
+                        keys: Vec::new(),
+                    });
+                    continue;
+                }
+            }
+        }
+
+        // the item is complete, which may complete its enclosing containers as well
+        loop {
+            let Some(open) = stack.last_mut() else {
+                return true;
+            };
+            open.remaining -= 1;
+            if open.dict && open.remaining % 2 == 1 {
+                open.keys.push(open.key..pos);
+            }
+            if open.remaining > 0 {
+                break;
+            }
+            let mut keys = stack.pop().unwrap().keys;
+            let key = |r: &Range<usize>| &bytes[r.clone()];
+            if key_order == KeyOrder::Preserve {
+                keys.sort_by(|a, b| key(a).cmp(key(b)));
+            }
+            let order = match key_order {
+                KeyOrder::Preserve => KeyOrder::Bytewise,
+                order => order,
+            };
+            if keys
+                .windows(2)
+                .any(|w| order.compare(key(&w[0]), key(&w[1])) != Ordering::Less)
+            {
+                return false;
+            }
+        }
+    }
+}
+
+/// whether the header of `value` has the given length in its shortest encoding
+fn shortest(value: u64, len: usize) -> bool {
+    let expected = match value {
+        0..=23 => 1,
+        24..=0xff => 2,
+        0x100..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    };
+    len == expected
+}
+
+/// whether the float is encoded in the shortest form, comparing bits to also cover NaN payloads
+fn same_float(literal: Literal, value: f64) -> bool {
+    match (literal, shortest_float(value)) {
+        (Literal::L2(a), Literal::L2(b)) => a == b,
+        (Literal::L4(a), Literal::L4(b)) => a == b,
+        (Literal::L8(a), Literal::L8(b)) => a == b,
+        _ => false,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{constants::TAG_CBOR_ITEM, index_str, CborBuilder, CborOwned, Writer};
+    use crate::{
+        constants::TAG_CBOR_ITEM, index_str, Cbor, CborBuilder, CborOwned, Encoder, ErrorKind::*,
+        KeyOrder, ValidationLimits, Writer,
+    };
+
+    #[test]
+    fn deep_nesting() {
+        const DEPTH: usize = 1_000_000;
+        let mut bytes = vec![0x9fu8; DEPTH];
+        bytes.extend_from_slice(&[0xbf, 0, 0x9f, 0xff, 0xff]);
+        bytes.extend(std::iter::repeat_n(0xff, DEPTH));
+
+        let canonical = CborOwned::canonical(&bytes).unwrap();
+        let mut expected = vec![0x81u8; DEPTH];
+        expected.extend_from_slice(&[0xa1, 0, 0x80]);
+        assert!(canonical.as_slice() == expected.as_slice());
+
+        let indefinite = CborBuilder::new()
+            .with_max_definite_size(None)
+            .write_canonical(&expected)
+            .unwrap();
+        assert!(indefinite.as_slice() == bytes.as_slice());
+    }
+
+    #[test]
+    fn deterministic() {
+        let d = |bytes: &[u8]| CborOwned::deterministic(bytes).map(|c| c.as_slice().to_vec());
+
+        // keys are ordered by their encoding, which puts shorter strings first
+        let bytes = CborBuilder::new().write_dict(None, |b| {
+            b.with_key("b", |b| b.write_pos(1, None));
+            b.with_key("aa", |b| b.write_pos(2, None));
+            b.with_cbor_key(|b| b.write_neg(0, None), |b| b.write_pos(3, None));
+            b.with_cbor_key(|b| b.write_pos(100, None), |b| b.write_pos(4, None));
+            b.with_cbor_key(|b| b.write_pos(10, None), |b| b.write_pos(5, None));
+        });
+        assert_eq!(
+            d(bytes.as_slice()).unwrap(),
+            [0xa5, 10, 5, 0x18, 100, 4, 0x20, 3, 0x61, b'b', 1, 0x62, b'a', b'a', 2]
+        );
+
+        // nested dicts are sorted, indefinite sizes become definite even for large arrays
+        let mut bytes = vec![0xbf, 0x61, b'z', 0x9f];
+        bytes.extend(std::iter::repeat_n(0, 30));
+        bytes.extend_from_slice(&[
+            0xff, 0x61, b'y', 0xbf, 0x61, b'd', 0xf6, 0x61, b'c', 0xf7, 0xff, 0xff,
+        ]);
+        let mut expected = vec![0xa2, 0x61, b'y', 0xa2, 0x61, b'c', 0xf7, 0x61, b'd', 0xf6];
+        expected.extend_from_slice(&[0x61, b'z', 0x98, 30]);
+        expected.extend(std::iter::repeat_n(0, 30));
+        assert_eq!(d(&bytes).unwrap(), expected);
+
+        // floats use the shortest encoding that preserves the value
+        let f = |v: f64| d(CborBuilder::new().encode_f64(v).as_slice()).unwrap();
+        assert_eq!(f(1.0), [0xf9, 0x3c, 0]);
+        assert_eq!(f(-0.0), [0xf9, 0x80, 0]);
+        assert_eq!(f(f64::INFINITY), [0xf9, 0x7c, 0]);
+        assert_eq!(f(f64::NAN), [0xf9, 0x7e, 0]);
+        assert_eq!(f(100000.0), [0xfa, 0x47, 0xc3, 0x50, 0]);
+        assert_eq!(f(0.1f32.into()), [0xfa, 0x3d, 0xcc, 0xcc, 0xcd]);
+        assert_eq!(f(0.1), CborBuilder::new().encode_f64(0.1).as_slice());
+        assert_eq!(d(&[0xfa, 0x3f, 0xc0, 0, 0]).unwrap(), [0xf9, 0x3e, 0]);
+
+        // keys are compared after normalisation
+        let error = d(&[0xa2, 1, 0xf6, 0x18, 1, 0xf6]).unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (3, DuplicateKey));
+        let error = d(&[
+            0xa4, 2, 0, 1, 0, 0xf9, 0x3c, 0, 0, 0xfb, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+        let error = error.unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (9, DuplicateKey));
+        let error = d(&[0x81, 0xd8, 24, 0x45, 0xa2, 0x60, 0, 0x60, 0]).unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (7, DuplicateKey));
+        assert!(CborOwned::canonical([0xa2, 1, 0xf6, 1, 0xf6]).is_ok());
+    }
+
+    #[test]
+    fn key_order() {
+        let bytes = CborBuilder::new().write_dict(None, |b| {
+            b.with_key("bb", |b| b.write_pos(1, None));
+            b.with_cbor_key(|b| b.write_pos(1000, None), |b| b.write_pos(2, None));
+            b.with_key("c", |b| b.write_pos(3, None));
+            b.with_cbor_key(|b| b.write_neg(0, None), |b| b.write_pos(4, None));
+        });
+        let d = |order| {
+            CborOwned::deterministic_with(bytes.as_slice(), order, &ValidationLimits::new())
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            d(KeyOrder::Bytewise),
+            r#"{1000: 2, -1: 4, "c": 3, "bb": 1}"#
+        );
+        assert_eq!(
+            d(KeyOrder::LengthFirst),
+            r#"{-1: 4, "c": 3, 1000: 2, "bb": 1}"#
+        );
+        assert_eq!(
+            d(KeyOrder::Preserve),
+            r#"{"bb": 1, 1000: 2, "c": 3, -1: 4}"#
+        );
+
+        let error = CborOwned::deterministic_with(
+            [0xa3, 0, 0, 1, 0, 0x18, 0, 0],
+            KeyOrder::Preserve,
+            &ValidationLimits::new(),
+        )
+        .unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (5, DuplicateKey));
+
+        // the DictWriter sorts the entries when finished, also within values
+        for order in [
+            KeyOrder::Bytewise,
+            KeyOrder::LengthFirst,
+            KeyOrder::Preserve,
+        ] {
+            let cbor = CborBuilder::new().write_dict(None, |b| {
+                b.set_key_order(order);
+                b.with_key("bb", |b| b.write_pos(1, None));
+                b.with_cbor_key(
+                    |b| b.write_pos(1000, None),
+                    |b| {
+                        b.write_dict(None, |b| {
+                            b.set_key_order(order);
+                            b.with_key("y", |b| b.write_null(None));
+                            b.with_key("x", |b| b.write_null(None));
+                        })
+                    },
+                );
+                b.with_key("c", |b| b.write_pos(3, None));
+                b.try_write_pair(|b| {
+                    Ok::<_, ()>(b.write_neg(0, None).write_array(None, |b| {
+                        for i in 0..30 {
+                            b.write_pos(i, None);
+                        }
+                    }))
+                })
+                .unwrap();
+            });
+            assert!(cbor.is_deterministic(order), "{:?}", order);
+            let expected =
+                CborOwned::deterministic_with(cbor.as_slice(), order, &ValidationLimits::new())
+                    .unwrap();
+            assert_eq!(cbor, expected, "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn is_deterministic() {
+        let inputs: &[&[u8]] = &[
+            &[0x00],
+            &[0x18, 0x17],
+            &[0x38, 0x18],
+            &[0x19, 0x00, 0xff],
+            &[0xd8, 0x17, 0x00],
+            &[0xd9, 0x00, 0x17, 0x00],
+            &[0x43, 1, 2, 3],
+            &[0x5f, 0x41, 1, 0xff],
+            &[0x78, 0x01, b'a'],
+            &[0xd8, 24, 0x41, 0x00],
+            &[0xd8, 24, 0xd8, 24, 0x41, 0x00],
+            &[0xd8, 24, 0xd9, 0xd9, 0xf7, 0x41, 0x00],
+            &[0x82, 0x01, 0x80],
+            &[0x9f, 0xff],
+            &[0x98, 0x01, 0x00],
+            &[0xa0],
+            &[0xbf, 0xff],
+            &[0xa2, 0x01, 0x00, 0x00, 0x00],
+            &[0xa2, 0x00, 0x00, 0x01, 0x00],
+            &[0xa2, 0x00, 0x00, 0x00, 0x00],
+            &[0xa2, 0x18, 100, 0x00, 0x20, 0x00],
+            &[0xa2, 0x20, 0x00, 0x18, 100, 0x00],
+            &[0xa1, 0x00, 0xa2, 0x62, b'a', b'a', 0x00, 0x61, b'b', 0x00],
+            &[0xa1, 0x00, 0xa2, 0x61, b'b', 0x00, 0x62, b'a', b'a', 0x00],
+            &[0xa2, 0x81, 0x00, 0x00, 0x80, 0x00],
+            &[0xf9, 0x3c, 0x00],
+            &[0xfa, 0x3f, 0x80, 0x00, 0x00],
+            &[0xfa, 0x3d, 0xcc, 0xcc, 0xcd],
+            &[0xfb, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0],
+            &[0xfb, 0x3f, 0xb9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a],
+            &[0xf9, 0x7e, 0x00],
+            &[0xfb, 0x7f, 0xf8, 0, 0, 0, 0, 0, 0],
+            &[0xf9, 0x7e, 0x01],
+            &[0xf4],
+            &[0xf8, 0x20],
+        ];
+        for bytes in inputs {
+            let cbor = Cbor::checked(bytes).unwrap();
+            for order in [
+                KeyOrder::Bytewise,
+                KeyOrder::LengthFirst,
+                KeyOrder::Preserve,
+            ] {
+                let expected =
+                    CborOwned::deterministic_with(bytes, order, &ValidationLimits::new())
+                        .is_ok_and(|d| d.as_slice() == *bytes);
+                assert_eq!(
+                    cbor.is_deterministic(order),
+                    expected,
+                    "{:?} {:?}",
+                    cbor,
+                    order
+                );
+            }
+        }
+    }
 
     #[test]
     fn remove_cbor_encoding() {
@@ -1,178 +1,606 @@
 use crate::{
+    canonical::Canonical,
     constants::*,
-    error::InternalError,
-    reader::{indefinite, integer, major},
-    validated::iterators::BytesIter,
-    Cbor,
-    ErrorKind::*,
-    ParseError,
-    WhileParsing::*,
+    limits::Budget,
+    reader::{careful_literal, indefinite, integer},
+    validated::skip,
+    Cbor, DuplicateKeys,
+    ErrorKind::{self, *},
+    ItemKind, ParseError, PathElement, Tags, ValidationLimits,
+    WhileParsing::{self, *},
 };
 use std::borrow::Cow;
 
-pub(crate) trait MkErr {
-    type Out;
-    fn header_value(self, bytes: &[u8]) -> Result<Self::Out, InternalError<'_>>;
+/// Validate the item at the start of `bytes`, returning it and the remaining bytes
+pub fn validate<'a>(
+    bytes: &'a [u8],
+    permit_suffix: bool,
+    limits: &ValidationLimits,
+) -> Result<(&'a Cbor, &'a [u8]), ParseError> {
+    let end = Parser::new(*limits)
+        .parse(bytes, None)
+        .map_err(Stop::into_error)?;
+    if end == bytes.len() || permit_suffix {
+        Ok((Cbor::unchecked(&bytes[..end]), &bytes[end..]))
+    } else {
+        Err(ParseError::new(end, TrailingGarbage))
+    }
 }
-impl<T> MkErr for Option<T> {
-    type Out = T;
-    fn header_value(self, bytes: &[u8]) -> Result<Self::Out, InternalError<'_>> {
-        self.ok_or_else(|| {
-            if bytes[0] & 31 > 27 {
-                InternalError::new(bytes, InvalidInfo)
-            } else {
-                InternalError::new(&bytes[bytes.len()..], UnexpectedEof(HeaderValue))
-            }
-        })
+
+/// Reason for the [`Parser`](struct.Parser.html) to stop before completing an item
+pub(crate) enum Stop {
+    /// the input is not well-formed or violates the limits
+    Invalid(ParseError),
+    /// the input ended before the item was complete, parsing may be resumed with more bytes
+    Incomplete(ParseError),
+}
+
+impl Stop {
+    pub fn into_error(self) -> ParseError {
+        match self {
+            Stop::Invalid(e) => e,
+            Stop::Incomplete(e) => e,
+        }
     }
 }
 
-fn frag_err(position: &[u8], is_string: bool) -> InternalError<'_> {
-    let w = if is_string {
-        StringFragment
-    } else {
-        BytesFragment
-    };
-    InternalError::new(position, UnexpectedEof(w))
+/// Reason for a single parsing step to stop, with offset relative to the current level
+enum Halt {
+    Error(usize, ErrorKind),
+    Eof(usize, WhileParsing),
 }
 
-pub(crate) fn value_bytes(
-    bytes: &[u8],
-    get_bytes: bool,
-    check_str: bool,
-) -> Result<(Cow<'_, [u8]>, &'_ [u8]), InternalError<'_>> {
-    let m = major(bytes).unwrap();
-    let (len, _, mut rest) = integer(bytes)
-        .or_else(|| indefinite(bytes))
-        .header_value(bytes)?;
-    if len == u64::MAX {
-        // since an item takes at least 1 byte, u64::MAX is an impossible size
-        let mut b = Vec::new();
-        while *rest.get(0).ok_or_else(|| frag_err(rest, check_str))? != STOP_BYTE {
-            if major(rest).unwrap() != m {
-                return Err(InternalError::new(rest, InvalidStringFragment));
-            }
-            let (len, _, r) = integer(rest).header_value(rest)?;
-            let len = len as usize;
-            if len > r.len() {
-                return Err(frag_err(r, check_str));
-            }
-            let s = &r[..len];
-            if check_str {
-                std::str::from_utf8(s).map_err(|e| InternalError::new(s, InvalidUtf8(e)))?;
+/// What the parser needs to do after a single step
+enum Action {
+    Continue,
+    Done,
+    /// start parsing the item contained in an indefinite size TAG_CBOR_ITEM byte string
+    Push(Level),
+    /// the item contained in the current level has been parsed
+    Pop,
+}
+
+/// A container or nested item that has been started but not yet finished
+enum Frame {
+    /// array or dict with declared number of items resp. pairs (`None` for indefinite size);
+    /// `items` counts keys and values separately, `key` is the position of the latest dict key,
+    /// `out` is the header position in the output
+    Container {
+        major: u8,
+        len: Option<u64>,
+        items: u64,
+        key: usize,
+        out: usize,
+    },
+    /// indefinite size string, collecting the contents only if needed
+    Fragments {
+        major: u8,
+        nested: bool,
+        header: usize,
+        bytes: Option<Vec<u8>>,
+    },
+    /// item within a definite size TAG_CBOR_ITEM byte string, remembering the outer limit
+    Nested { outer: Option<usize>, done: bool },
+    /// item within an indefinite size TAG_CBOR_ITEM byte string, see [`Level`](struct.Level.html)
+    Level { done: bool },
+}
+
+/// Contents of an indefinite size TAG_CBOR_ITEM byte string that is being parsed
+struct Level {
+    bytes: Vec<u8>,
+    /// position of the byte string header in the enclosing level
+    header: usize,
+    /// position after the byte string in the enclosing level
+    after: usize,
+    /// limit of the enclosing level
+    limit: Option<usize>,
+}
+
+/// Parsing state that doesn’t borrow the input bytes
+struct State {
+    budget: Budget,
+    stack: Vec<Frame>,
+    depth: usize,
+    /// position within the current level
+    pos: usize,
+    /// end of the current nested item; `None` means the end of the input
+    limit: Option<usize>,
+    done: bool,
+}
+
+/// Parser for a single top-level item that uses an explicit stack instead of recursion
+///
+/// All positions are kept as offsets, so that parsing can be resumed after
+/// [`Stop::Incomplete`](enum.Stop.html) once more bytes have been appended to the input.
+pub(crate) struct Parser {
+    levels: Vec<Level>,
+    state: State,
+    /// scratch output for finding duplicate keys while validating
+    keys: Option<Canonical>,
+    /// whether to record the path to the problem in errors
+    error_path: bool,
+}
+
+impl Parser {
+    pub fn new(limits: ValidationLimits) -> Self {
+        Self {
+            levels: Vec::new(),
+            state: State {
+                budget: Budget::new(limits),
+                stack: Vec::new(),
+                depth: 0,
+                pos: 0,
+                limit: None,
+                done: false,
+            },
+            keys: (limits.duplicate_keys() == DuplicateKeys::Reject)
+                .then(|| Canonical::new(Vec::new(), None, DuplicateKeys::Reject)),
+            error_path: limits.error_path(),
+        }
+    }
+
+    /// forget all progress, the next item is expected at the start of the input
+    pub fn reset(&mut self) {
+        self.levels.clear();
+        let state = &mut self.state;
+        state.budget.reset();
+        state.stack.clear();
+        state.depth = 0;
+        state.pos = 0;
+        state.limit = None;
+        state.done = false;
+        if let Some(keys) = &mut self.keys {
+            keys.clear();
+        }
+    }
+
+    /// Continue parsing the item at the start of `input`, returning its end offset.
+    ///
+    /// After an error other than [`Stop::Incomplete`](enum.Stop.html) the parser needs to be
+    /// [`reset`](#method.reset) before further use.
+    pub fn parse(&mut self, input: &[u8], out: Option<&mut Canonical>) -> Result<usize, Stop> {
+        let mut keys = self.keys.take();
+        let result = self.parse_into(input, out.or(keys.as_mut()));
+        self.keys = keys;
+        result.map_err(|stop| match stop {
+            _ if !self.error_path => stop,
+            Stop::Invalid(e) => Stop::Invalid(e.with_path(self.path(input))),
+            Stop::Incomplete(e) => Stop::Incomplete(e.with_path(self.path(input))),
+        })
+    }
+
+    /// the path from the top-level item to the item currently being parsed
+    fn path(&self, input: &[u8]) -> Vec<PathElement<'static>> {
+        let mut path = Vec::new();
+        let mut bytes = input;
+        let mut level = 0;
+        for frame in &self.state.stack {
+            match frame {
+                Frame::Level { .. } => {
+                    bytes = &self.levels[level].bytes;
+                    level += 1;
+                }
+                Frame::Container {
+                    major: MAJOR_ARRAY,
+                    items,
+                    ..
+                } => path.push(PathElement::Number(*items)),
+                Frame::Container { items, key, .. } if items % 2 == 1 => {
+                    let key = &bytes[*key..];
+                    let key = Cbor::unchecked(&key[..key.len() - skip(key).1.len()]);
+                    path.push(match key.kind() {
+                        ItemKind::Str(s) => {
+                            PathElement::String(Cow::Owned(s.as_cow().into_owned()))
+                        }
+                        ItemKind::Pos(n) => PathElement::Number(n),
+                        _ => PathElement::Item(Cow::Owned(key.to_owned())),
+                    });
+                }
+                // the problem lies within a key, or between dict entries
+                Frame::Container { .. } => break,
+                Frame::Fragments { .. } | Frame::Nested { .. } => {}
             }
-            if get_bytes {
-                b.extend_from_slice(s);
+        }
+        path
+    }
+
+    fn parse_into(&mut self, input: &[u8], mut out: Option<&mut Canonical>) -> Result<usize, Stop> {
+        let max_size = self.state.budget.max_size().unwrap_or(usize::MAX);
+        let input = &input[..input.len().min(max_size)];
+        loop {
+            let bytes = match self.levels.last() {
+                Some(level) => level.bytes.as_slice(),
+                None => input,
+            };
+            let bytes = &bytes[..self.state.limit.unwrap_or(bytes.len())];
+            match self.state.step(bytes, out.as_deref_mut()) {
+                Ok(Action::Continue) => {}
+                Ok(Action::Done) => return Ok(self.state.pos),
+                Ok(Action::Push(level)) => {
+                    let state = &mut self.state;
+                    state.stack.push(Frame::Level { done: false });
+                    state.depth += 1;
+                    state.pos = 0;
+                    state.limit = Some(level.bytes.len());
+                    self.levels.push(level);
+                }
+                Ok(Action::Pop) => {
+                    let level = self.levels.pop().unwrap();
+                    let state = &mut self.state;
+                    state.stack.pop();
+                    state.depth -= 1;
+                    state.pos = level.after;
+                    state.limit = level.limit;
+                    state.item_done();
+                }
+                Err(Halt::Eof(_, _)) if self.state.limit.is_none() && input.len() == max_size => {
+                    // the item would need bytes beyond the maximum size
+                    return Err(Stop::Invalid(ParseError::new(max_size, ItemTooLarge)));
+                }
+                Err(Halt::Eof(pos, w)) if self.state.limit.is_none() => {
+                    return Err(Stop::Incomplete(ParseError::new(pos, UnexpectedEof(w))))
+                }
+                Err(Halt::Eof(pos, w)) => {
+                    return Err(Stop::Invalid(self.error(input, pos, UnexpectedEof(w))))
+                }
+                Err(Halt::Error(pos, kind)) => {
+                    return Err(Stop::Invalid(self.error(input, pos, kind)))
+                }
             }
-            rest = &r[len..];
         }
-        Ok((Cow::Owned(b), &rest[1..]))
-    } else {
+    }
+
+    /// translate a position in the current level into an offset within the input
+    fn error(&self, input: &[u8], pos: usize, kind: ErrorKind) -> ParseError {
+        let mut offset = pos;
+        for (idx, level) in self.levels.iter().enumerate().rev() {
+            let outer = match idx {
+                0 => input,
+                _ => self.levels[idx - 1].bytes.as_slice(),
+            };
+            offset = fragment_offset(outer, level.header, offset).unwrap_or(level.after);
+        }
+        ParseError::new(offset, kind)
+    }
+}
+
+/// find the position of the `offset`-th content byte of the indefinite size byte string at `header`
+fn fragment_offset(bytes: &[u8], header: usize, mut offset: usize) -> Option<usize> {
+    let mut pos = header + 1;
+    while bytes[pos] != STOP_BYTE {
+        let (len, h, _) = integer(&bytes[pos..])?;
+        let start = pos + h.len();
         let len = len as usize;
-        if rest.len() >= len {
-            let s = &rest[..len];
-            if check_str {
-                std::str::from_utf8(s).map_err(|e| InternalError::new(s, InvalidUtf8(e)))?;
-            }
-            Ok((Cow::Borrowed(s), &rest[len..]))
-        } else {
-            let w = if check_str { StringValue } else { BytesValue };
-            Err(InternalError::new(rest, UnexpectedEof(w)))
+        if offset < len {
+            return Some(start + offset);
         }
+        offset -= len;
+        pos = start + len;
     }
+    None
 }
 
-pub fn validate(bytes: &[u8], permit_suffix: bool) -> Result<(&Cbor, &[u8]), ParseError> {
-    fn rec(bytes: &[u8], tag: Option<u64>) -> Result<(&'_ Cbor, &'_ [u8]), InternalError<'_>> {
-        let m = major(bytes).ok_or_else(|| InternalError::new(bytes, UnexpectedEof(ItemHeader)))?;
-        match m {
-            MAJOR_POS | MAJOR_NEG | MAJOR_LIT => integer(bytes)
-                .map(|(_, b, r)| (Cbor::unchecked(b), r))
-                .header_value(bytes),
-            MAJOR_BYTES | MAJOR_STR => {
-                let check = m == MAJOR_BYTES && tag == Some(TAG_CBOR_ITEM);
-                let (value, rest) = value_bytes(bytes, check, m == MAJOR_STR)?;
-                if check {
-                    rec(value.as_ref(), None)
-                        .and_then(|(_cbor, r)| {
-                            if r.is_empty() {
-                                Ok((Cbor::unchecked(&bytes[..bytes.len() - rest.len()]), rest))
-                            } else {
-                                Err(InternalError::new(r, TrailingGarbage))
-                            }
-                        })
-                        .map_err(|e| {
-                            let mut offset = e.offset(value.as_ref());
-                            let iter = if bytes[0] & 31 == INDEFINITE_SIZE {
-                                BytesIter::new(&bytes[1..], None)
-                            } else {
-                                BytesIter::new(bytes, Some(1))
-                            };
-                            for slice in iter {
-                                if offset < slice.len() {
-                                    return e.with_location(&slice[offset..]);
-                                }
-                                offset -= slice.len();
-                            }
-                            e.with_location(rest)
-                        })
-                } else {
-                    Ok((Cbor::unchecked(&bytes[..bytes.len() - rest.len()]), rest))
+/// parse an item header at `pos`, returning its value and length
+fn header(bytes: &[u8], pos: usize, indefinite_ok: bool) -> Result<(u64, usize), Halt> {
+    let b = &bytes[pos..];
+    let header = match integer(b) {
+        None if indefinite_ok => indefinite(b),
+        x => x,
+    };
+    match header {
+        Some((value, h, _)) => Ok((value, h.len())),
+        None if b[0] & 31 > 27 => Err(Halt::Error(pos, InvalidInfo)),
+        None => Err(Halt::Eof(bytes.len(), HeaderValue)),
+    }
+}
+
+impl State {
+    fn step(&mut self, bytes: &[u8], out: Option<&mut Canonical>) -> Result<Action, Halt> {
+        match self.stack.last() {
+            None if self.done => Ok(Action::Done),
+            None => self.item(bytes, out),
+            Some(Frame::Container {
+                major,
+                len,
+                items,
+                out: out_pos,
+                ..
+            }) => {
+                let (major, len, items, out_pos) = (*major, *len, *items, *out_pos);
+                let dict = major == MAJOR_DICT;
+                let at_key = !dict || items % 2 == 0;
+                let count = if dict { items / 2 } else { items };
+                if at_key {
+                    let finished = match len {
+                        Some(len) => count == len,
+                        None => bytes.get(self.pos) == Some(&STOP_BYTE),
+                    };
+                    if finished {
+                        if len.is_none() {
+                            self.pos += 1;
+                        }
+                        self.stack.pop();
+                        self.depth -= 1;
+                        if let Some(out) = out {
+                            out.close(out_pos, major, count)
+                                .map_err(|pos| Halt::Error(pos, DuplicateKey))?;
+                        }
+                        self.item_done();
+                        return Ok(Action::Continue);
+                    }
+                    if self.pos == bytes.len() {
+                        let w = if dict { DictItem } else { ArrayItem };
+                        return Err(Halt::Eof(self.pos, w));
+                    }
+                    if len.is_none() {
+                        self.budget
+                            .items(count + 1)
+                            .map_err(|kind| Halt::Error(self.pos, kind))?;
+                    }
                 }
+                if dict && at_key {
+                    if let Some(Frame::Container { key, .. }) = self.stack.last_mut() {
+                        *key = self.pos;
+                    }
+                }
+                let mut out = out;
+                if let Some(out) = out.as_deref_mut().filter(|_| dict) {
+                    if at_key {
+                        out.key(self.pos);
+                    } else {
+                        out.value();
+                    }
+                }
+                self.item(bytes, out)
             }
-            MAJOR_TAG => {
-                let (t, _, rest) = integer(bytes).header_value(bytes)?;
-                let tag = match tag {
-                    Some(_) => Some(u64::MAX),
-                    None => Some(t),
-                };
-                let (_cbor, rest) = rec(rest, tag)?;
-                Ok((Cbor::unchecked(&bytes[..bytes.len() - rest.len()]), rest))
+            Some(Frame::Fragments { .. }) => self.fragment(bytes, out),
+            Some(Frame::Nested { outer, done }) => {
+                if *done {
+                    if self.pos < bytes.len() {
+                        return Err(Halt::Error(self.pos, TrailingGarbage));
+                    }
+                    self.limit = *outer;
+                    self.stack.pop();
+                    self.depth -= 1;
+                    self.item_done();
+                    Ok(Action::Continue)
+                } else {
+                    self.item(bytes, out)
+                }
             }
-            MAJOR_ARRAY | MAJOR_DICT => {
-                let (len, _, mut rest) = integer(bytes)
-                    .or_else(|| indefinite(bytes))
-                    .header_value(bytes)?;
-                let w = if m == MAJOR_ARRAY {
-                    ArrayItem
+            Some(Frame::Level { done }) => {
+                if *done {
+                    if self.pos < bytes.len() {
+                        return Err(Halt::Error(self.pos, TrailingGarbage));
+                    }
+                    Ok(Action::Pop)
                 } else {
-                    DictItem
-                };
+                    self.item(bytes, out)
+                }
+            }
+        }
+    }
+
+    /// parse the tags and header of the item at the current position
+    fn item(&mut self, bytes: &[u8], out: Option<&mut Canonical>) -> Result<Action, Halt> {
+        let start = self.pos;
+        let mut pos = start;
+        let mut count = 0;
+        let mut tag = None;
+        loop {
+            let b = *bytes.get(pos).ok_or(Halt::Eof(pos, ItemHeader))?;
+            if b >> 5 != MAJOR_TAG {
+                break;
+            }
+            count += 1;
+            self.budget
+                .tags(count)
+                .map_err(|kind| Halt::Error(pos, kind))?;
+            let (t, len) = header(bytes, pos, false)?;
+            tag = if count == 1 { Some(t) } else { None };
+            pos += len;
+        }
+        let tags = Tags::new(&bytes[start..pos]);
+
+        let major = bytes[pos] >> 5;
+        match major {
+            MAJOR_POS | MAJOR_NEG => {
+                let (value, len) = header(bytes, pos, false)?;
+                if let Some(out) = out {
+                    out.int(tags, major, value);
+                }
+                self.pos = pos + len;
+                self.item_done();
+            }
+            MAJOR_LIT => {
+                let (_, len) = header(bytes, pos, false)?;
+                if let Some(out) = out {
+                    out.literal(tags, careful_literal(&bytes[pos..]).unwrap().0);
+                }
+                self.pos = pos + len;
+                self.item_done();
+            }
+            MAJOR_BYTES | MAJOR_STR => {
+                let nested = major == MAJOR_BYTES && tag == Some(TAG_CBOR_ITEM);
+                if nested {
+                    self.budget
+                        .enter(self.depth)
+                        .map_err(|kind| Halt::Error(pos, kind))?;
+                }
+                let (len, h) = header(bytes, pos, true)?;
                 if len == u64::MAX {
-                    while *rest
-                        .get(0)
-                        .ok_or_else(|| InternalError::new(rest, UnexpectedEof(w)))?
-                        != STOP_BYTE
-                    {
-                        rest = rec(rest, None).map(|x| x.1)?;
-                        if m == MAJOR_DICT {
-                            rest = rec(rest, None).map(|x| x.1)?;
-                        }
+                    // since an item takes at least 1 byte, u64::MAX is an impossible size
+                    let collect = nested || out.is_some();
+                    if let Some(out) = out.filter(|_| !nested) {
+                        out.tags(tags);
                     }
-                    let size = bytes.len() - rest.len() + 1;
-                    Ok((Cbor::unchecked(&bytes[..size]), &rest[1..]))
+                    self.stack.push(Frame::Fragments {
+                        major,
+                        nested,
+                        header: pos,
+                        bytes: collect.then(Vec::new),
+                    });
+                    self.pos = pos + h;
+                    return Ok(Action::Continue);
+                }
+                self.budget
+                    .string(len)
+                    .map_err(|kind| Halt::Error(pos, kind))?;
+                self.size(pos, pos + h, len)?;
+                let value = self.value(bytes, major, pos + h, len)?;
+                if nested {
+                    // parse the contained item in place, the limit ensures that it cannot exceed the string
+                    self.stack.push(Frame::Nested {
+                        outer: self.limit,
+                        done: false,
+                    });
+                    self.depth += 1;
+                    self.limit = Some(value.end);
+                    self.pos = value.start;
                 } else {
-                    for _ in 0..len {
-                        if rest.is_empty() {
-                            return Err(InternalError::new(rest, UnexpectedEof(w)));
-                        }
-                        rest = rec(rest, None).map(|x| x.1)?;
-                        if m == MAJOR_DICT {
-                            rest = rec(rest, None).map(|x| x.1)?;
-                        }
+                    if let Some(out) = out {
+                        out.tags(tags);
+                        out.string(major, &bytes[value.clone()]);
                     }
-                    let size = bytes.len() - rest.len();
-                    Ok((Cbor::unchecked(&bytes[..size]), rest))
+                    self.pos = value.end;
+                    self.item_done();
                 }
             }
+            MAJOR_ARRAY | MAJOR_DICT => {
+                self.budget
+                    .enter(self.depth)
+                    .map_err(|kind| Halt::Error(pos, kind))?;
+                let (len, h) = header(bytes, pos, true)?;
+                let len = if len == u64::MAX { None } else { Some(len) };
+                if let Some(len) = len {
+                    self.budget
+                        .items(len)
+                        .map_err(|kind| Halt::Error(pos, kind))?;
+                }
+                let out_pos = out.map(|out| out.open(tags, major)).unwrap_or_default();
+                self.stack.push(Frame::Container {
+                    major,
+                    len,
+                    items: 0,
+                    key: 0,
+                    out: out_pos,
+                });
+                self.depth += 1;
+                self.pos = pos + h;
+            }
             _ => unreachable!(),
         }
+        Ok(Action::Continue)
     }
-    let (cbor, rest) = rec(bytes, None).map_err(|e| e.rebase(bytes))?;
-    if rest.is_empty() || permit_suffix {
-        Ok((cbor, rest))
-    } else {
-        Err(InternalError::new(rest, TrailingGarbage).rebase(bytes))
+
+    /// parse the next fragment of an indefinite size string
+    fn fragment(&mut self, bytes: &[u8], out: Option<&mut Canonical>) -> Result<Action, Halt> {
+        let pos = self.pos;
+        let Some(Frame::Fragments { major, .. }) = self.stack.last() else {
+            unreachable!()
+        };
+        let major = *major;
+        let w = if major == MAJOR_STR {
+            StringFragment
+        } else {
+            BytesFragment
+        };
+        let b = *bytes.get(pos).ok_or(Halt::Eof(pos, w))?;
+        if b == STOP_BYTE {
+            self.pos += 1;
+            let Some(Frame::Fragments {
+                nested,
+                header,
+                bytes: contents,
+                ..
+            }) = self.stack.pop()
+            else {
+                unreachable!()
+            };
+            let contents = contents.unwrap_or_default();
+            if nested {
+                return Ok(Action::Push(Level {
+                    bytes: contents,
+                    header,
+                    after: self.pos,
+                    limit: self.limit,
+                }));
+            }
+            if let Some(out) = out {
+                out.string(major, &contents);
+            }
+            self.item_done();
+            return Ok(Action::Continue);
+        }
+        if b >> 5 != major {
+            return Err(Halt::Error(pos, InvalidStringFragment));
+        }
+        let (len, h) = header(bytes, pos, false)?;
+        self.budget
+            .string(len)
+            .map_err(|kind| Halt::Error(pos, kind))?;
+        self.size(pos, pos + h, len)?;
+        let value = self.value(bytes, major, pos + h, len)?;
+        if let Some(Frame::Fragments {
+            bytes: Some(contents),
+            ..
+        }) = self.stack.last_mut()
+        {
+            contents.extend_from_slice(&bytes[value.clone()]);
+        }
+        self.pos = value.end;
+        Ok(Action::Continue)
+    }
+
+    /// reject a string (fragment) at `pos` whose declared length reaches beyond the maximum item size
+    ///
+    /// This is only checked for the top-level input, strings within nested items are bounded by
+    /// their container.
+    fn size(&self, pos: usize, start: usize, len: u64) -> Result<(), Halt> {
+        match self.budget.max_size() {
+            Some(max)
+                if self.limit.is_none() && (start as u64).saturating_add(len) > max as u64 =>
+            {
+                Err(Halt::Error(pos, ItemTooLarge))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// check the contents of a definite size string (fragment) and account for them
+    fn value(
+        &mut self,
+        bytes: &[u8],
+        major: u8,
+        start: usize,
+        len: u64,
+    ) -> Result<std::ops::Range<usize>, Halt> {
+        if ((bytes.len() - start) as u64) < len {
+            let w = match (major == MAJOR_STR, self.stack.last()) {
+                (true, Some(Frame::Fragments { .. })) => StringFragment,
+                (false, Some(Frame::Fragments { .. })) => BytesFragment,
+                (true, _) => StringValue,
+                (false, _) => BytesValue,
+            };
+            return Err(Halt::Eof(start, w));
+        }
+        let end = start + len as usize;
+        if major == MAJOR_STR {
+            std::str::from_utf8(&bytes[start..end])
+                .map_err(|e| Halt::Error(start, InvalidUtf8(e)))?;
+        }
+        self.budget.add_string(len);
+        Ok(start..end)
+    }
+
+    /// record that an item has been completed within the innermost container
+    fn item_done(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Container { items, .. }) => *items += 1,
+            Some(Frame::Nested { done, .. }) | Some(Frame::Level { done }) => *done = true,
+            Some(Frame::Fragments { .. }) => unreachable!(),
+            None => self.done = true,
+        }
     }
 }
 
@@ -181,15 +609,16 @@ mod tests {
     use super::validate;
     use crate::{
         value::Number,
-        Cbor, CborBuilder, CborOwned, Encoder,
+        Cbor, CborBuilder, CborOwned, DuplicateKeys, Encoder,
         ErrorKind::{self, *},
+        KeyOrder, PathElement, ValidationLimits,
         WhileParsing::*,
         Writer,
     };
 
     fn t(bytes: impl AsRef<[u8]>) -> (usize, ErrorKind) {
         let bytes = bytes.as_ref();
-        let error = validate(bytes, false).unwrap_err();
+        let error = validate(bytes, false, &ValidationLimits::new()).unwrap_err();
         let error2 = CborOwned::canonical(bytes).unwrap_err();
         assert_eq!(error2, error, "canonical != checked");
         (error.offset(), error.kind())
@@ -325,7 +754,7 @@ mod tests {
     fn suffix() {
         fn t(bytes: impl AsRef<[u8]>) -> Result<usize, (usize, ErrorKind)> {
             let bytes = bytes.as_ref();
-            validate(bytes, true)
+            validate(bytes, true, &ValidationLimits::new())
                 .map(|(_cbor, rest)| rest.len())
                 .map_err(|error| {
                     let error2 = CborOwned::canonical(bytes).unwrap_err();
@@ -338,6 +767,237 @@ mod tests {
         assert_eq!(t([0xd8, 24, 0x42, 0x01, 2]), Err((4, TrailingGarbage)));
     }
 
+    #[test]
+    fn limits() {
+        fn l(bytes: impl AsRef<[u8]>, limits: ValidationLimits) -> Result<(), (usize, ErrorKind)> {
+            let bytes = bytes.as_ref();
+            let checked = validate(bytes, false, &limits).map(|_| ());
+            let canonical = CborOwned::canonical_with(bytes, &limits).map(|_| ());
+            assert_eq!(canonical, checked, "canonical != checked");
+            checked.map_err(|error| (error.offset(), error.kind()))
+        }
+
+        let depth = ValidationLimits::new().with_max_depth(Some(2));
+        assert_eq!(l([0x81, 0x81, 0], depth), Ok(()));
+        assert_eq!(l([0x81, 0x81, 0x81, 0], depth), Err((2, DepthExceeded)));
+        assert_eq!(l([0x81, 0xa1, 0, 0x80], depth), Err((3, DepthExceeded)));
+        assert_eq!(l([0x81, 0xd8, 24, 0x41, 0], depth), Ok(()));
+        assert_eq!(
+            l([0x81, 0xd8, 24, 0x42, 0x81, 0], depth),
+            Err((4, DepthExceeded))
+        );
+
+        let items = ValidationLimits::new().with_max_items(Some(2));
+        assert_eq!(l([0x82, 1, 2], items), Ok(()));
+        assert_eq!(l([0x83, 1, 2, 3], items), Err((0, TooManyItems)));
+        assert_eq!(l([0x9f, 1, 2, 3, 0xff], items), Err((3, TooManyItems)));
+        assert_eq!(l([0xa2, 1, 2, 3, 4], items), Ok(()));
+        assert_eq!(
+            l([0xbf, 1, 2, 3, 4, 5, 6, 0xff], items),
+            Err((5, TooManyItems))
+        );
+
+        let strings = ValidationLimits::new().with_max_string_bytes(Some(4));
+        assert_eq!(l([0x82, 0x42, 1, 2, 0x62, b'a', b'b'], strings), Ok(()));
+        assert_eq!(
+            l([0x82, 0x43, 1, 2, 3, 0x62, b'a', b'b'], strings),
+            Err((5, TooManyStringBytes))
+        );
+        assert_eq!(
+            l(
+                [0x7f, 0x62, b'a', b'b', 0x63, b'c', b'd', b'e', 0xff],
+                strings
+            ),
+            Err((4, TooManyStringBytes))
+        );
+        assert_eq!(
+            l([0x5b, 0, 0, 0, 1, 0, 0, 0, 0], strings),
+            Err((0, TooManyStringBytes))
+        );
+
+        let size = ValidationLimits::new().with_max_size(Some(3));
+        assert_eq!(l([0x82, 1, 2], size), Ok(()));
+        assert_eq!(l([0x83, 1, 2, 3], size), Err((3, ItemTooLarge)));
+        assert_eq!(l([0x5a, 0, 0, 0, 1, 0], size), Err((3, ItemTooLarge)));
+        assert_eq!(l([0x43, 0], size), Err((0, ItemTooLarge)));
+        assert_eq!(l([0x81, 0x5f, 0x41], size), Err((2, ItemTooLarge)));
+        assert_eq!(l([0x83, 1], size), Err((2, UnexpectedEof(ArrayItem))));
+
+        let tags = ValidationLimits::new().with_max_tags(Some(1));
+        assert_eq!(l([0xc1, 0], tags), Ok(()));
+        assert_eq!(l([0xc1, 0xc2, 0], tags), Err((1, TooManyTags)));
+        assert_eq!(
+            l([0x81, 0xd8, 42, 0xd9, 1, 2, 0], tags),
+            Err((3, TooManyTags))
+        );
+
+        let dups = ValidationLimits::new().with_duplicate_keys(DuplicateKeys::Reject);
+        assert_eq!(l([0xa2, 1, 0, 2, 0], dups), Ok(()));
+        assert_eq!(l([0xa2, 1, 0, 1, 0], dups), Err((3, DuplicateKey)));
+        assert_eq!(l([0xa3, 0, 0, 0, 1, 0, 2], dups), Err((3, DuplicateKey)));
+        assert_eq!(l([0xa2, 1, 0, 0x18, 1, 0], dups), Err((3, DuplicateKey)));
+        assert_eq!(
+            l([0xa2, 0x61, b'a', 0, 0x7f, 0x61, b'a', 0xff, 0], dups),
+            Err((4, DuplicateKey))
+        );
+        assert_eq!(
+            l([0xa2, 0xf9, 0x3c, 0, 0, 0xfa, 0x3f, 0x80, 0, 0, 0], dups),
+            Err((5, DuplicateKey))
+        );
+        assert_eq!(l([0xa2, 1, 0, 0xf9, 0x3c, 0, 0], dups), Ok(()));
+        assert_eq!(
+            l([0xa2, 0xa2, 0, 0, 1, 0, 0, 0xa2, 1, 0, 0, 0, 0], dups),
+            Err((7, DuplicateKey))
+        );
+        assert_eq!(
+            l([0x81, 0xbf, 0, 0, 0, 0, 0xff], dups),
+            Err((4, DuplicateKey))
+        );
+        assert_eq!(l([0xa2, 0, 0xa1, 0, 0, 1, 0xa1, 0, 0], dups), Ok(()));
+        assert_eq!(
+            l(
+                [0xa1, 0, 0xd8, 24, 0x5f, 0x42, 0xa2, 0, 0x41, 0, 0x42, 0, 0, 0xff],
+                dups
+            ),
+            Err((11, DuplicateKey))
+        );
+        // duplicates are only detected once the dict is complete
+        assert_eq!(
+            l([0xa3, 0, 0, 0, 0], dups),
+            Err((5, UnexpectedEof(DictItem)))
+        );
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let first = ValidationLimits::new().with_duplicate_keys(DuplicateKeys::KeepFirst);
+        let bytes = [0xa3, 0x61, b'a', 1, 0x61, b'b', 2, 0x61, b'a', 3];
+        assert!(validate(&bytes, false, &first).is_ok());
+        let cbor = CborOwned::canonical_with(bytes, &first).unwrap();
+        assert_eq!(cbor.to_string(), r#"{"a": 1, "b": 2}"#);
+        let cbor = CborOwned::deterministic_with(bytes, KeyOrder::Bytewise, &first).unwrap();
+        assert_eq!(cbor.to_string(), r#"{"a": 1, "b": 2}"#);
+        let error = CborOwned::deterministic(bytes).unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (7, DuplicateKey));
+
+        // the number of entries is adjusted
+        let mut bytes = vec![0xb8, 24];
+        for i in 0..23 {
+            bytes.extend_from_slice(&[i, 0x81, 0xa2, 0, 0, 0, 1]);
+        }
+        bytes.extend_from_slice(&[0x18, 22, 0xf6]);
+        let cbor = CborOwned::canonical_with(&bytes, &first).unwrap();
+        let mut expected = vec![0xb7];
+        for i in 0..23 {
+            expected.extend_from_slice(&[i, 0x81, 0xa1, 0, 0]);
+        }
+        assert_eq!(cbor.as_slice(), expected.as_slice());
+    }
+
+    #[test]
+    fn error_path() {
+        fn p(bytes: impl AsRef<[u8]>, limits: ValidationLimits) -> (usize, String) {
+            let bytes = bytes.as_ref();
+            let limits = limits.with_error_path(true);
+            let error = validate(bytes, false, &limits).unwrap_err();
+            let error2 = CborOwned::canonical_with(bytes, &limits).unwrap_err();
+            assert_eq!(error2, error, "canonical != checked");
+            let path = error.to_string();
+            let path = path.split(" (path ").nth(1).unwrap_or(")");
+            (error.offset(), path[..path.len() - 1].to_owned())
+        }
+        let none = ValidationLimits::new();
+
+        // {"a": [1, {"b": "\xff"}]}
+        let bytes = [0xa1, 0x61, b'a', 0x82, 1, 0xa1, 0x61, b'b', 0x61, 0xff];
+        assert_eq!(p(bytes, none), (9, ".a[1].b".to_owned()));
+        let error = validate(&bytes, false, &none).unwrap_err();
+        assert!(error.path().is_empty());
+        assert_eq!(error.to_string(), format!("{} at offset 9", error.kind()));
+
+        let error = validate(&bytes, false, &none.with_error_path(true)).unwrap_err();
+        assert_eq!(
+            error.path(),
+            [
+                PathElement::String("a".into()),
+                PathElement::Number(1),
+                PathElement::String("b".into())
+            ]
+        );
+
+        // problems within keys or between entries point to the dict
+        assert_eq!(
+            p([0xa1, 0x61, b'a', 0xa1, 0x61, 0xff, 1], none),
+            (5, ".a".to_owned())
+        );
+        assert_eq!(p([0x81, 0xbf, 0, 0, 0x1c], none), (4, "[0]".to_owned()));
+        // numeric and structured keys
+        assert_eq!(
+            p([0xa1, 1, 0xa1, 0x81, 1, 0x18], none),
+            (6, "[1][[1]]".to_owned())
+        );
+        // within a TAG_CBOR_ITEM byte string of indefinite size
+        assert_eq!(
+            p(
+                [0x81, 0xd8, 24, 0x5f, 0x42, 0x81, 0x61, 0x41, 0xff, 0xff],
+                none
+            ),
+            (8, "[0][0]".to_owned())
+        );
+        let dups = ValidationLimits::new().with_duplicate_keys(DuplicateKeys::Reject);
+        assert_eq!(p([0x81, 0xa2, 0, 0, 0, 1], dups), (4, "[0]".to_owned()));
+        assert_eq!(p([0x61, 0xff], none), (1, "".to_owned()));
+    }
+
+    const DEPTH: usize = 1_000_000;
+
+    #[test]
+    fn deep_nesting() {
+        let mut bytes = vec![0x81u8; DEPTH];
+        bytes.push(0);
+        assert!(validate(&bytes, false, &ValidationLimits::new()).is_ok());
+        let limits = ValidationLimits::new().with_max_depth(Some(DEPTH - 1));
+        let error = validate(&bytes, false, &limits).unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (DEPTH - 1, DepthExceeded));
+
+        bytes.pop();
+        assert_eq!(t(&bytes), (DEPTH, UnexpectedEof(ArrayItem)));
+
+        let mut bytes = vec![0x9fu8; DEPTH];
+        bytes.push(0xa1);
+        bytes.push(0);
+        bytes.push(0xf6);
+        bytes.extend(std::iter::repeat_n(0xff, DEPTH));
+        assert!(validate(&bytes, false, &ValidationLimits::new()).is_ok());
+        bytes.push(0xff);
+        assert_eq!(t(&bytes), (2 * DEPTH + 3, TrailingGarbage));
+
+        let mut bytes = [0xa1u8, 0].repeat(DEPTH);
+        bytes.push(0xd8);
+        assert_eq!(t(&bytes), (2 * DEPTH + 1, UnexpectedEof(HeaderValue)));
+    }
+
+    #[test]
+    fn deep_cbor_item_nesting() {
+        // nested definite size byte strings, built from the inside out
+        let mut headers = Vec::new();
+        let mut len = 1;
+        for _ in 0..DEPTH {
+            let mut header = vec![0xd8, 24];
+            crate::builder::low_level::write_info(&mut header, 2, len as u64);
+            len += header.len();
+            headers.push(header);
+        }
+        let mut bytes = headers.into_iter().rev().flatten().collect::<Vec<_>>();
+        bytes.push(0xf6);
+        assert!(validate(&bytes, false, &ValidationLimits::new()).is_ok());
+        let canonical = CborOwned::canonical(&bytes).unwrap();
+        assert_eq!(canonical.as_slice(), &[0xf6]);
+
+        *bytes.last_mut().unwrap() = 0x18;
+        assert_eq!(t(&bytes), (bytes.len(), UnexpectedEof(HeaderValue)));
+    }
+
     #[test]
     fn weird1() {
         let bytes = Cbor::checked(&[0xd8u8, 24, 0x5f, 0x41, 0x18, 0x41, 0x2a, 0xff]).unwrap();
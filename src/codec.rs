@@ -283,7 +283,7 @@ impl WriteCbor for String {
     }
 }
 
-impl<'a> WriteCbor for &'a str {
+impl WriteCbor for &str {
     fn write_cbor<W: Writer>(&self, w: W) -> W::Output {
         w.encode_str(self)
     }
@@ -793,12 +793,12 @@ mod tests {
     fn via() {
         assert_eq!(X::name(), "X");
         let bytes = X(5).write_cbor(CborBuilder::default());
-        let x = X::read_cbor(&*bytes).unwrap();
+        let x = X::read_cbor(&bytes).unwrap();
         assert_eq!(x, X(5));
 
         assert_eq!(Y::name(), "super::Y");
         let bytes = Y(5).write_cbor(CborBuilder::default());
-        let y = Y::read_cbor(&*bytes).unwrap();
+        let y = Y::read_cbor(&bytes).unwrap();
         assert_eq!(y, Y(5));
     }
 
@@ -806,7 +806,7 @@ mod tests {
     fn tuple() {
         assert_eq!(<(String, u64)>::name(), "(String, u64, )");
         let bytes = ("hello".to_owned(), 42u64).write_cbor(CborBuilder::default());
-        let tuple = <(String, u64)>::read_cbor(&*bytes).unwrap();
+        let tuple = <(String, u64)>::read_cbor(&bytes).unwrap();
         assert_eq!(tuple, ("hello".to_owned(), 42u64));
     }
 
@@ -815,7 +815,7 @@ mod tests {
         assert_eq!(<Vec<String>>::name(), "Vec<String>");
         let x = vec!["hello".to_owned(), "world".to_owned()];
         let bytes = x.write_cbor(CborBuilder::default());
-        let vec = <Vec<String>>::read_cbor(&*bytes).unwrap();
+        let vec = <Vec<String>>::read_cbor(&bytes).unwrap();
         assert_eq!(vec, x);
     }
 
@@ -824,12 +824,12 @@ mod tests {
         assert_eq!(<Option<String>>::name(), "Option<String>");
         let x = Some("hello".to_owned());
         let bytes = x.write_cbor(CborBuilder::default());
-        let opt = <Option<String>>::read_cbor(&*bytes).unwrap();
+        let opt = <Option<String>>::read_cbor(&bytes).unwrap();
         assert_eq!(opt, x);
 
         let x = None;
         let bytes = x.write_cbor(CborBuilder::default());
-        let opt = <Option<String>>::read_cbor(&*bytes).unwrap();
+        let opt = <Option<String>>::read_cbor(&bytes).unwrap();
         assert_eq!(opt, x);
     }
 
@@ -837,7 +837,7 @@ mod tests {
     fn int() {
         assert_eq!(u64::name(), "u64");
         let bytes = 42u64.write_cbor(CborBuilder::default());
-        let x = u64::read_cbor(&*bytes).unwrap();
+        let x = u64::read_cbor(&bytes).unwrap();
         assert_eq!(x, 42);
     }
 }
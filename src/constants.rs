@@ -41,6 +41,8 @@ pub const TAG_REGEX: u64 = 35;
 pub const TAG_MIME: u64 = 36;
 /// Marker for tagging the top-level CBOR item such that it cannot be misinterpreted as JSON
 pub const TAG_CBOR_MARKER: u64 = 55799;
+/// Marker for a CBOR sequence, applied to the first item as `55799(55800(h'424f52'))`, see [RFC 9277](https://www.rfc-editor.org/rfc/rfc9277)
+pub const TAG_CBOR_SEQ_MARKER: u64 = 55800;
 
 /// Simple value: FALSE
 pub const LIT_FALSE: u8 = 20;
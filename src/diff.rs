@@ -0,0 +1,510 @@
+//! Structural differences between two CBOR items
+//!
+//! [`diff`](fn.diff.html) walks both items in parallel and reports where they deviate: dict
+//! entries are matched by their keys, array items by their position or — using
+//! [`diff_with`](fn.diff_with.html) — by the value found at a key path within each item, like an
+//! `id` field. Values are compared by their meaning, so differences in encoding (like integer
+//! width, indefinite size strings or the order of dict entries) are not reported.
+//!
+//! The resulting changes can be turned into a [JSON Patch](https://www.rfc-editor.org/rfc/rfc6902)
+//! with [`to_patch`](fn.to_patch.html), which transforms the first item into the second one
+//! when applied with [`patch::apply`](../patch/fn.apply.html).
+//!
+//! ```rust
+//! use cbor_data::{CborBuilder, PathElement, Writer, diff::{diff, to_patch, Change}, patch};
+//!
+//! let a = CborBuilder::new().write_dict(None, |b| {
+//!     b.with_key("name", |b| b.write_str("a", None));
+//!     b.with_key("tags", |b| b.write_array(None, |b| {
+//!         b.write_str("x", None);
+//!     }));
+//! });
+//! let b = CborBuilder::new().write_dict(None, |b| {
+//!     b.with_key("name", |b| b.write_str("b", None));
+//!     b.with_key("tags", |b| b.write_array(None, |b| {
+//!         b.write_str("x", None);
+//!         b.write_str("y", None);
+//!     }));
+//! });
+//!
+//! let changes = diff(&a, &b);
+//! assert_eq!(changes.len(), 2);
+//! assert!(matches!(&changes[0], Change::Changed { path, .. } if path == &[PathElement::String("name".into())]));
+//! assert!(matches!(&changes[1], Change::Added { new, .. } if new.to_string() == r#""y""#));
+//!
+//! let patch = to_patch(&changes);
+//! assert_eq!(
+//!     patch.to_string(),
+//!     r#"[{"op": "replace", "path": "/name", "value": "b"}, {"op": "add", "path": "/tags/1", "value": "y"}]"#
+//! );
+//! assert_eq!(patch::apply(&a, &patch).unwrap(), b);
+//! ```
+use crate::{
+    render_pointer, semantic::semantic_eq, validated::Lengths, ArrayView, Cbor, CborBuilder,
+    CborOwned, DictView, PathElement, Semantic, Writer,
+};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+};
+
+/// A single difference found by [`diff`](fn.diff.html)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<'a> {
+    /// An array item or dict entry that only exists in the second item
+    Added {
+        path: Vec<PathElement<'a>>,
+        new: &'a Cbor,
+    },
+    /// An array item or dict entry that only exists in the first item
+    Removed {
+        path: Vec<PathElement<'a>>,
+        old: &'a Cbor,
+    },
+    /// A value that differs between both items, where they are not both arrays or both dicts
+    Changed {
+        path: Vec<PathElement<'a>>,
+        old: &'a Cbor,
+        new: &'a Cbor,
+    },
+}
+
+impl<'a> Change<'a> {
+    /// Location of the change
+    ///
+    /// The path of a removed array item refers to its index in the first item, all other array
+    /// indices refer to the second item. Dict keys other than untagged text strings are given as
+    /// [`PathElement::Item`](../enum.PathElement.html#variant.Item) so that they cannot be
+    /// mistaken for array indices, encoded as in the first item unless the entry was added.
+    pub fn path(&self) -> &[PathElement<'a>] {
+        match self {
+            Change::Added { path, .. } => path,
+            Change::Removed { path, .. } => path,
+            Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// How array items of both sides are paired up for comparison
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrayMatching<'p> {
+    /// Compare the items at the same index, surplus items are added or removed at the end.
+    Position,
+    /// Pair up items that have equal values at the given path, e.g. an `id` field of dicts.
+    ///
+    /// Items without value at this path or without partner are added or removed. Partners
+    /// that would need to change their relative order are reported as removed and added as well.
+    /// Arrays in which no item on either side has a value at this path are compared by position.
+    Key(Vec<PathElement<'p>>),
+}
+
+/// Compute the differences between two items, matching array items by position.
+pub fn diff<'a>(a: &'a Cbor, b: &'a Cbor) -> Vec<Change<'a>> {
+    diff_with(a, b, &ArrayMatching::Position)
+}
+
+/// Compute the differences between two items, matching array items as specified.
+///
+/// Changes are listed in an order in which they can be applied to the first item: within an
+/// array the removals come first, from highest index to lowest, then the additions and then the
+/// changes within the remaining items.
+///
+/// ```rust
+/// use cbor_data::{CborOwned, diff::{diff_with, to_patch, ArrayMatching}, index_str};
+/// use serde_json::json;
+///
+/// let a = CborOwned::canonical(serde_cbor::to_vec(&json!([{"id": 1, "v": 1}, {"id": 2, "v": 2}])).unwrap()).unwrap();
+/// let b = CborOwned::canonical(serde_cbor::to_vec(&json!([{"id": 2, "v": 3}])).unwrap()).unwrap();
+///
+/// let by_key = ArrayMatching::Key(index_str("id").collect());
+/// assert_eq!(
+///     to_patch(&diff_with(&a, &b, &by_key)).to_string(),
+///     r#"[{"op": "remove", "path": "/0"}, {"op": "replace", "path": "/0/v", "value": 3}]"#
+/// );
+/// assert_eq!(
+///     to_patch(&diff_with(&a, &b, &ArrayMatching::Position)).to_string(),
+///     r#"[{"op": "remove", "path": "/1"}, {"op": "replace", "path": "/0/id", "value": 2}, {"op": "replace", "path": "/0/v", "value": 3}]"#
+/// );
+/// ```
+pub fn diff_with<'a>(a: &'a Cbor, b: &'a Cbor, matching: &ArrayMatching<'_>) -> Vec<Change<'a>> {
+    let mut changes = Vec::new();
+    let mut lengths = Lengths::default();
+    // path of the pair being compared, the pairs below only store their last path element
+    let mut path = Vec::new();
+    // pairs of items still to be compared in reverse order, with the path length of their parent
+    let mut todo = vec![(0, None, a, b)];
+    while let Some((depth, elem, a, b)) = todo.pop() {
+        path.truncate(depth);
+        path.extend(elem);
+        let depth = path.len();
+        let nested = todo.len();
+        if let (Some(a), Some(b)) = (a.as_dict_view(), b.as_dict_view()) {
+            let (a, b) = (entries(a, &mut lengths), entries(b, &mut lengths));
+            // like for indexing only the first occurrence of a key is considered
+            let (a_keys, b_keys) = (first_keys(&a), first_keys(&b));
+            for (key, old) in a.iter().copied() {
+                if !b_keys.contains_key(&Semantic(key)) {
+                    let path = child(&path, key_element(key));
+                    changes.push(Change::Removed { path, old });
+                }
+            }
+            for (idx, (key, new)) in b.iter().copied().enumerate() {
+                if b_keys[&Semantic(key)] != idx {
+                    continue;
+                }
+                match a_keys.get(&Semantic(key)) {
+                    Some(&i) => todo.push((depth, Some(key_element(a[i].0)), a[i].1, new)),
+                    None => changes.push(Change::Added {
+                        path: child(&path, key_element(key)),
+                        new,
+                    }),
+                }
+            }
+        } else if let (Some(a), Some(b)) = (a.as_array_view(), b.as_array_view()) {
+            let (a, b) = (items(a, &mut lengths), items(b, &mut lengths));
+            let pairs = match matching {
+                ArrayMatching::Position => (0..a.len().min(b.len())).map(|i| (i, i)).collect(),
+                ArrayMatching::Key(key) => match_keys(&a, &b, key),
+            };
+            let mut old = vec![true; a.len()];
+            let mut new = vec![true; b.len()];
+            for (i, j) in &pairs {
+                old[*i] = false;
+                new[*j] = false;
+            }
+            for (idx, item) in a.iter().enumerate().rev().filter(|(i, _)| old[*i]) {
+                let path = child(&path, PathElement::Number(idx as u64));
+                changes.push(Change::Removed { path, old: item });
+            }
+            for (idx, item) in b.iter().enumerate().filter(|(i, _)| new[*i]) {
+                let path = child(&path, PathElement::Number(idx as u64));
+                changes.push(Change::Added { path, new: item });
+            }
+            for (i, j) in pairs {
+                todo.push((depth, Some(PathElement::Number(j as u64)), a[i], b[j]));
+            }
+        } else if !semantic_eq(a, b) {
+            changes.push(Change::Changed {
+                path: path.clone(),
+                old: a,
+                new: b,
+            });
+        }
+        todo[nested..].reverse();
+    }
+    changes
+}
+
+/// Render the changes as a JSON Patch, i.e. an array of operation dicts.
+///
+/// Paths are rendered with [`render_pointer`](../fn.render_pointer.html) as long as they only
+/// contain text keys and array indices. A JSON Pointer cannot address any other dict key, so
+/// paths containing a [`PathElement::Item`](../enum.PathElement.html#variant.Item) (or a
+/// [`PathElement::FromEnd`](../enum.PathElement.html#variant.FromEnd) in changes not created by
+/// [`diff`](fn.diff.html)) are written as an array of keys instead, which
+/// [`patch::apply`](../patch/fn.apply.html) also accepts.
+///
+/// ```rust
+/// use cbor_data::{CborBuilder, Writer, diff::{diff, to_patch}, patch};
+///
+/// let a = CborBuilder::new().write_dict(None, |b| {
+///     b.with_cbor_key(|b| b.write_pos(1, None), |b| b.write_str("a", None));
+/// });
+/// let b = CborBuilder::new().write_dict(None, |b| {
+///     b.with_cbor_key(|b| b.write_pos(1, None), |b| b.write_str("b", None));
+/// });
+///
+/// let patch = to_patch(&diff(&a, &b));
+/// assert_eq!(patch.to_string(), r#"[{"op": "replace", "path": [1], "value": "b"}]"#);
+/// assert_eq!(patch::apply(&a, &patch).unwrap(), b);
+/// ```
+pub fn to_patch(changes: &[Change<'_>]) -> CborOwned {
+    CborBuilder::new().write_array(None, |b| {
+        for change in changes {
+            let (op, value) = match change {
+                Change::Added { new, .. } => ("add", Some(new)),
+                Change::Removed { .. } => ("remove", None),
+                Change::Changed { new, .. } => ("replace", Some(new)),
+            };
+            b.write_dict(None, |b| {
+                b.with_key("op", |b| b.write_str(op, None));
+                b.with_key("path", |b| write_path(b, change.path()));
+                if let Some(value) = value {
+                    b.with_key("value", |b| b.write_item(value));
+                }
+            });
+        }
+    })
+}
+
+fn write_path<W: Writer>(b: W, path: &[PathElement<'_>]) -> W::Output {
+    let pointer =
+        |elem: &PathElement<'_>| matches!(elem, PathElement::String(_) | PathElement::Number(_));
+    if path.iter().all(pointer) {
+        return b.write_str(&render_pointer(path), None);
+    }
+    b.write_array(None, |b| {
+        for elem in path {
+            match elem {
+                PathElement::String(s) => b.write_str(s, None),
+                PathElement::Number(n) => b.write_pos(*n, None),
+                PathElement::Item(key) => b.write_item(key),
+                PathElement::FromEnd(n) => b.write_neg(n.saturating_sub(1), None),
+            };
+        }
+    })
+}
+
+/// dict keys that a JSON Pointer could mistake for array indices are kept as encoded
+fn key_element(key: &Cbor) -> PathElement<'_> {
+    match PathElement::from_key(key) {
+        PathElement::Number(_) => PathElement::Item(Cow::Borrowed(key)),
+        elem => elem,
+    }
+}
+
+fn child<'a>(path: &[PathElement<'a>], elem: PathElement<'a>) -> Vec<PathElement<'a>> {
+    let mut path = path.to_vec();
+    path.push(elem);
+    path
+}
+
+fn items<'a>(array: ArrayView<'a>, lengths: &mut Lengths<'a>) -> Vec<&'a Cbor> {
+    let mut iter = array.iter();
+    std::iter::from_fn(|| iter.next_with(lengths)).collect()
+}
+
+fn entries<'a>(dict: DictView<'a>, lengths: &mut Lengths<'a>) -> Vec<(&'a Cbor, &'a Cbor)> {
+    let mut iter = dict.iter();
+    std::iter::from_fn(|| iter.next_with(lengths)).collect()
+}
+
+/// index of the first entry for each key
+fn first_keys<'a>(entries: &[(&'a Cbor, &'a Cbor)]) -> HashMap<Semantic<&'a Cbor>, usize> {
+    let mut keys = HashMap::with_capacity(entries.len());
+    for (idx, (key, _)) in entries.iter().enumerate() {
+        keys.entry(Semantic(*key)).or_insert(idx);
+    }
+    keys
+}
+
+/// pair up items with equal keys, keeping only the largest set of pairs that are in the same
+/// relative order on both sides
+fn match_keys(a: &[&Cbor], b: &[&Cbor], key: &[PathElement<'_>]) -> Vec<(usize, usize)> {
+    let b_keys = b
+        .iter()
+        .map(|item| item.index(key.iter().cloned()))
+        .collect::<Vec<_>>();
+    let a_keys = a
+        .iter()
+        .map(|item| item.index(key.iter().cloned()))
+        .collect::<Vec<_>>();
+    if a_keys.iter().chain(b_keys.iter()).all(Option::is_none) {
+        return (0..a.len().min(b.len())).map(|i| (i, i)).collect();
+    }
+    // indices of the items with each key on the right side, to be taken from the front
+    let mut b_items = HashMap::<_, VecDeque<usize>>::new();
+    for (j, k) in b_keys.iter().enumerate() {
+        if let Some(k) = k {
+            b_items.entry(Semantic(&**k)).or_default().push_back(j);
+        }
+    }
+    let mut pairs = Vec::new();
+    for (i, k) in a_keys.iter().enumerate() {
+        let found = k
+            .as_ref()
+            .and_then(|k| b_items.get_mut(&Semantic(&**k)))
+            .and_then(VecDeque::pop_front);
+        if let Some(j) = found {
+            pairs.push((i, j));
+        }
+    }
+    longest_increasing(&pairs)
+}
+
+/// the longest subsequence of pairs (sorted by first element) whose second elements increase
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // tails[k] is the index of the pair ending the best known subsequence of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; pairs.len()];
+    for (idx, (_, j)) in pairs.iter().enumerate() {
+        let len = tails.partition_point(|t| pairs[*t].1 < *j);
+        prev[idx] = len.checked_sub(1).map(|l| tails[l]);
+        if len == tails.len() {
+            tails.push(idx);
+        } else {
+            tails[len] = idx;
+        }
+    }
+    let mut result = Vec::with_capacity(tails.len());
+    let mut next = tails.last().copied();
+    while let Some(idx) = next {
+        result.push(pairs[idx]);
+        next = prev[idx];
+    }
+    result.reverse();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, diff_with, longest_increasing, to_patch, ArrayMatching, Change};
+    use crate::{index_str, patch, Cbor, CborOwned, PathElement};
+    use serde_json::{json, Value};
+    use std::borrow::Cow;
+
+    fn c(json: Value) -> CborOwned {
+        CborOwned::canonical(serde_cbor::to_vec(&json).unwrap()).unwrap()
+    }
+
+    fn check(a: Value, b: Value, matching: &ArrayMatching<'_>, patch: Value) {
+        let (a, b) = (c(a), c(b));
+        let changes = diff_with(&a, &b, matching);
+        let rendered = to_patch(&changes);
+        assert_eq!(rendered, c(patch), "{}", rendered);
+        let patched = patch::apply(&a, &rendered).unwrap();
+        assert!(diff(&patched, &b).is_empty(), "{} != {}", patched, b);
+    }
+
+    #[test]
+    fn positions() {
+        let p = &ArrayMatching::Position;
+        check(json!({"a": 1}), json!({"a": 1}), p, json!([]));
+        check(
+            json!({"a": 1, "b": [1, 2, 3]}),
+            json!({"b": [1, 5], "c": null}),
+            p,
+            json!([
+                {"op": "remove", "path": "/a"},
+                {"op": "add", "path": "/c", "value": null},
+                {"op": "remove", "path": "/b/2"},
+                {"op": "replace", "path": "/b/1", "value": 5},
+            ]),
+        );
+        check(
+            json!([[1], {"x": [true]}]),
+            json!([[1, 2, 3], {"x": {"y": false}}, "z"]),
+            p,
+            json!([
+                {"op": "add", "path": "/2", "value": "z"},
+                {"op": "add", "path": "/0/1", "value": 2},
+                {"op": "add", "path": "/0/2", "value": 3},
+                {"op": "replace", "path": "/1/x", "value": {"y": false}},
+            ]),
+        );
+        check(
+            json!([1]),
+            json!({"0": 1}),
+            p,
+            json!([{"op": "replace", "path": "", "value": {"0": 1}}]),
+        );
+    }
+
+    #[test]
+    fn keys() {
+        let k = &ArrayMatching::Key(index_str("id").collect());
+        check(
+            // only one of the items with id 2 and 3 can keep its place
+            json!([{"id": 1}, {"id": 2, "v": 0}, {"id": 3}, {"x": 0}]),
+            json!([{"id": 3}, {"id": 4}, {"id": 2, "v": 1}]),
+            k,
+            json!([
+                {"op": "remove", "path": "/3"},
+                {"op": "remove", "path": "/1"},
+                {"op": "remove", "path": "/0"},
+                {"op": "add", "path": "/1", "value": {"id": 4}},
+                {"op": "add", "path": "/2", "value": {"id": 2, "v": 1}},
+            ]),
+        );
+        check(
+            json!({"l": [{"id": 1, "v": [0]}, {"id": 2}, {"id": 3, "v": 0}]}),
+            json!({"l": [{"id": 0}, {"id": 1, "v": [1]}, {"id": 3, "v": 0}, {"id": 5}]}),
+            k,
+            json!([
+                {"op": "remove", "path": "/l/1"},
+                {"op": "add", "path": "/l/0", "value": {"id": 0}},
+                {"op": "add", "path": "/l/3", "value": {"id": 5}},
+                {"op": "replace", "path": "/l/1/v/0", "value": 1},
+            ]),
+        );
+    }
+
+    #[test]
+    fn encodings() {
+        // {1: 0x1801, "a": (_ "b")} vs {"a": "b", 1: 1}
+        let a = Cbor::checked(&[0xa2, 1, 0x18, 1, 0x61, b'a', 0x7f, 0x61, b'b', 0xff]).unwrap();
+        let b = Cbor::checked(&[0xa2, 0x61, b'a', 0x61, b'b', 1, 1]).unwrap();
+        assert_eq!(diff(a, b), []);
+        let c = Cbor::checked(&[0xa2, 0x61, b'a', 0x61, b'b', 1, 2]).unwrap();
+        assert_eq!(
+            diff(a, c),
+            [Change::Changed {
+                path: vec![PathElement::Item(Cow::Borrowed(Cbor::unchecked(&[1])))],
+                old: Cbor::checked(&[0x18, 1]).unwrap(),
+                new: Cbor::checked(&[2]).unwrap(),
+            }]
+        );
+    }
+
+    #[test]
+    fn integer_keys() {
+        // {1: {2: 3}, "a": [1], -1: 0} vs {1: {2: 4}, "a": [2], 5: 6}
+        let a = Cbor::checked(&[0xa3, 1, 0xa1, 2, 3, 0x61, b'a', 0x81, 1, 0x20, 0]).unwrap();
+        let b = Cbor::checked(&[0xa3, 1, 0xa1, 2, 4, 0x61, b'a', 0x81, 2, 5, 6]).unwrap();
+        let patch = to_patch(&diff(a, b));
+        assert_eq!(
+            patch.to_string(),
+            r#"[{"op": "remove", "path": [-1]}, {"op": "add", "path": [5], "value": 6}, {"op": "replace", "path": [1, 2], "value": 4}, {"op": "replace", "path": "/a/0", "value": 2}]"#
+        );
+        let patched = patch::apply(a, &patch).unwrap();
+        assert!(diff(&patched, b).is_empty(), "{} != {}", patched, b);
+
+        // [{1: [5]}] vs [{1: [6]}]: array indices in key paths are integers as well
+        let a = Cbor::checked(&[0x81, 0xa1, 1, 0x81, 5]).unwrap();
+        let b = Cbor::checked(&[0x81, 0xa1, 1, 0x81, 6]).unwrap();
+        let patch = to_patch(&diff(a, b));
+        assert_eq!(
+            patch.to_string(),
+            r#"[{"op": "replace", "path": [0, 1, 0], "value": 6}]"#
+        );
+        assert_eq!(&*patch::apply(a, &patch).unwrap(), b);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        const DEPTH: usize = 100_000;
+        // [{0: [{0: … 1 …}, 1]}, 1] vs the same with 2 innermost
+        let nest = |inner: u8| {
+            let mut bytes = [0x82u8, 0xa1, 0].repeat(DEPTH);
+            bytes.push(inner);
+            bytes.extend(vec![1u8; DEPTH]);
+            bytes
+        };
+        let (a, b) = (nest(1), nest(2));
+        let changes = diff(Cbor::unchecked(&a), Cbor::unchecked(&b));
+        let key = PathElement::Item(Cow::Borrowed(Cbor::unchecked(&[0])));
+        let path: Vec<_> = [PathElement::Number(0), key]
+            .iter()
+            .cycle()
+            .take(2 * DEPTH)
+            .cloned()
+            .collect();
+        assert_eq!(
+            changes,
+            [Change::Changed {
+                path,
+                old: Cbor::unchecked(&[1]),
+                new: Cbor::unchecked(&[2]),
+            }]
+        );
+    }
+
+    #[test]
+    fn lis() {
+        assert_eq!(longest_increasing(&[]), []);
+        assert_eq!(
+            longest_increasing(&[(0, 3), (1, 0), (2, 1), (3, 4), (4, 2)]),
+            [(1, 0), (2, 1), (4, 2)]
+        );
+    }
+}
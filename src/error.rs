@@ -1,6 +1,9 @@
+use crate::PathElement;
 use std::{
     fmt::{Debug, Display},
+    io,
     str::Utf8Error,
+    sync::Arc,
 };
 
 /// What the parser was looking for when bytes ran out
@@ -33,6 +36,25 @@ pub enum ErrorKind {
     /// This error can be flagged also at the end of a TAG_CBOR_ITEM byte string, i.e.
     /// in the middle of the validated bytes.
     UnexpectedEof(WhileParsing),
+    /// arrays, dicts, or nested CBOR items are nested more deeply than permitted by the
+    /// [`ValidationLimits`](struct.ValidationLimits.html)
+    DepthExceeded,
+    /// an array or dict contains more items than permitted by the
+    /// [`ValidationLimits`](struct.ValidationLimits.html)
+    TooManyItems,
+    /// text and byte strings contain more bytes in total than permitted by the
+    /// [`ValidationLimits`](struct.ValidationLimits.html)
+    TooManyStringBytes,
+    /// an item carries more tags than permitted by the [`ValidationLimits`](struct.ValidationLimits.html)
+    TooManyTags,
+    /// the encoding of an item is longer than permitted by the [`ValidationLimits`](struct.ValidationLimits.html)
+    ItemTooLarge,
+    /// a dict contains the same key more than once, reported at the second occurrence
+    DuplicateKey,
+    /// reading the bytes failed, see [`CborSeqReader`](struct.CborSeqReader.html)
+    ///
+    /// The error itself is available from [`ParseError::io_error`](struct.ParseError.html#method.io_error).
+    Io(std::io::ErrorKind),
 }
 
 impl Display for ErrorKind {
@@ -43,18 +65,56 @@ impl Display for ErrorKind {
             ErrorKind::InvalidStringFragment => write!(f, "string fragment of wrong major type"),
             ErrorKind::InvalidUtf8(e) => write!(f, "UTF-8 error `{}`", e),
             ErrorKind::UnexpectedEof(w) => write!(f, "ran out of bytes while parsing {:?}", w),
+            ErrorKind::DepthExceeded => write!(f, "maximum nesting depth exceeded"),
+            ErrorKind::TooManyItems => write!(f, "maximum number of items exceeded"),
+            ErrorKind::TooManyStringBytes => write!(f, "maximum number of string bytes exceeded"),
+            ErrorKind::TooManyTags => write!(f, "maximum number of tags exceeded"),
+            ErrorKind::ItemTooLarge => write!(f, "maximum item size exceeded"),
+            ErrorKind::DuplicateKey => write!(f, "duplicate dict key"),
+            ErrorKind::Io(kind) => write!(f, "I/O error `{}`", kind),
         }
     }
 }
 
 /// Error container for parsing problems
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone)]
 pub struct ParseError {
     offset: usize,
     kind: ErrorKind,
+    path: Vec<PathElement<'static>>,
+    io: Option<Arc<io::Error>>,
 }
 
 impl ParseError {
+    pub(crate) fn new(offset: usize, kind: ErrorKind) -> Self {
+        Self {
+            offset,
+            kind,
+            path: Vec::new(),
+            io: None,
+        }
+    }
+
+    pub(crate) fn io(offset: usize, error: io::Error) -> Self {
+        let kind = ErrorKind::Io(error.kind());
+        Self {
+            io: Some(Arc::new(error)),
+            ..Self::new(offset, kind)
+        }
+    }
+
+    pub(crate) fn with_path(self, path: Vec<PathElement<'static>>) -> Self {
+        Self { path, ..self }
+    }
+
+    /// move the offset by `delta`, e.g. when the parsed bytes started in the middle of a stream
+    pub(crate) fn shifted(self, delta: usize) -> Self {
+        Self {
+            offset: self.offset + delta,
+            ..self
+        }
+    }
+
     /// Get a reference to the parse error's offset.
     pub fn offset(&self) -> usize {
         self.offset
@@ -64,50 +124,67 @@ impl ParseError {
     pub fn kind(&self) -> ErrorKind {
         self.kind.clone()
     }
-}
 
-impl Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} at offset {}", self.kind, self.offset)
+    /// Get the path from the top-level item to the array item or dict value containing the problem.
+    ///
+    /// The path is only recorded when requested with
+    /// [`ValidationLimits::with_error_path`](struct.ValidationLimits.html#method.with_error_path),
+    /// otherwise it is empty. It is also empty when the problem lies in the top-level item itself.
+    /// Array indices are given as [`PathElement::Number`](enum.PathElement.html#variant.Number),
+    /// dict keys as the element that [`Cbor::index`](struct.Cbor.html#method.index) would use to
+    /// find the value again. A problem within a dict key yields the path to the dict.
+    pub fn path(&self) -> &[PathElement<'static>] {
+        &self.path
     }
-}
-
-impl std::error::Error for ParseError {}
 
-impl Debug for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(self, f)
+    /// Get the error of the underlying reader if the kind is [`ErrorKind::Io`](enum.ErrorKind.html#variant.Io).
+    pub fn io_error(&self) -> Option<&io::Error> {
+        self.io.as_deref()
     }
 }
 
-pub(crate) struct InternalError<'a> {
-    position: &'a [u8],
-    kind: ErrorKind,
+/// I/O errors cannot be compared, so only their kind is taken into account
+impl PartialEq for ParseError {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset && self.kind == other.kind && self.path == other.path
+    }
 }
 
-impl<'a> InternalError<'a> {
-    pub fn new(position: &'a [u8], kind: ErrorKind) -> Self {
-        Self { position, kind }
-    }
+impl Eq for ParseError {}
 
-    pub fn offset(&self, base: &[u8]) -> usize {
-        let position = self.position as *const _ as *const u8;
-        let base = base as *const _ as *const u8;
-        // safety: self.position is a subslice of base
-        unsafe { position.offset_from(base) as usize }
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.io {
+            Some(e) => write!(f, "I/O error `{}`", e)?,
+            None => write!(f, "{}", self.kind)?,
+        }
+        write!(f, " at offset {}", self.offset)?;
+        if !self.path.is_empty() {
+            write!(f, " (path ")?;
+            for elem in &self.path {
+                match elem {
+                    PathElement::String(s) => write!(f, ".{}", s)?,
+                    PathElement::Number(n) => write!(f, "[{}]", n)?,
+                    PathElement::Item(c) => write!(f, "[{}]", c)?,
+                    PathElement::FromEnd(n) => write!(f, "[-{}]", n)?,
+                }
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
     }
+}
 
-    pub fn with_location(self, loc: &[u8]) -> InternalError<'_> {
-        InternalError {
-            position: loc,
-            kind: self.kind,
-        }
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io
+            .as_deref()
+            .map(|e| e as &(dyn std::error::Error + 'static))
     }
+}
 
-    pub fn rebase(self, base: &[u8]) -> ParseError {
-        ParseError {
-            offset: self.offset(base),
-            kind: self.kind,
-        }
+impl Debug for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
     }
 }
@@ -0,0 +1,297 @@
+use crate::{constants::TAG_CBOR_ITEM, Cbor, ItemKind, PathElement};
+use std::{
+    borrow::{Borrow, Cow},
+    convert::TryFrom,
+};
+
+/// Index over the arrays and dicts within a CBOR item for fast repeated access
+///
+/// [`Cbor::index`](struct.Cbor.html#method.index) needs to skip over all items preceding the one
+/// that is sought, which gets expensive for large arrays or dicts that are accessed many times.
+/// This wrapper records the position of every array item and every dict entry once, so that
+/// [`index`](#method.index) afterwards takes constant time per array step and logarithmic time
+/// per dict step. The results are the same as those of `Cbor::index`, including the choice of the
+/// first entry for a key that occurs more than once in a dict.
+///
+/// ```rust
+/// use cbor_data::{CborBuilder, IndexedCbor, Writer, index_str};
+///
+/// let cbor = CborBuilder::new().write_dict(None, |builder| {
+///     builder.with_key("items", |builder| {
+///         builder.write_array(None, |builder| {
+///             for i in 0..100_000 {
+///                 builder.write_pos(i, None);
+///             }
+///         })
+///     });
+/// });
+///
+/// let indexed = IndexedCbor::new(&cbor);
+/// let item = indexed.index(index_str("items[90000]")).unwrap();
+/// assert_eq!(item.to_string(), "90000");
+/// ```
+#[derive(Debug, Clone)]
+pub struct IndexedCbor<'a> {
+    cbor: &'a Cbor,
+    root: Slot<'a>,
+    nodes: Vec<Node<'a>>,
+}
+
+/// an item, with [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings of
+/// definite size already replaced by their contents
+#[derive(Debug, Clone, Copy)]
+struct Slot<'a> {
+    cbor: &'a Cbor,
+    /// index into `nodes` if this is an indexed array or dict
+    node: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Node<'a> {
+    Array(Vec<Slot<'a>>),
+    Dict(Dict<'a>),
+}
+
+/// the key lookup tables are sorted by key and then by position within the dict
+#[derive(Debug, Clone, Default)]
+struct Dict<'a> {
+    values: Vec<Slot<'a>>,
+    strings: Vec<(Cow<'a, str>, usize)>,
+    numbers: Vec<(u64, usize)>,
+    items: Vec<(&'a Cbor, usize)>,
+}
+
+impl<'a> Dict<'a> {
+    fn get(&self, elem: &PathElement<'_>) -> Option<Slot<'a>> {
+        let pos = match elem {
+            PathElement::String(s) => find(&self.strings, s.as_ref()),
+            PathElement::Number(n) => find(&self.numbers, n),
+            PathElement::Item(c) => find(&self.items, c.as_ref()),
+            PathElement::FromEnd(_) => None,
+        }?;
+        Some(self.values[pos])
+    }
+}
+
+/// find the first position recorded for `key`
+fn find<K: Borrow<Q>, Q: Ord + ?Sized>(entries: &[(K, usize)], key: &Q) -> Option<usize> {
+    let idx = entries.partition_point(|(k, _)| k.borrow() < key);
+    entries
+        .get(idx)
+        .filter(|(k, _)| k.borrow() == key)
+        .map(|(_, pos)| *pos)
+}
+
+impl<'a> IndexedCbor<'a> {
+    /// Index all arrays and dicts within the given item.
+    pub fn new(cbor: &'a Cbor) -> Self {
+        Self::with_max_depth(cbor, None)
+    }
+
+    /// Index the arrays and dicts up to the given nesting depth.
+    ///
+    /// A top-level array or dict has depth 1, a container within it has depth 2, and so on.
+    /// Deeper containers are still reachable by [`index`](#method.index), which then falls back
+    /// to [`Cbor::index`](struct.Cbor.html#method.index) from the deepest indexed container on.
+    pub fn with_max_depth(cbor: &'a Cbor, max_depth: Option<usize>) -> Self {
+        let max_depth = max_depth.unwrap_or(usize::MAX);
+        let mut nodes = Vec::new();
+        // containers whose contents still need to be indexed, with their depth
+        let mut todo = Vec::new();
+        let root = slot(cbor, 1, max_depth, &mut nodes, &mut todo);
+        while let Some((idx, cbor, depth)) = todo.pop() {
+            let depth = depth + 1;
+            nodes[idx] = match cbor.kind() {
+                ItemKind::Array(iter) => Node::Array(
+                    iter.map(|item| slot(item, depth, max_depth, &mut nodes, &mut todo))
+                        .collect(),
+                ),
+                ItemKind::Dict(iter) => {
+                    let mut dict = Dict::default();
+                    for (pos, (key, value)) in iter.enumerate() {
+                        match key.kind() {
+                            ItemKind::Str(s) => dict.strings.push((s.as_cow(), pos)),
+                            ItemKind::Pos(n) => dict.numbers.push((n, pos)),
+                            _ => {}
+                        }
+                        dict.items.push((key, pos));
+                        let value = slot(value, depth, max_depth, &mut nodes, &mut todo);
+                        dict.values.push(value);
+                    }
+                    // stable sorting keeps the first occurrence of a key in front
+                    dict.strings.sort_by(|a, b| a.0.cmp(&b.0));
+                    dict.numbers.sort_by_key(|x| x.0);
+                    dict.items.sort_by_key(|x| x.0);
+                    Node::Dict(dict)
+                }
+                _ => unreachable!("only arrays and dicts are scheduled for indexing"),
+            };
+        }
+        Self { cbor, root, nodes }
+    }
+
+    /// The item that has been indexed
+    pub fn cbor(&self) -> &'a Cbor {
+        self.cbor
+    }
+
+    /// Extract a value by indexing into arrays and dicts, with path elements yielded by an iterator.
+    ///
+    /// This yields the same results as [`Cbor::index`](struct.Cbor.html#method.index).
+    pub fn index<'b>(
+        &self,
+        path: impl IntoIterator<Item = PathElement<'b>>,
+    ) -> Option<Cow<'a, Cbor>> {
+        let mut path = path.into_iter();
+        let mut slot = self.root;
+        loop {
+            let node = match slot.node {
+                Some(node) => &self.nodes[node],
+                None => return slot.cbor.index(path),
+            };
+            let elem = match path.next() {
+                Some(elem) => elem,
+                None => return Some(Cow::Borrowed(slot.cbor)),
+            };
+            slot = match (node, elem) {
+                (Node::Array(items), PathElement::Number(idx)) => {
+                    *items.get(usize::try_from(idx).ok()?)?
+                }
+                (Node::Array(items), PathElement::FromEnd(idx)) => {
+                    *items.get(items.len().checked_sub(usize::try_from(idx).ok()?)?)?
+                }
+                (Node::Array(_), _) => return None,
+                (Node::Dict(dict), elem) => dict.get(&elem)?,
+            };
+        }
+    }
+
+    /// Extract a value by indexing into arrays and dicts, with path elements yielded by an iterator.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`Cbor::index_borrowed`](struct.Cbor.html#method.index_borrowed).
+    pub fn index_borrowed<'b>(
+        &self,
+        path: impl IntoIterator<Item = PathElement<'b>>,
+    ) -> Option<&'a Cbor> {
+        self.index(path).map(|cow| match cow {
+            Cow::Borrowed(b) => b,
+            Cow::Owned(_) => panic!("indexing required allocation"),
+        })
+    }
+}
+
+/// create the slot for an item at the given depth, scheduling it for indexing if it is a container
+fn slot<'a>(
+    mut cbor: &'a Cbor,
+    depth: usize,
+    max_depth: usize,
+    nodes: &mut Vec<Node<'a>>,
+    todo: &mut Vec<(usize, &'a Cbor, usize)>,
+) -> Slot<'a> {
+    let item = loop {
+        let item = cbor.tagged_item();
+        if let (Some(TAG_CBOR_ITEM), ItemKind::Bytes(bytes)) = (item.tags().single(), item.kind()) {
+            match bytes.as_slice() {
+                Some(bytes) => cbor = Cbor::unchecked(bytes),
+                // contents need to be assembled, leave this to `Cbor::index`
+                None => return Slot { cbor, node: None },
+            }
+        } else {
+            break item;
+        }
+    };
+    let node = match item.kind() {
+        ItemKind::Array(_) | ItemKind::Dict(_) if depth <= max_depth => {
+            nodes.push(Node::Array(Vec::new()));
+            todo.push((nodes.len() - 1, cbor, depth));
+            Some(nodes.len() - 1)
+        }
+        _ => None,
+    };
+    Slot { cbor, node }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexedCbor;
+    use crate::{index_str, Cbor, PathElement};
+    use std::borrow::Cow;
+
+    fn check(bytes: &[u8], paths: &[&str]) {
+        let cbor = Cbor::checked(bytes).unwrap();
+        for depth in [Some(0), Some(1), Some(2), None] {
+            let indexed = IndexedCbor::with_max_depth(cbor, depth);
+            for path in paths {
+                assert_eq!(
+                    indexed.index(index_str(path)),
+                    cbor.index(index_str(path)),
+                    "path {} depth {:?}",
+                    path,
+                    depth
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn same_as_index() {
+        let paths = [
+            "",
+            "[0]",
+            "[1]",
+            "[2]",
+            "[3]",
+            "[0].a",
+            "[0].b",
+            "[0].c",
+            "[0][5]",
+            "[0].a[1]",
+            "[1][0]",
+            "[1][1]",
+            "[1][1].x",
+            "[-1]",
+            "[-3]",
+            "[-4]",
+            "[0].a[-2]",
+            "[1][-1].x",
+            "[2].a",
+            "[2][0]",
+            "[2][0].x",
+            "a",
+        ];
+        // [{"a": [1, 2], "b": 3, 5: 4, "a": 5}, 24(<<[_ 1, {"x": 2}]>>), 24((_ h'81', h'a1617801'))]
+        check(
+            &[
+                0x83, 0xa4, 0x61, b'a', 0x82, 1, 2, 0x61, b'b', 3, 0x18, 5, 4, 0x61, b'a', 5, 0xd8,
+                24, 0x47, 0x9f, 1, 0xa1, 0x61, b'x', 2, 0xff, 0xd8, 24, 0x5f, 0x41, 0x81, 0x44,
+                0xa1, 0x61, b'x', 1, 0xff,
+            ],
+            &paths,
+        );
+        // {_ "a": [_ ], (_ "b"): 1, [1]: 2}
+        check(
+            &[
+                0xbf, 0x61, b'a', 0x9f, 0xff, 0x7f, 0x61, b'b', 0xff, 1, 0x81, 1, 2, 0xff,
+            ],
+            &["a", "a[0]", "b", "[0]"],
+        );
+    }
+
+    #[test]
+    fn keys() {
+        let cbor = Cbor::unchecked(&[0xa3, 0x81, 1, 2, 0xc1, 7, 3, 7, 4]);
+        let indexed = IndexedCbor::new(cbor);
+        let key = Cbor::unchecked(&[0x81, 1]);
+        let item = indexed.index_borrowed([PathElement::Item(Cow::Borrowed(key))]);
+        assert_eq!(item.unwrap().to_string(), "2");
+        // tagged numbers are found by number as well as by their encoding
+        let item = indexed.index_borrowed([PathElement::Number(7)]);
+        assert_eq!(item.unwrap().to_string(), "3");
+        let key = Cbor::unchecked(&[7]);
+        let item = indexed.index_borrowed([PathElement::Item(Cow::Borrowed(key))]);
+        assert_eq!(item.unwrap().to_string(), "4");
+        assert_eq!(indexed.index_borrowed([PathElement::Number(8)]), None);
+    }
+}
@@ -13,8 +13,20 @@ mod canonical;
 mod check;
 pub mod codec;
 pub mod constants;
+pub mod diff;
 mod error;
+mod indexed;
+mod limits;
+pub mod patch;
+pub mod query;
 mod reader;
+mod semantic;
+mod seq;
+#[cfg(feature = "tokio")]
+mod seq_async;
+mod stream;
+mod transform;
+mod update;
 mod validated;
 pub mod value;
 mod visit;
@@ -26,21 +38,35 @@ pub use builder::{
     ArrayWriter, CborBuilder, CborOutput, DictWriter, Encoder, KeyBuilder, NoOutput, SingleBuilder,
     SingleResult, WithOutput, Writer,
 };
+pub use canonical::KeyOrder;
 pub use error::{ErrorKind, ParseError, WhileParsing};
+pub use indexed::IndexedCbor;
+pub use limits::{DuplicateKeys, ValidationLimits};
 pub use reader::Literal;
+pub use semantic::{Semantic, SemanticHash};
+pub use seq::{CborSeqReader, CborSeqWriter};
+#[cfg(feature = "tokio")]
+pub use seq_async::{AsyncCborSeqReader, AsyncCborSeqWriter};
+pub use stream::{StreamDecoder, StreamItems};
+pub use transform::{Transform, Transformer};
+pub use update::Update;
 pub use validated::{
-    indexing::{IndexStr, PathElement},
+    indexing::{IndexStr, IndexStrError, PathElement},
     item::{ItemKind, ItemKindShort, TaggedItem},
     iterators::{ArrayIter, BytesIter, DictIter, StringIter},
+    pointer::{render_pointer, PointerStr},
     tags::{Tags, TagsShort},
+    views::{ArrayView, DictView},
 };
-pub use value::CborValue;
-pub use visit::Visitor;
+use value::OrderedDict;
+pub use value::{CborTree, CborValue};
+pub use visit::{PathVisitor, Visitor};
 
-use canonical::canonicalise;
+use canonical::{canonicalise, canonicalise_deterministic};
+use constants::TAG_CBOR_ITEM;
 use smallvec::SmallVec;
 use validated::indexing::IndexVisitor;
-use visit::visit;
+use visit::{visit, PathTracker};
 
 /// Wrapper around a byte slice that encodes a valid CBOR item.
 ///
@@ -152,12 +178,29 @@ impl Cbor {
 
     /// Cast the given byte slice as CBOR item if the encoding is valid
     pub fn checked(bytes: &[u8]) -> Result<&Self, ParseError> {
-        check::validate(bytes, false).map(|(cbor, _rest)| cbor)
+        Self::checked_with(bytes, &ValidationLimits::new())
+    }
+
+    /// Cast the given byte slice as CBOR item if the encoding is valid and within the given limits
+    pub fn checked_with<'a>(
+        bytes: &'a [u8],
+        limits: &ValidationLimits,
+    ) -> Result<&'a Self, ParseError> {
+        check::validate(bytes, false, limits).map(|(cbor, _rest)| cbor)
     }
 
     /// Cast the given byte slice as CBOR item if the encoding is valid, permitting more bytes to follow the item
     pub fn checked_prefix(bytes: &[u8]) -> Result<(&Self, &[u8]), ParseError> {
-        check::validate(bytes, true)
+        Self::checked_prefix_with(bytes, &ValidationLimits::new())
+    }
+
+    /// Cast the given byte slice as CBOR item if the encoding is valid and within the given limits,
+    /// permitting more bytes to follow the item
+    pub fn checked_prefix_with<'a>(
+        bytes: &'a [u8],
+        limits: &ValidationLimits,
+    ) -> Result<(&'a Self, &'a [u8]), ParseError> {
+        check::validate(bytes, true, limits)
     }
 
     /// Convert the given bytes to a CBOR item if the encoding is valid
@@ -176,6 +219,26 @@ impl Cbor {
         &self.0
     }
 
+    /// Check whether this item is in deterministic encoding with the given order of dict keys
+    ///
+    /// This is the case if [`CborOwned::deterministic_with`](struct.CborOwned.html#method.deterministic_with)
+    /// would return the same bytes, but the check does not need to allocate a copy.
+    ///
+    /// ```rust
+    /// use cbor_data::{Cbor, KeyOrder};
+    ///
+    /// let cbor = Cbor::checked(&[0xa2, 0x18, 100, 1, 0x20, 2]).unwrap();
+    /// assert!(cbor.is_deterministic(KeyOrder::Bytewise));
+    /// assert!(!cbor.is_deterministic(KeyOrder::LengthFirst));
+    ///
+    /// // 1.0 could be encoded as half-precision float
+    /// let cbor = Cbor::checked(&[0xfa, 0x3f, 0x80, 0, 0]).unwrap();
+    /// assert!(!cbor.is_deterministic(KeyOrder::Preserve));
+    /// ```
+    pub fn is_deterministic(&self, key_order: KeyOrder) -> bool {
+        canonical::is_deterministic(self.as_slice(), key_order)
+    }
+
     /// Interpret the CBOR item at a higher level
     ///
     /// While [`kind`](#method.kind) gives you precise information on how the item is encoded,
@@ -185,6 +248,21 @@ impl Cbor {
         CborValue::new(self.tagged_item())
     }
 
+    /// Interpret the CBOR item as a dict, keeping the order of its entries and duplicate keys
+    ///
+    /// Returns `None` if the item is not a dict. Like [`decode`](#method.decode), this looks
+    /// into [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings, see
+    /// [`OrderedDict`](value/struct.OrderedDict.html).
+    pub fn decode_ordered(&self) -> Option<OrderedDict<'_>> {
+        OrderedDict::new(self.tagged_item())
+    }
+
+    /// Decode this item with all its contents into an owned tree, see
+    /// [`CborTree`](value/enum.CborTree.html)
+    pub fn to_tree(&self) -> CborTree {
+        CborTree::new(self)
+    }
+
     /// An iterator over the tags present on this item, from outermost to innermost
     pub fn tags(&self) -> Tags<'_> {
         reader::tags(self.as_slice()).unwrap().0
@@ -284,6 +362,73 @@ impl Cbor {
         })
     }
 
+    /// Borrow this item as an array without collecting its items.
+    ///
+    /// Returns `None` if the item is not an array. Like [`try_array`](#method.try_array) this looks
+    /// through [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings, but only those
+    /// of definite size, so that the view never allocates. Use
+    /// [`CborOwned::canonical`](struct.CborOwned.html#method.canonical) to ensure that indefinite
+    /// size byte strings cannot occur.
+    ///
+    /// ```rust
+    /// use cbor_data::{CborBuilder, Writer};
+    ///
+    /// let cbor = CborBuilder::new().write_array(None, |b| {
+    ///     b.write_pos(1, None);
+    ///     b.write_str("two", None);
+    /// });
+    /// let array = cbor.as_array_view().unwrap();
+    /// assert_eq!(array.len(), 2);
+    /// assert_eq!(array.get_index(1).unwrap().to_string(), r#""two""#);
+    /// assert_eq!(array.iter().map(|item| item.to_string()).collect::<Vec<_>>(), ["1", r#""two""#]);
+    /// ```
+    pub fn as_array_view(&self) -> Option<ArrayView<'_>> {
+        match self.untagged()?.kind() {
+            ItemKind::Array(iter) => Some(ArrayView::new(iter)),
+            _ => None,
+        }
+    }
+
+    /// Borrow this item as a dict without collecting its entries.
+    ///
+    /// Returns `None` if the item is not a dict. The same treatment of
+    /// [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings applies as for
+    /// [`as_array_view`](#method.as_array_view).
+    ///
+    /// ```rust
+    /// use cbor_data::{CborBuilder, Writer};
+    ///
+    /// let cbor = CborBuilder::new().write_dict(None, |b| {
+    ///     b.with_key("name", |b| b.write_str("Alice", None));
+    ///     b.with_key("age", |b| b.write_pos(42, None));
+    /// });
+    /// let dict = cbor.as_dict_view().unwrap();
+    /// assert_eq!(dict.len(), 2);
+    /// assert_eq!(dict.get("age").unwrap().to_string(), "42");
+    /// assert_eq!(dict.get("email"), None);
+    /// ```
+    pub fn as_dict_view(&self) -> Option<DictView<'_>> {
+        match self.untagged()?.kind() {
+            ItemKind::Dict(iter) => Some(DictView::new(iter)),
+            _ => None,
+        }
+    }
+
+    /// strip TAG_CBOR_ITEM byte strings of definite size, or `None` for any other tags
+    fn untagged(&self) -> Option<&Cbor> {
+        let mut cbor = self;
+        loop {
+            let item = cbor.tagged_item();
+            match (item.tags().single(), item.kind()) {
+                (None, _) => return Some(cbor),
+                (Some(TAG_CBOR_ITEM), ItemKind::Bytes(bytes)) => {
+                    cbor = Cbor::unchecked(bytes.as_slice()?)
+                }
+                _ => return None,
+            }
+        }
+    }
+
     /// Extract a value by indexing into arrays and dicts, with path elements yielded by an iterator.
     ///
     /// Returns None if an index doesn’t exist or the indexed object is neither an array nor a dict.
@@ -325,6 +470,220 @@ impl Cbor {
         })
     }
 
+    /// Extract a value using a [JSON Pointer](https://www.rfc-editor.org/rfc/rfc6901) like `/a/b/0`.
+    ///
+    /// Following the RFC, a reference token applied to an array must be `0` or a decimal number
+    /// without leading zeros (`-` denotes the nonexistent item after the last one), while a token
+    /// applied to a dict is always a text string key, even if it looks like a number. The escapes
+    /// `~1` and `~0` stand for `/` and `~`.
+    ///
+    /// Returns `None` if the pointer is malformed or does not resolve. As for
+    /// [`index`](#method.index), the result is borrowed unless the traversal entered a
+    /// TAG_CBOR_ITEM byte string with indefinite encoding.
+    ///
+    /// ```rust
+    /// use cbor_data::{CborBuilder, Writer};
+    ///
+    /// let cbor = CborBuilder::new().write_dict(None, |b| {
+    ///     b.with_key("a/b", |b| b.write_array(None, |b| {
+    ///         b.write_str("first", None);
+    ///     }));
+    ///     b.with_key("0", |b| b.write_str("string key", None));
+    ///     b.with_cbor_key(|b| b.write_pos(0, None), |b| b.write_str("integer key", None));
+    /// });
+    ///
+    /// assert_eq!(cbor.pointer("/a~1b/0").unwrap().to_string(), r#""first""#);
+    /// assert_eq!(cbor.pointer("/0").unwrap().to_string(), r#""string key""#);
+    /// assert_eq!(cbor.pointer("/a~1b/00"), None);
+    /// assert_eq!(cbor.pointer("/a~1b/-"), None);
+    /// assert_eq!(cbor.pointer("").unwrap().as_ref(), cbor.as_ref());
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<Cow<'_, Cbor>> {
+        fn step<'a>(cbor: &'a Cbor, elem: PathElement<'_>) -> Option<Cow<'a, Cbor>> {
+            // look through TAG_CBOR_ITEM byte strings to learn whether this is a dict
+            let item = cbor.index(std::iter::empty::<PathElement>())?;
+            let elem = match (item.kind(), elem) {
+                (ItemKind::Dict(_), PathElement::Number(n)) => {
+                    PathElement::String(Cow::Owned(n.to_string()))
+                }
+                (_, elem) => elem,
+            };
+            cbor.index([elem])
+        }
+        let mut current = Cow::Borrowed(self);
+        for elem in PointerStr::new(pointer)? {
+            current = match current {
+                Cow::Borrowed(cbor) => step(cbor, elem)?,
+                Cow::Owned(cbor) => Cow::Owned(step(&cbor, elem)?.into_owned()),
+            };
+        }
+        Some(current)
+    }
+
+    /// Like [`pointer`](#method.pointer), but copying the result so that it can outlive this item.
+    pub fn pointer_owned(&self, pointer: &str) -> Option<CborOwned> {
+        self.pointer(pointer).map(Cow::into_owned)
+    }
+
+    /// Copy this item with the value at the given path replaced, see
+    /// [`Update::Set`](enum.Update.html#variant.Set).
+    ///
+    /// Untouched items are copied byte for byte and only the headers of the arrays and dicts
+    /// along the path are rewritten, keeping their definite or indefinite size encoding;
+    /// use [`Writer::write_updated`](trait.Writer.html#method.write_updated) to choose the encoding
+    /// instead. [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings along the path
+    /// are entered and rewritten with definite size. Returns `None` if the path does not resolve.
+    ///
+    /// ```rust
+    /// use cbor_data::{index_str, CborBuilder, Writer};
+    ///
+    /// let cbor = CborBuilder::new().write_dict(None, |b| {
+    ///     b.with_key("a", |b| b.write_array(None, |b| {
+    ///         b.write_pos(1, None);
+    ///         b.write_pos(2, None);
+    ///     }));
+    /// });
+    /// let value = CborBuilder::new().write_str("x", None);
+    ///
+    /// let updated = cbor.with_set(index_str("a[1]"), &value).unwrap();
+    /// assert_eq!(updated.to_string(), r#"{"a": [1, "x"]}"#);
+    /// let updated = cbor.with_set(index_str("b"), &value).unwrap();
+    /// assert_eq!(updated.to_string(), r#"{"a": [1, 2], "b": "x"}"#);
+    /// assert_eq!(cbor.with_set(index_str("a[2]"), &value), None);
+    /// ```
+    pub fn with_set<'b>(
+        &self,
+        path: impl IntoIterator<Item = PathElement<'b>>,
+        value: &Cbor,
+    ) -> Option<CborOwned> {
+        self.updated(path, Update::Set(value))
+    }
+
+    /// Copy this item with a new array item or dict entry at the given path, see
+    /// [`Update::Insert`](enum.Update.html#variant.Insert).
+    ///
+    /// The encoding is treated as for [`with_set`](#method.with_set). Returns `None` if the path
+    /// does not resolve or the dict key is already present.
+    ///
+    /// ```rust
+    /// use cbor_data::{index_str, Cbor};
+    ///
+    /// let cbor = Cbor::checked(&[0x9f, 1, 2, 0xff]).unwrap();
+    /// let value = Cbor::checked(&[0]).unwrap();
+    ///
+    /// assert_eq!(cbor.with_inserted(index_str("[0]"), value).unwrap().to_string(), "[_ 0, 1, 2]");
+    /// assert_eq!(cbor.with_inserted(index_str("[-1]"), value).unwrap().to_string(), "[_ 1, 2, 0]");
+    /// ```
+    pub fn with_inserted<'b>(
+        &self,
+        path: impl IntoIterator<Item = PathElement<'b>>,
+        value: &Cbor,
+    ) -> Option<CborOwned> {
+        self.updated(path, Update::Insert(value))
+    }
+
+    /// Copy this item without the array item or dict entry at the given path, see
+    /// [`Update::Remove`](enum.Update.html#variant.Remove).
+    ///
+    /// The encoding is treated as for [`with_set`](#method.with_set). Returns `None` if the path
+    /// does not resolve or is empty.
+    ///
+    /// ```rust
+    /// use cbor_data::{index_str, CborOwned};
+    ///
+    /// // {"a": 1, "b": [2, 3]}
+    /// let cbor = CborOwned::canonical([0xa2, 0x61, b'a', 1, 0x61, b'b', 0x82, 2, 3]).unwrap();
+    ///
+    /// assert_eq!(cbor.with_removed(index_str("a")).unwrap().to_string(), r#"{"b": [2, 3]}"#);
+    /// assert_eq!(cbor.with_removed(index_str("b[0]")).unwrap().to_string(), r#"{"a": 1, "b": [3]}"#);
+    /// assert_eq!(cbor.with_removed(index_str("c")), None);
+    /// ```
+    pub fn with_removed<'b>(
+        &self,
+        path: impl IntoIterator<Item = PathElement<'b>>,
+    ) -> Option<CborOwned> {
+        self.updated(path, Update::Remove)
+    }
+
+    /// Apply a [JSON Merge Patch](https://www.rfc-editor.org/rfc/rfc7386) to this item.
+    ///
+    /// If the patch is a dict, its entries are merged into this item (or into an empty dict if
+    /// this item is not a dict): `null` values remove the key, dicts are merged recursively and
+    /// all other values replace the existing value or are added under their key. Otherwise the
+    /// patch replaces this item. Keys may be any CBOR items, they are matched by their meaning
+    /// instead of their encoding. Entries that are not affected by the patch are copied byte for
+    /// byte, but new dicts are written with the default settings of
+    /// [`CborBuilder`](struct.CborBuilder.html).
+    ///
+    /// A dict in this item that carries tags is merged like any other, keeping its tags, whereas a
+    /// dict in the patch that carries tags is a value like any other and thus written as it is.
+    ///
+    /// ```rust
+    /// use cbor_data::{CborBuilder, Writer};
+    ///
+    /// let settings = CborBuilder::new().write_dict(None, |b| {
+    ///     b.with_key("theme", |b| b.write_str("dark", None));
+    ///     b.with_key("font", |b| b.write_dict(None, |b| {
+    ///         b.with_key("size", |b| b.write_pos(12, None));
+    ///         b.with_key("family", |b| b.write_str("mono", None));
+    ///     }));
+    ///     b.with_cbor_key(|b| b.write_pos(7, None), |b| b.write_bool(true, None));
+    /// });
+    /// let patch = CborBuilder::new().write_dict(None, |b| {
+    ///     b.with_key("font", |b| b.write_dict(None, |b| {
+    ///         b.with_key("size", |b| b.write_pos(14, None));
+    ///     }));
+    ///     b.with_cbor_key(|b| b.write_pos(7, None), |b| b.write_null(None));
+    ///     b.with_key("lang", |b| b.write_str("en", None));
+    /// });
+    ///
+    /// assert_eq!(
+    ///     settings.merge_patch(&patch).to_string(),
+    ///     r#"{"theme": "dark", "font": {"size": 14, "family": "mono"}, "lang": "en"}"#
+    /// );
+    /// ```
+    pub fn merge_patch(&self, patch: &Cbor) -> CborOwned {
+        patch::merge_patch(Some(self), patch, CborBuilder::new())
+    }
+
+    fn updated<'b>(
+        &self,
+        path: impl IntoIterator<Item = PathElement<'b>>,
+        update: Update<'_>,
+    ) -> Option<CborOwned> {
+        let mut bytes = Vec::new();
+        update::update(self, path, update, None, &mut bytes)?;
+        Some(CborOwned::unchecked(bytes))
+    }
+
+    /// Compare this item to another one according to the CBOR data model instead of the
+    /// encoding, which is what `==` does.
+    ///
+    /// Following RFC 8949 §2, items are equal regardless of integer and float encoding widths,
+    /// string chunking, definite or indefinite size encoding, and the order of dict entries.
+    /// [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings compare like their
+    /// contents, all other tags need to match. Bignums are equal to integers of the same value
+    /// (RFC 8949 §3.4.3), but integers are never equal to floats; floats compare by their
+    /// IEEE 754 value, i.e. `0.0` and `-0.0` differ while all NaNs are the same.
+    ///
+    /// See [`SemanticHash`](trait.SemanticHash.html) and [`Semantic`](struct.Semantic.html) for
+    /// the corresponding hashing.
+    ///
+    /// ```
+    /// use cbor_data::Cbor;
+    ///
+    /// let five = Cbor::checked(&[0x05]).unwrap();
+    /// let wide = Cbor::checked(&[0x18, 0x05]).unwrap();
+    /// let big = Cbor::checked(&[0xc2, 0x42, 0x00, 0x05]).unwrap();
+    /// let float = Cbor::checked(&[0xf9, 0x45, 0x00]).unwrap();
+    /// assert!(five.semantic_eq(wide));
+    /// assert!(five.semantic_eq(big));
+    /// assert!(!five.semantic_eq(float));
+    /// ```
+    pub fn semantic_eq(&self, other: &Cbor) -> bool {
+        semantic::semantic_eq(self, other)
+    }
+
     /// Visit the interesting parts of this CBOR item as guided by the given
     /// [`Visitor`](trait.Visitor.html).
     ///
@@ -335,6 +694,64 @@ impl Cbor {
     ) -> Result<(), Err> {
         visit(visitor, self.tagged_item())
     }
+
+    /// Visit the interesting parts of this CBOR item as guided by the given
+    /// [`PathVisitor`](trait.PathVisitor.html), which is told the path and byte range of each
+    /// visited item.
+    pub fn visit_paths<'a, 'b, Err, V: PathVisitor<'a, Err> + 'b>(
+        &'a self,
+        visitor: &'b mut V,
+    ) -> Result<(), Err> {
+        visit(&mut PathTracker::new(visitor), self.tagged_item())
+    }
+
+    /// Write a rewritten copy of this CBOR item to the given [`Writer`](trait.Writer.html), as
+    /// directed by the [`Transformer`](trait.Transformer.html).
+    ///
+    /// The result is written directly without building intermediate values; arrays and dicts
+    /// that are descended into use definite size encoding according to the writer’s
+    /// [`max_definite`](trait.Writer.html#tymethod.max_definite). Returns `None` without
+    /// writing anything if this item itself is dropped.
+    ///
+    /// ```
+    /// use cbor_data::{CborBuilder, CborOwned, PathElement, Transform, Transformer};
+    ///
+    /// struct Redact;
+    /// impl<'a> Transformer<'a> for Redact {
+    ///     fn transform(&mut self, path: &[PathElement<'a>], item: &'a cbor_data::Cbor) -> Transform {
+    ///         match path.last() {
+    ///             Some(PathElement::String(key)) if key == "password" => Transform::Drop,
+    ///             _ => Transform::Descend,
+    ///         }
+    ///     }
+    ///     fn rename_key(&mut self, path: &[PathElement<'a>], key: &'a cbor_data::Cbor) -> Option<CborOwned> {
+    ///         match path.last() {
+    ///             Some(PathElement::String(key)) if key == "user" => {
+    ///                 Some(CborOwned::canonical(b"\x64name").unwrap())
+    ///             }
+    ///             _ => None,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// // [{"user": "x", "password": "y"}]
+    /// let cbor = CborOwned::canonical(b"\x81\xa2\x64user\x61x\x68password\x61y").unwrap();
+    /// let result = cbor.transform(&mut Redact, CborBuilder::new()).unwrap();
+    /// assert_eq!(result.to_string(), r#"[{"name": "x"}]"#);
+    /// ```
+    pub fn transform<'a, W: Writer>(
+        &'a self,
+        transformer: &mut impl Transformer<'a>,
+        mut writer: W,
+    ) -> Option<W::Output> {
+        let decision = transformer.transform(&[], self);
+        if decision == Transform::Drop {
+            return None;
+        }
+        let max_definite = writer.max_definite();
+        writer.bytes(|b| transform::transform(self, decision, transformer, max_definite, b));
+        Some(writer.into_output())
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -384,19 +801,19 @@ impl Display for CborOwned {
 
 impl Borrow<Cbor> for CborOwned {
     fn borrow(&self) -> &Cbor {
-        Cbor::unchecked(&*self.0)
+        Cbor::unchecked(&self.0)
     }
 }
 
 impl AsRef<Cbor> for CborOwned {
     fn as_ref(&self) -> &Cbor {
-        Cbor::unchecked(&*self.0)
+        Cbor::unchecked(&self.0)
     }
 }
 
 impl AsRef<[u8]> for CborOwned {
     fn as_ref(&self) -> &[u8] {
-        &*self.0
+        &self.0
     }
 }
 
@@ -437,7 +854,67 @@ impl CborOwned {
     /// For more configuration options like reusing a scratch space or preferring definite size encoding
     /// see [`CborBuilder`](struct.CborBuilder.html).
     pub fn canonical(bytes: impl AsRef<[u8]>) -> Result<Self, ParseError> {
-        canonicalise(bytes.as_ref(), CborBuilder::new())
+        Self::canonical_with(bytes, &ValidationLimits::new())
+    }
+
+    /// Like [`canonical()`](#method.canonical), but rejecting input that exceeds the given limits.
+    ///
+    /// ```rust
+    /// use cbor_data::{CborOwned, ErrorKind, ValidationLimits};
+    ///
+    /// let limits = ValidationLimits::new().with_max_items(Some(2));
+    /// let error = CborOwned::canonical_with([0x83, 1, 2, 3], &limits).unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::TooManyItems);
+    /// assert_eq!(error.offset(), 0);
+    /// ```
+    pub fn canonical_with(
+        bytes: impl AsRef<[u8]>,
+        limits: &ValidationLimits,
+    ) -> Result<Self, ParseError> {
+        canonicalise(bytes.as_ref(), CborBuilder::new(), limits)
+    }
+
+    /// Copy the bytes while checking for integrity and converting them into the deterministic encoding
+    /// described in [RFC 8949 §4.2.1](https://www.rfc-editor.org/rfc/rfc8949#section-4.2.1).
+    ///
+    /// In addition to what [`canonical()`](#method.canonical) does, this
+    ///
+    ///  - writes all arrays and dicts with definite size
+    ///  - writes floating-point numbers in the shortest form that preserves their value
+    ///  - sorts dict entries by the bytewise lexicographic order of the deterministic encoding of their keys
+    ///
+    /// The result is suitable for content hashing or signatures, since equal data yield equal bytes.
+    /// Dicts that contain the same key more than once are rejected with
+    /// [`ErrorKind::DuplicateKey`](enum.ErrorKind.html#variant.DuplicateKey).
+    ///
+    /// ```rust
+    /// use cbor_data::{CborOwned, ErrorKind};
+    ///
+    /// // {_ "b": 1.5 (as double), "a": 24 (as two-byte integer)}
+    /// let bytes = [0xbf, 0x61, b'b', 0xfb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0, 0x61, b'a', 0x19, 0, 24, 0xff];
+    /// let cbor = CborOwned::deterministic(bytes).unwrap();
+    /// assert_eq!(cbor.as_slice(), [0xa2, 0x61, b'a', 0x18, 24, 0x61, b'b', 0xf9, 0x3e, 0]);
+    ///
+    /// let error = CborOwned::deterministic([0xa2, 0x61, b'a', 1, 0x61, b'a', 2]).unwrap_err();
+    /// assert_eq!(error.kind(), ErrorKind::DuplicateKey);
+    /// assert_eq!(error.offset(), 4);
+    /// ```
+    pub fn deterministic(bytes: impl AsRef<[u8]>) -> Result<Self, ParseError> {
+        Self::deterministic_with(bytes, KeyOrder::Bytewise, &ValidationLimits::new())
+    }
+
+    /// Like [`deterministic()`](#method.deterministic), but with the given order of dict keys
+    /// and rejecting input that exceeds the given limits.
+    ///
+    /// With [`KeyOrder::Preserve`](enum.KeyOrder.html#variant.Preserve) the dict entries are kept in
+    /// their original order, but duplicate keys are still rejected — unless the limits are configured
+    /// with [`DuplicateKeys::KeepFirst`](enum.DuplicateKeys.html#variant.KeepFirst).
+    pub fn deterministic_with(
+        bytes: impl AsRef<[u8]>,
+        key_order: KeyOrder,
+        limits: &ValidationLimits,
+    ) -> Result<Self, ParseError> {
+        canonicalise_deterministic(bytes.as_ref(), CborBuilder::new(), key_order, limits)
     }
 
     /// Hand out the underlying SmallVec as a Vec<u8>
@@ -450,19 +927,34 @@ impl CborOwned {
 
 /// Generate an iterator of [`PathElement`](struct.PathElement.html) from a string
 ///
-/// A path element is either
+/// Path elements may be separated by dots, and each is either
+///
+///  - a string starting with any other character than dot, opening bracket, or opening brace
+///    and delimited by the next one of those, yielding [`PathElement::String`](enum.PathElement.html#variant.String)
+///  - a string in single or double quotes enclosed in brackets, like `["a.b"]`, which may contain
+///    any characters
+///  - a number enclosed in brackets, yielding [`PathElement::Number`](enum.PathElement.html#variant.Number)
+///  - a negative number enclosed in brackets, like `[-1]` for the last array item, yielding
+///    [`PathElement::FromEnd`](enum.PathElement.html#variant.FromEnd)
+///  - a byte string in hex notation enclosed in brackets, like `[h'00ff']`, yielding
+///    [`PathElement::Item`](enum.PathElement.html#variant.Item) for the byte string key
+///  - an integer enclosed in braces, like `{-3}` for a negative integer key, yielding
+///    [`PathElement::Item`](enum.PathElement.html#variant.Item) for the integer in its shortest
+///    encoding, which only matches dict keys (unlike `[3]`, `{3}` does not select an array item)
 ///
-///  - a string starting with any other character than dot or opening bracket
-///    and delimited by the next dot or opening bracket
-///  - a number enclosed in brackets
+/// Opening braces delimit unquoted keys since version 0.9, before that `a{1}` denoted the key
+/// `a{1}` while it now selects the integer key 1 within the value of key `a`; such keys need
+/// to be quoted, like `["a{1}"]`.
+/// Within strings, a backslash escapes the following character, so that `a\.b` denotes the
+/// key `a.b`; `\n`, `\t`, `\r`, `\0`, and `\u{…}` have their usual meaning.
 ///
-/// `None` is returned in case an opening bracket is not matched with a closing one
-/// or the characters between brackets are not a valid representation of `u64`.
+/// `None` is returned if the string does not follow this syntax, use
+/// [`IndexStr::parse`](struct.IndexStr.html#method.parse) to learn where and why.
 ///
 /// # Examples:
 ///
 /// ```rust
-/// use cbor_data::{Cbor, index_str, ItemKind};
+/// use cbor_data::{Cbor, index_str, IndexStr, ItemKind};
 ///
 /// let cbor = Cbor::checked(b"eActyx").unwrap();
 ///
@@ -470,6 +962,28 @@ impl CborOwned {
 /// assert_eq!(cbor.index(index_str("x[12].y")), None);
 /// // empty string means the outermost item
 /// assert!(matches!(cbor.index(index_str("")).unwrap().kind(), ItemKind::Str(s) if s == "Actyx"));
+///
+/// let error = IndexStr::parse("x[12").unwrap_err();
+/// assert_eq!(error.to_string(), "expected `]` at offset 4");
+/// ```
+///
+/// ```rust
+/// use cbor_data::{CborBuilder, index_str, Writer};
+///
+/// let cbor = CborBuilder::new().write_dict(None, |b| {
+///     b.with_key("a.b", |b| b.write_array(None, |b| {
+///         b.write_pos(1, None);
+///         b.write_pos(2, None);
+///     }));
+///     b.with_cbor_key(|b| b.write_neg(2, None), |b| b.write_str("minus three", None));
+///     b.with_cbor_key(|b| b.write_bytes(&[0, 255], None), |b| b.write_str("bytes", None));
+/// });
+///
+/// assert_eq!(cbor.index(index_str(r#"["a.b"][-1]"#)).unwrap().to_string(), "2");
+/// assert_eq!(cbor.index(index_str(r"a\.b[0]")).unwrap().to_string(), "1");
+/// assert_eq!(cbor.index(index_str("{-3}")).unwrap().to_string(), r#""minus three""#);
+/// assert_eq!(cbor.index(index_str("a.b{0}")), None);
+/// assert_eq!(cbor.index(index_str("[h'00ff']")).unwrap().to_string(), r#""bytes""#);
 /// ```
 pub fn try_index_str(s: &str) -> Option<IndexStr<'_>> {
     IndexStr::new(s)
@@ -495,7 +1009,7 @@ pub fn try_index_str(s: &str) -> Option<IndexStr<'_>> {
 /// assert_eq!(item.decode().to_number().unwrap(), Number::Int(42));
 /// ```
 pub fn index_str(s: &str) -> IndexStr<'_> {
-    try_index_str(s).expect("invalid index string")
+    IndexStr::parse(s).unwrap_or_else(|e| panic!("invalid index string: {}", e))
 }
 
 struct DebugUsingDisplay<'a, T>(&'a T);
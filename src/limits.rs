@@ -0,0 +1,259 @@
+use crate::{ErrorKind, ErrorKind::*};
+
+/// Resource limits to apply while validating or canonicalising CBOR bytes
+///
+/// The default configuration does not limit anything, it only rejects malformed bytes.
+/// When ingesting messages from untrusted sources you should configure limits that are
+/// comfortably above what your application legitimately needs, so that a single hostile
+/// message cannot exhaust the memory or time available to your process.
+///
+/// ```rust
+/// use cbor_data::{Cbor, ErrorKind, ValidationLimits};
+///
+/// let limits = ValidationLimits::new().with_max_depth(Some(2));
+///
+/// assert!(Cbor::checked_with(&[0x81, 0x81, 0x00], &limits).is_ok());
+///
+/// let error = Cbor::checked_with(&[0x81, 0x81, 0x81, 0x00], &limits).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::DepthExceeded);
+/// assert_eq!(error.offset(), 2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ValidationLimits {
+    max_depth: Option<usize>,
+    max_items: Option<u64>,
+    max_string_bytes: Option<u64>,
+    max_tags: Option<usize>,
+    max_size: Option<usize>,
+    duplicate_keys: DuplicateKeys,
+    error_path: bool,
+}
+
+/// Treatment of dicts that contain the same key more than once
+///
+/// [RFC 8949 §5.6](https://www.rfc-editor.org/rfc/rfc8949#section-5.6) does not consider such
+/// dicts valid, since different decoders pick different values for the repeated key, but it leaves
+/// their detection to the application. Keys are compared after canonicalisation, so for example
+/// the number 1 is the same key regardless of its encoded width, `1.0` is the same key regardless
+/// of its floating-point precision, and dicts as keys are compared irrespective of their order.
+///
+/// ```rust
+/// use cbor_data::{Cbor, CborOwned, DuplicateKeys, ErrorKind, ValidationLimits};
+///
+/// // {"a": 1, "b": 2, "a": 3}
+/// let bytes = [0xa3, 0x61, b'a', 1, 0x61, b'b', 2, 0x61, b'a', 3];
+/// assert!(Cbor::checked(&bytes).is_ok());
+///
+/// let limits = ValidationLimits::new().with_duplicate_keys(DuplicateKeys::Reject);
+/// let error = Cbor::checked_with(&bytes, &limits).unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::DuplicateKey);
+/// assert_eq!(error.offset(), 7);
+///
+/// let limits = ValidationLimits::new().with_duplicate_keys(DuplicateKeys::KeepFirst);
+/// let cbor = CborOwned::canonical_with(&bytes, &limits).unwrap();
+/// assert_eq!(cbor.to_string(), r#"{"a": 1, "b": 2}"#);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// do not check for duplicate keys
+    ///
+    /// Deterministic encoding still rejects them, see
+    /// [`CborOwned::deterministic`](struct.CborOwned.html#method.deterministic).
+    #[default]
+    Allow,
+    /// reject the dict with [`ErrorKind::DuplicateKey`](enum.ErrorKind.html#variant.DuplicateKey)
+    /// at the second occurrence of the key
+    ///
+    /// Validation needs to canonicalise the item into a scratch buffer to find duplicates.
+    Reject,
+    /// keep only the first entry for each key when canonicalising, dropping the later ones
+    ///
+    /// This matches the value found by [`Cbor::index`](struct.Cbor.html#method.index) and
+    /// [`CborValue`](value/enum.CborValue.html). Validation accepts dicts with duplicate keys.
+    KeepFirst,
+}
+
+impl ValidationLimits {
+    /// Create a configuration without any limits.
+    pub const fn new() -> Self {
+        Self {
+            max_depth: None,
+            max_items: None,
+            max_string_bytes: None,
+            max_tags: None,
+            max_size: None,
+            duplicate_keys: DuplicateKeys::Allow,
+            error_path: false,
+        }
+    }
+
+    /// Configure the maximum nesting depth of arrays, dicts, and
+    /// [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings.
+    ///
+    /// A top-level array has depth 1, an array within it has depth 2, and so on.
+    /// Exceeding this limit yields [`ErrorKind::DepthExceeded`](enum.ErrorKind.html#variant.DepthExceeded).
+    pub const fn with_max_depth(self, max_depth: Option<usize>) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    /// Configure the maximum number of items in a single array or key–value pairs in a single dict.
+    ///
+    /// Exceeding this limit yields [`ErrorKind::TooManyItems`](enum.ErrorKind.html#variant.TooManyItems).
+    pub const fn with_max_items(self, max_items: Option<u64>) -> Self {
+        Self { max_items, ..self }
+    }
+
+    /// Configure the maximum number of bytes contained in all text and byte strings taken together.
+    ///
+    /// The contents of [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings
+    /// are counted again while validating the nested item.
+    /// Exceeding this limit yields [`ErrorKind::TooManyStringBytes`](enum.ErrorKind.html#variant.TooManyStringBytes).
+    pub const fn with_max_string_bytes(self, max_string_bytes: Option<u64>) -> Self {
+        Self {
+            max_string_bytes,
+            ..self
+        }
+    }
+
+    /// Configure the maximum number of tags applied to a single item.
+    ///
+    /// Exceeding this limit yields [`ErrorKind::TooManyTags`](enum.ErrorKind.html#variant.TooManyTags).
+    pub const fn with_max_tags(self, max_tags: Option<usize>) -> Self {
+        Self { max_tags, ..self }
+    }
+
+    /// Configure the maximum number of bytes taken up by the encoding of a single top-level item.
+    ///
+    /// This is most useful when reading a stream of items, where it bounds the amount of buffered
+    /// bytes while waiting for the rest of an item. Strings whose header declares a length beyond
+    /// this limit are rejected right away, reporting the offset of that header.
+    /// Exceeding this limit yields [`ErrorKind::ItemTooLarge`](enum.ErrorKind.html#variant.ItemTooLarge).
+    pub const fn with_max_size(self, max_size: Option<usize>) -> Self {
+        Self { max_size, ..self }
+    }
+
+    /// Configure the treatment of dicts that contain the same key more than once.
+    ///
+    /// The default is [`DuplicateKeys::Allow`](enum.DuplicateKeys.html#variant.Allow).
+    pub const fn with_duplicate_keys(self, duplicate_keys: DuplicateKeys) -> Self {
+        Self {
+            duplicate_keys,
+            ..self
+        }
+    }
+
+    /// Configure whether errors shall record the path from the top-level item to the problem.
+    ///
+    /// The path is available from [`ParseError::path`](struct.ParseError.html#method.path) and
+    /// is included in its display. Recording it costs some allocations when an error occurs.
+    ///
+    /// ```rust
+    /// use cbor_data::{Cbor, ValidationLimits};
+    ///
+    /// // {"items": [1, "\xff"]}
+    /// let bytes = [0xa1, 0x65, b'i', b't', b'e', b'm', b's', 0x82, 1, 0x61, 0xff];
+    /// let limits = ValidationLimits::new().with_error_path(true);
+    ///
+    /// let error = Cbor::checked_with(&bytes, &limits).unwrap_err();
+    /// assert!(error.to_string().ends_with("at offset 10 (path .items[1])"));
+    /// ```
+    pub const fn with_error_path(self, error_path: bool) -> Self {
+        Self { error_path, ..self }
+    }
+
+    /// Configured maximum nesting depth
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Configured maximum number of items per array or pairs per dict
+    pub fn max_items(&self) -> Option<u64> {
+        self.max_items
+    }
+
+    /// Configured maximum number of string bytes
+    pub fn max_string_bytes(&self) -> Option<u64> {
+        self.max_string_bytes
+    }
+
+    /// Configured maximum number of tags per item
+    pub fn max_tags(&self) -> Option<usize> {
+        self.max_tags
+    }
+
+    /// Configured maximum size of a top-level item in bytes
+    pub fn max_size(&self) -> Option<usize> {
+        self.max_size
+    }
+
+    /// Configured treatment of duplicate dict keys
+    pub fn duplicate_keys(&self) -> DuplicateKeys {
+        self.duplicate_keys
+    }
+
+    /// Whether errors record the path to the problem
+    pub fn error_path(&self) -> bool {
+        self.error_path
+    }
+}
+
+/// Accounting of the resources used so far while parsing a single top-level item
+pub(crate) struct Budget {
+    limits: ValidationLimits,
+    string_bytes: u64,
+}
+
+impl Budget {
+    pub fn new(limits: ValidationLimits) -> Self {
+        Self {
+            limits,
+            string_bytes: 0,
+        }
+    }
+
+    /// start accounting for the next top-level item
+    pub fn reset(&mut self) {
+        self.string_bytes = 0;
+    }
+
+    /// check that a container may be opened when `depth` containers are already open
+    pub fn enter(&self, depth: usize) -> Result<(), ErrorKind> {
+        match self.limits.max_depth {
+            Some(max) if depth >= max => Err(DepthExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    /// check that a container may hold `count` items
+    pub fn items(&self, count: u64) -> Result<(), ErrorKind> {
+        match self.limits.max_items {
+            Some(max) if count > max => Err(TooManyItems),
+            _ => Ok(()),
+        }
+    }
+
+    /// check that a string (fragment) of `len` bytes may be added, see [`add_string`](#method.add_string)
+    pub fn string(&self, len: u64) -> Result<(), ErrorKind> {
+        match self.limits.max_string_bytes {
+            Some(max) if self.string_bytes.saturating_add(len) > max => Err(TooManyStringBytes),
+            _ => Ok(()),
+        }
+    }
+
+    /// account for a string (fragment) of `len` bytes once its contents have been found valid
+    pub fn add_string(&mut self, len: u64) {
+        self.string_bytes = self.string_bytes.saturating_add(len);
+    }
+
+    pub fn max_size(&self) -> Option<usize> {
+        self.limits.max_size
+    }
+
+    /// check that an item may carry `count` tags
+    pub fn tags(&self, count: usize) -> Result<(), ErrorKind> {
+        match self.limits.max_tags {
+            Some(max) if count > max => Err(TooManyTags),
+            _ => Ok(()),
+        }
+    }
+}
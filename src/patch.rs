@@ -0,0 +1,794 @@
+//! [JSON Patch](https://www.rfc-editor.org/rfc/rfc6902) for CBOR items
+//!
+//! A patch is an array of operation dicts, each with an `op` key naming one of `add`, `remove`,
+//! `replace`, `move`, `copy` or `test`, a `path` key holding a
+//! [JSON Pointer](https://www.rfc-editor.org/rfc/rfc6901) to the target location, and the `value`
+//! or `from` key as required by the operation. The operations are applied one after the other,
+//! if one of them fails the whole patch fails with an error naming the index of that operation.
+//!
+//! Pointers are interpreted as for [`Cbor::pointer`](../struct.Cbor.html#method.pointer), so
+//! their reference tokens always address text string keys when applied to a dict. As an extension
+//! for CBOR, `path` and `from` may also be given as an array of keys: untagged text strings
+//! select dict entries as above, all other keys select the dict entry with exactly this encoding,
+//! or — if they are integers applied to an array — the item at this index. Negative indices
+//! count from the end like [`PathElement::FromEnd`](../enum.PathElement.html#variant.FromEnd),
+//! so `-1` denotes the last item, or the position after it for `add`. This is how [`diff::to_patch`](../diff/fn.to_patch.html) writes paths to
+//! integer keys like those of COSE or CWT maps. `test`
+//! compares values by their meaning instead of their encoding, e.g. `1` encoded in one or two
+//! bytes or dicts with differently ordered keys are equal. All parts of the document that are not
+//! touched by an operation are copied byte for byte.
+//!
+//! For the simpler [JSON Merge Patch](https://www.rfc-editor.org/rfc/rfc7386) format see
+//! [`Cbor::merge_patch`](../struct.Cbor.html#method.merge_patch).
+//!
+//! ```rust
+//! use cbor_data::{CborBuilder, Writer, patch};
+//!
+//! let doc = CborBuilder::new().write_dict(None, |b| {
+//!     b.with_key("name", |b| b.write_str("server", None));
+//!     b.with_key("ports", |b| b.write_array(None, |b| {
+//!         b.write_pos(80, None);
+//!     }));
+//! });
+//! let patch = CborBuilder::new().write_array(None, |b| {
+//!     b.write_dict(None, |b| {
+//!         b.with_key("op", |b| b.write_str("test", None));
+//!         b.with_key("path", |b| b.write_str("/name", None));
+//!         b.with_key("value", |b| b.write_str("server", None));
+//!     });
+//!     b.write_dict(None, |b| {
+//!         b.with_key("op", |b| b.write_str("add", None));
+//!         b.with_key("path", |b| b.write_str("/ports/-", None));
+//!         b.with_key("value", |b| b.write_pos(443, None));
+//!     });
+//! });
+//!
+//! let patched = patch::apply(&doc, &patch).unwrap();
+//! assert_eq!(patched.to_string(), r#"{"name": "server", "ports": [80, 443]}"#);
+//!
+//! let other = CborBuilder::new().write_dict(None, |b| {
+//!     b.with_key("name", |b| b.write_str("client", None));
+//! });
+//! let error = patch::apply(&other, &patch).unwrap_err();
+//! assert_eq!(error.operation(), 0);
+//! assert_eq!(error.to_string(), "test failed in operation 0");
+//! ```
+use crate::{
+    builder::low_level::{finish_array, write_indefinite, write_tags},
+    constants::MAJOR_DICT,
+    semantic::semantic_eq,
+    validated::Lengths,
+    Cbor, CborOwned, DictIter, ItemKind, PathElement, PointerStr, Semantic, Writer,
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+/// A parsed patch, see the [module documentation](index.html)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch<'a> {
+    operations: Vec<Operation<'a>>,
+}
+
+/// A single patch operation
+///
+/// Paths are the reference tokens of a JSON Pointer as yielded by
+/// [`PointerStr`](../struct.PointerStr.html), or the keys of a key path with every key other than
+/// a text string given as `PathElement::Item`. They are resolved against the document when the
+/// operation is applied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation<'a> {
+    /// Insert into an array, or set a dict entry, or replace the whole document
+    Add {
+        path: Vec<PathElement<'a>>,
+        value: &'a Cbor,
+    },
+    /// Remove an array item or dict entry
+    Remove { path: Vec<PathElement<'a>> },
+    /// Replace an existing array item or dict value, or the whole document
+    Replace {
+        path: Vec<PathElement<'a>>,
+        value: &'a Cbor,
+    },
+    /// Remove a value and add it at another location
+    Move {
+        from: Vec<PathElement<'a>>,
+        path: Vec<PathElement<'a>>,
+    },
+    /// Add a copy of a value at another location
+    Copy {
+        from: Vec<PathElement<'a>>,
+        path: Vec<PathElement<'a>>,
+    },
+    /// Check that the value at the location is equal to the given value
+    Test {
+        path: Vec<PathElement<'a>>,
+        value: &'a Cbor,
+    },
+}
+
+/// Error returned when a patch is malformed or one of its operations cannot be applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchError {
+    operation: usize,
+    message: &'static str,
+}
+
+impl PatchError {
+    /// Index of the failing operation within the patch
+    pub fn operation(&self) -> usize {
+        self.operation
+    }
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} in operation {}", self.message, self.operation)
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Parse the given patch and apply it to the document.
+pub fn apply(doc: &Cbor, patch: &Cbor) -> Result<CborOwned, PatchError> {
+    Patch::new(patch)?.apply(doc)
+}
+
+impl<'a> Patch<'a> {
+    /// Parse a patch given as an array of operation dicts.
+    ///
+    /// A patch that is not an array is reported as failing in operation 0.
+    pub fn new(patch: &'a Cbor) -> Result<Self, PatchError> {
+        let error = |operation, message| PatchError { operation, message };
+        let array = patch
+            .as_array_view()
+            .ok_or_else(|| error(0, "expected array of operations"))?;
+        let operations = array
+            .iter()
+            .enumerate()
+            .map(|(idx, op)| Operation::new(op).map_err(|message| error(idx, message)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { operations })
+    }
+
+    /// The operations of this patch
+    pub fn operations(&self) -> &[Operation<'a>] {
+        &self.operations
+    }
+
+    /// Apply all operations in order, failing if any one of them fails.
+    pub fn apply(&self, doc: &Cbor) -> Result<CborOwned, PatchError> {
+        let mut doc = doc.to_owned();
+        for (operation, op) in self.operations.iter().enumerate() {
+            doc = op
+                .apply(&doc)
+                .map_err(|message| PatchError { operation, message })?;
+        }
+        Ok(doc)
+    }
+}
+
+impl<'a> From<Vec<Operation<'a>>> for Patch<'a> {
+    fn from(operations: Vec<Operation<'a>>) -> Self {
+        Self { operations }
+    }
+}
+
+impl<'a> Operation<'a> {
+    fn new(op: &'a Cbor) -> Result<Self, &'static str> {
+        let dict = op.as_dict_view().ok_or("expected dict")?;
+        let pointer = |key, message| {
+            let pointer = dict.get(key).ok_or(message)?;
+            if let Some(keys) = pointer.as_array_view() {
+                return Ok(keys.iter().map(key_element).collect());
+            }
+            parse_pointer(pointer.try_str().map_err(|_| message)?).ok_or("invalid pointer")
+        };
+        let path = || pointer("path", "missing or invalid `path`");
+        let from = || pointer("from", "missing or invalid `from`");
+        let value = || dict.get("value").ok_or("missing `value`");
+        let name = dict
+            .get("op")
+            .and_then(|op| op.try_str().ok())
+            .ok_or("missing or invalid `op`")?;
+        Ok(match name.as_ref() {
+            "add" => Operation::Add {
+                path: path()?,
+                value: value()?,
+            },
+            "remove" => Operation::Remove { path: path()? },
+            "replace" => Operation::Replace {
+                path: path()?,
+                value: value()?,
+            },
+            "move" => Operation::Move {
+                from: from()?,
+                path: path()?,
+            },
+            "copy" => Operation::Copy {
+                from: from()?,
+                path: path()?,
+            },
+            "test" => Operation::Test {
+                path: path()?,
+                value: value()?,
+            },
+            _ => return Err("unknown `op`"),
+        })
+    }
+
+    /// Apply this operation, returning the message of the error if it fails.
+    fn apply(&self, doc: &Cbor) -> Result<CborOwned, &'static str> {
+        match self {
+            Operation::Add { path, value } => add(doc, path, value),
+            Operation::Remove { path } => {
+                let (path, _) = resolve(doc, path, false)?;
+                if path.is_empty() {
+                    return Err("cannot remove the whole document");
+                }
+                doc.with_removed(path).ok_or("path not found")
+            }
+            Operation::Replace { path, value } => {
+                let (path, _) = resolve(doc, path, false)?;
+                doc.index(path.iter().cloned()).ok_or("path not found")?;
+                doc.with_set(path, value).ok_or("path not found")
+            }
+            Operation::Move { from, path } => {
+                if from.len() < path.len() && path.starts_with(from) {
+                    return Err("cannot move a value into itself");
+                }
+                let value = get(doc, from)?;
+                let (from, _) = resolve(doc, from, false)?;
+                if from.is_empty() {
+                    return Ok(doc.to_owned());
+                }
+                let doc = doc.with_removed(from).ok_or("path not found")?;
+                add(&doc, path, &value)
+            }
+            Operation::Copy { from, path } => add(doc, path, &get(doc, from)?),
+            Operation::Test { path, value } => {
+                if semantic_eq(&get(doc, path)?, value) {
+                    Ok(doc.to_owned())
+                } else {
+                    Err("test failed")
+                }
+            }
+        }
+    }
+}
+
+/// Write the result of applying the [merge patch](https://www.rfc-editor.org/rfc/rfc7386) to the
+/// target, see [`Cbor::merge_patch`](../struct.Cbor.html#method.merge_patch).
+pub(crate) fn merge_patch<W: Writer>(
+    target: Option<&Cbor>,
+    patch: &Cbor,
+    mut writer: W,
+) -> W::Output {
+    let max_definite = writer.max_definite();
+    writer.bytes(|b| {
+        let mut lengths = Lengths::default();
+        let mut stack = Vec::new();
+        stack.extend(merge_open(target, patch, &mut lengths, b));
+
+        while let Some(merge) = stack.last_mut() {
+            let next = if let Some((key, value)) = merge
+                .target
+                .as_mut()
+                .and_then(|t| t.next_with(&mut lengths))
+            {
+                match merge.keys.get(&Semantic(key)) {
+                    None => {
+                        b.extend_from_slice(key.as_slice());
+                        b.extend_from_slice(value.as_slice());
+                        merge.count += 1;
+                        continue;
+                    }
+                    Some(&idx) => {
+                        merge.patch[idx].2 = true;
+                        let update = merge.patch[idx].1;
+                        if is_null(update) {
+                            continue;
+                        }
+                        b.extend_from_slice(key.as_slice());
+                        (Some(value), update)
+                    }
+                }
+            } else {
+                let rest = merge.patch.iter().enumerate().skip(merge.next);
+                match rest
+                    .filter(|(_, (_, value, applied))| !applied && !is_null(value))
+                    .map(|(idx, (key, value, _))| (idx, *key, *value))
+                    .next()
+                {
+                    Some((idx, key, value)) => {
+                        merge.next = idx + 1;
+                        b.extend_from_slice(key.as_slice());
+                        (None, value)
+                    }
+                    None => {
+                        finish_array(merge.count, b, merge.pos, MAJOR_DICT, max_definite);
+                        stack.pop();
+                        continue;
+                    }
+                }
+            };
+            merge.count += 1;
+            stack.extend(merge_open(next.0, next.1, &mut lengths, b));
+        }
+    });
+    writer.into_output()
+}
+
+/// like `try_null`, but without decoding arrays and dicts
+fn is_null(value: &Cbor) -> bool {
+    match value.tagged_item().kind() {
+        ItemKind::Array(_) | ItemKind::Dict(_) => false,
+        _ => value.try_null().is_ok(),
+    }
+}
+
+/// a dict whose merged entries are being written
+struct Merge<'a> {
+    /// remaining target entries
+    target: Option<DictIter<'a>>,
+    /// patch entries with a flag whether they have been applied to target entries already,
+    /// which is also set for all but the first occurrence of a key
+    patch: Vec<(&'a Cbor, &'a Cbor, bool)>,
+    /// index of the first patch entry for each key
+    keys: HashMap<Semantic<&'a Cbor>, usize>,
+    /// patch entry to continue with once all target entries have been written
+    next: usize,
+    /// position of the header in the output
+    pos: usize,
+    /// number of entries written
+    count: u64,
+}
+
+/// write the patch if it is not a dict, otherwise the header of the merged dict
+fn merge_open<'a>(
+    target: Option<&'a Cbor>,
+    patch: &'a Cbor,
+    lengths: &mut Lengths<'a>,
+    bytes: &mut Vec<u8>,
+) -> Option<Merge<'a>> {
+    let patch = match patch.as_dict_view() {
+        Some(patch) => patch,
+        None => {
+            bytes.extend_from_slice(patch.as_slice());
+            return None;
+        }
+    };
+    // a tagged target dict is merged as well, keeping its tags
+    let target = target.and_then(|target| match target.as_dict_view() {
+        Some(dict) => Some(dict.iter()),
+        None => match target.tagged_item().kind() {
+            ItemKind::Dict(iter) => {
+                write_tags(bytes, target.tags());
+                Some(iter)
+            }
+            _ => None,
+        },
+    });
+    let mut entries = Vec::new();
+    let mut iter = patch.iter();
+    while let Some((key, value)) = iter.next_with(lengths) {
+        entries.push((key, value, false));
+    }
+    let mut keys = HashMap::with_capacity(entries.len());
+    for (idx, entry) in entries.iter_mut().enumerate() {
+        // only the first occurrence of a key in the patch is used, as for lookups
+        entry.2 = *keys.entry(Semantic(entry.0)).or_insert(idx) != idx;
+    }
+    let pos = bytes.len();
+    write_indefinite(bytes, MAJOR_DICT);
+    Some(Merge {
+        target,
+        patch: entries,
+        keys,
+        next: 0,
+        pos,
+        count: 0,
+    })
+}
+
+fn parse_pointer(pointer: Cow<'_, str>) -> Option<Vec<PathElement<'_>>> {
+    Some(match pointer {
+        Cow::Borrowed(s) => PointerStr::new(s)?.collect(),
+        Cow::Owned(s) => PointerStr::new(&s)?.map(PathElement::into_owned).collect(),
+    })
+}
+
+fn key_element(key: &Cbor) -> PathElement<'_> {
+    match PathElement::from_key(key) {
+        elem @ PathElement::String(_) => elem,
+        _ => PathElement::Item(Cow::Borrowed(key)),
+    }
+}
+
+fn add(doc: &Cbor, path: &[PathElement<'_>], value: &Cbor) -> Result<CborOwned, &'static str> {
+    let (path, in_array) = resolve(doc, path, true)?;
+    if in_array {
+        doc.with_inserted(path, value)
+    } else {
+        doc.with_set(path, value)
+    }
+    .ok_or("path not found")
+}
+
+fn get(doc: &Cbor, path: &[PathElement<'_>]) -> Result<CborOwned, &'static str> {
+    let (path, _) = resolve(doc, path, false)?;
+    doc.index(path).map(Cow::into_owned).ok_or("path not found")
+}
+
+/// Translate reference tokens into path elements for the given document.
+///
+/// Tokens applied to dicts become text string keys, `-` applied to an array denotes the position
+/// after the last item if `append` is true. Keys of a key path applied to an array must be
+/// integers. Also returns whether the last token is applied to an array.
+fn resolve<'p>(
+    doc: &Cbor,
+    path: &[PathElement<'p>],
+    append: bool,
+) -> Result<(Vec<PathElement<'p>>, bool), &'static str> {
+    let mut resolved = Vec::with_capacity(path.len());
+    let mut current = Cow::Borrowed(doc);
+    let mut in_array = false;
+    for (idx, elem) in path.iter().enumerate() {
+        let last = idx + 1 == path.len();
+        // look through TAG_CBOR_ITEM byte strings to learn whether this is an array or a dict
+        let (is_array, is_dict) = match current.index(std::iter::empty()) {
+            Some(item) => match item.kind() {
+                ItemKind::Array(_) => (true, false),
+                ItemKind::Dict(_) => (false, true),
+                _ => (false, false),
+            },
+            None => (false, false),
+        };
+        let elem = match elem {
+            PathElement::Number(n) if is_dict => PathElement::String(Cow::Owned(n.to_string())),
+            PathElement::String(s) if is_array && append && last && s == "-" => {
+                PathElement::FromEnd(1)
+            }
+            PathElement::String(_) if is_array => return Err("invalid array index"),
+            PathElement::Item(key) if is_array => match key.kind() {
+                _ if !key.tags().is_empty() => return Err("invalid array index"),
+                ItemKind::Pos(n) => PathElement::Number(n),
+                ItemKind::Neg(n) if n < u64::MAX => PathElement::FromEnd(n + 1),
+                _ => return Err("invalid array index"),
+            },
+            _ if is_array || is_dict => elem.clone(),
+            _ => return Err("path not found"),
+        };
+        if !last {
+            current = match current {
+                Cow::Borrowed(cbor) => cbor.index([elem.clone()]),
+                Cow::Owned(cbor) => cbor
+                    .index([elem.clone()])
+                    .map(|c| Cow::Owned(c.into_owned())),
+            }
+            .ok_or("path not found")?;
+        }
+        in_array = is_array;
+        resolved.push(elem);
+    }
+    Ok((resolved, in_array))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, Operation, Patch};
+    use crate::{Cbor, CborOwned, PathElement};
+
+    fn cbor(diag: &[u8]) -> CborOwned {
+        CborOwned::canonical(diag).unwrap()
+    }
+
+    /// patch `[{"op": op, "path": path, key: value}]` where `key` is `value` or `from`
+    fn op(op: &str, path: &str, extra: Option<(&str, &Cbor)>) -> CborOwned {
+        use crate::{CborBuilder, Writer};
+        CborBuilder::new().write_array(None, |b| {
+            b.write_dict(None, |b| {
+                b.with_key("op", |b| b.write_str(op, None));
+                b.with_key("path", |b| b.write_str(path, None));
+                if let Some((key, value)) = extra {
+                    b.with_key(key, |b| b.write_item(value));
+                }
+            });
+        })
+    }
+
+    fn run(doc: &Cbor, patch: &Cbor) -> Result<String, String> {
+        apply(doc, patch)
+            .map(|c| c.to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn key_paths() {
+        use crate::{CborBuilder, Writer};
+        // {"a": [1, 2], 5: "five"}
+        let doc = cbor(&[
+            0xa2, 0x61, b'a', 0x82, 1, 2, 5, 0x64, b'f', b'i', b'v', b'e',
+        ]);
+        // paths are given as encoded arrays of keys
+        let patch = |op: &str, path: &[u8], from: Option<&[u8]>| {
+            CborBuilder::new().write_array(None, |b| {
+                b.write_dict(None, |b| {
+                    b.with_key("op", |b| b.write_str(op, None));
+                    b.with_key("path", |b| b.write_item(&cbor(path)));
+                    if let Some(from) = from {
+                        b.with_key("from", |b| b.write_item(&cbor(from)));
+                    }
+                });
+            })
+        };
+        let r = |op, path, from| run(&doc, &patch(op, path, from));
+
+        // ["a", -1] from [5]
+        assert_eq!(
+            r("move", &[0x82, 0x61, b'a', 0x20], Some(&[0x81, 5])).unwrap(),
+            r#"{"a": [1, 2, "five"]}"#
+        );
+        // ["a", 0] from ["a", -1]
+        assert_eq!(
+            r(
+                "copy",
+                &[0x82, 0x61, b'a', 0],
+                Some(&[0x82, 0x61, b'a', 0x20])
+            )
+            .unwrap(),
+            r#"{"a": [2, 1, 2], 5: "five"}"#
+        );
+        assert_eq!(r("remove", &[0x81, 5], None).unwrap(), r#"{"a": [1, 2]}"#);
+        // the text key "5" is not the integer key 5
+        assert_eq!(
+            r("remove", &[0x81, 0x61, b'5'], None).unwrap_err(),
+            "path not found in operation 0"
+        );
+        // ["a", "0"]
+        assert_eq!(
+            r("remove", &[0x82, 0x61, b'a', 0x61, b'0'], None).unwrap_err(),
+            "invalid array index in operation 0"
+        );
+    }
+
+    #[test]
+    fn operations() {
+        // {"a": [1, 2], "b": {"c": 3}, 5: "five"}
+        let doc = cbor(&[
+            0xa3, 0x61, b'a', 0x82, 1, 2, 0x61, b'b', 0xa1, 0x61, b'c', 3, 5, 0x64, b'f', b'i',
+            b'v', b'e',
+        ]);
+        let x = cbor(&[0x61, b'x']);
+        let value = Some(("value", &*x));
+        let from = |s: &str| cbor(&[&[0x60 + s.len() as u8][..], s.as_bytes()].concat());
+
+        let r = |op_name, path, extra| run(&doc, &op(op_name, path, extra));
+        assert_eq!(
+            r("add", "/a/1", value).unwrap(),
+            r#"{"a": [1, "x", 2], "b": {"c": 3}, 5: "five"}"#
+        );
+        assert_eq!(
+            r("add", "/a/-", value).unwrap(),
+            r#"{"a": [1, 2, "x"], "b": {"c": 3}, 5: "five"}"#
+        );
+        // numeric tokens are text string keys in dicts
+        assert_eq!(
+            r("add", "/5", value).unwrap(),
+            r#"{"a": [1, 2], "b": {"c": 3}, 5: "five", "5": "x"}"#
+        );
+        assert_eq!(
+            r("add", "/b/c", value).unwrap(),
+            r#"{"a": [1, 2], "b": {"c": "x"}, 5: "five"}"#
+        );
+        assert_eq!(r("add", "", value).unwrap(), r#""x""#);
+        assert_eq!(
+            r("add", "/a/3", value).unwrap_err(),
+            "path not found in operation 0"
+        );
+        assert_eq!(
+            r("add", "/a/x", value).unwrap_err(),
+            "invalid array index in operation 0"
+        );
+        assert_eq!(
+            r("add", "/x/y", value).unwrap_err(),
+            "path not found in operation 0"
+        );
+
+        assert_eq!(
+            r("remove", "/a/0", None).unwrap(),
+            r#"{"a": [2], "b": {"c": 3}, 5: "five"}"#
+        );
+        assert_eq!(
+            r("remove", "/b/d", None).unwrap_err(),
+            "path not found in operation 0"
+        );
+        assert_eq!(
+            r("remove", "/a/-", None).unwrap_err(),
+            "invalid array index in operation 0"
+        );
+
+        assert_eq!(
+            r("replace", "/b", value).unwrap(),
+            r#"{"a": [1, 2], "b": "x", 5: "five"}"#
+        );
+        assert_eq!(
+            r("replace", "/d", value).unwrap_err(),
+            "path not found in operation 0"
+        );
+
+        let f = from("/b/c");
+        assert_eq!(
+            r("move", "/a/0", Some(("from", &f))).unwrap(),
+            r#"{"a": [3, 1, 2], "b": {}, 5: "five"}"#
+        );
+        assert_eq!(
+            r("copy", "/a/0", Some(("from", &f))).unwrap(),
+            r#"{"a": [3, 1, 2], "b": {"c": 3}, 5: "five"}"#
+        );
+        let f = from("/b");
+        assert_eq!(
+            r("move", "/b/d", Some(("from", &f))).unwrap_err(),
+            "cannot move a value into itself in operation 0"
+        );
+        assert_eq!(
+            r("move", "/b", Some(("from", &f))).unwrap(),
+            r#"{"a": [1, 2], 5: "five", "b": {"c": 3}}"#
+        );
+
+        // semantic equality: 1 encoded with two bytes, reordered keys
+        let one = Cbor::checked(&[0x18, 1]).unwrap();
+        assert!(r("test", "/a/0", Some(("value", one))).is_ok());
+        let dict = cbor(&[0xa1, 0x61, b'c', 3]);
+        assert!(r("test", "/b", Some(("value", &dict))).is_ok());
+        assert_eq!(
+            r("test", "/a/1", Some(("value", one))).unwrap_err(),
+            "test failed in operation 0"
+        );
+    }
+
+    #[test]
+    fn parsing() {
+        let doc = cbor(&[0xa0]);
+        assert_eq!(
+            run(&doc, &cbor(&[0xa0])).unwrap_err(),
+            "expected array of operations in operation 0"
+        );
+        assert_eq!(run(&doc, &cbor(&[0x80])).unwrap(), "{}");
+        assert_eq!(
+            run(&doc, &op("add", "/a", None)).unwrap_err(),
+            "missing `value` in operation 0"
+        );
+        assert_eq!(
+            run(&doc, &op("frobnicate", "/a", None)).unwrap_err(),
+            "unknown `op` in operation 0"
+        );
+        assert_eq!(
+            run(&doc, &op("remove", "a", None)).unwrap_err(),
+            "invalid pointer in operation 0"
+        );
+        assert_eq!(
+            run(&doc, &op("copy", "/a", None)).unwrap_err(),
+            "missing or invalid `from` in operation 0"
+        );
+
+        let x = cbor(&[1]);
+        let patch = op("add", "/a~1b", Some(("value", &x)));
+        let patch = Patch::new(&patch).unwrap();
+        assert_eq!(
+            patch.operations(),
+            [Operation::Add {
+                path: vec![PathElement::String("a/b".into())],
+                value: &x,
+            }]
+        );
+        assert_eq!(patch.apply(&doc).unwrap().to_string(), r#"{"a/b": 1}"#);
+
+        // the second operation fails after the first succeeded
+        let patch = Patch::from(vec![
+            Operation::Remove {
+                path: vec![PathElement::String("a/b".into())],
+            },
+            Operation::Remove {
+                path: vec![PathElement::String("a/b".into())],
+            },
+        ]);
+        let doc = Cbor::checked(&[0xa1, 0x63, b'a', b'/', b'b', 1]).unwrap();
+        assert_eq!(patch.apply(doc).unwrap_err().operation(), 1);
+    }
+
+    #[test]
+    fn merge_patch() {
+        use crate::semantic::semantic_eq;
+        use serde_json::{json, Value};
+
+        fn c(json: Value) -> CborOwned {
+            CborOwned::canonical(serde_cbor::to_vec(&json).unwrap()).unwrap()
+        }
+        // examples from RFC 7386 appendix A
+        let cases = [
+            (json!({"a":"b"}), json!({"a":"c"}), json!({"a":"c"})),
+            (json!({"a":"b"}), json!({"b":"c"}), json!({"a":"b","b":"c"})),
+            (json!({"a":"b"}), json!({"a":null}), json!({})),
+            (
+                json!({"a":"b","b":"c"}),
+                json!({"a":null}),
+                json!({"b":"c"}),
+            ),
+            (json!({"a":["b"]}), json!({"a":"c"}), json!({"a":"c"})),
+            (json!({"a":"c"}), json!({"a":["b"]}), json!({"a":["b"]})),
+            (
+                json!({"a":{"b":"c"}}),
+                json!({"a":{"b":"d","c":null}}),
+                json!({"a":{"b":"d"}}),
+            ),
+            (json!({"a":[{"b":"c"}]}), json!({"a":[1]}), json!({"a":[1]})),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a":"b"}), json!(["c"]), json!(["c"])),
+            (json!({"a":"foo"}), json!(null), json!(null)),
+            (json!({"a":"foo"}), json!("bar"), json!("bar")),
+            (json!({"e":null}), json!({"a":1}), json!({"e":null,"a":1})),
+            (json!([1, 2]), json!({"a":"b","c":null}), json!({"a":"b"})),
+            (
+                json!({}),
+                json!({"a":{"bb":{"ccc":null}}}),
+                json!({"a":{"bb":{}}}),
+            ),
+        ];
+        for (target, patch, result) in cases {
+            let merged = c(target.clone()).merge_patch(&c(patch.clone()));
+            assert!(
+                semantic_eq(&merged, &c(result)),
+                "{} merged with {} gave {}",
+                target,
+                patch,
+                merged
+            );
+        }
+
+        // non-string keys, untouched values keep their encoding
+        // {1: 0x1801, [2]: {"x": 1, "y": 2}, 3: 3}
+        let target = Cbor::checked(&[
+            0xa3, 1, 0x18, 1, 0x81, 2, 0xa2, 0x61, b'x', 1, 0x61, b'y', 2, 3, 3,
+        ])
+        .unwrap();
+        // {0x1803: null, [2]: {"y": null}, 4: 4, 4: 5}
+        let patch = Cbor::checked(&[
+            0xa4, 0x18, 3, 0xf6, 0x81, 2, 0xa1, 0x61, b'y', 0xf6, 4, 4, 4, 5,
+        ])
+        .unwrap();
+        let merged = target.merge_patch(patch);
+        assert_eq!(
+            merged.as_slice(),
+            [0xa3, 1, 0x18, 1, 0x81, 2, 0xa1, 0x61, b'x', 1, 4, 4]
+        );
+
+        // tags are kept on target dicts, whereas tagged patch dicts are values
+        // 5({1: 6({2: 2}), 3: 3})
+        let target = Cbor::checked(&[0xc5, 0xa2, 1, 0xc6, 0xa1, 2, 2, 3, 3]).unwrap();
+        // {1: {4: 4}, 3: 7({})}
+        let patch = Cbor::checked(&[0xa2, 1, 0xa1, 4, 4, 3, 0xc7, 0xa0]).unwrap();
+        assert_eq!(
+            target.merge_patch(patch).to_string(),
+            "5({1: 6({2: 2, 4: 4}), 3: 7({})})"
+        );
+    }
+
+    #[test]
+    fn merge_patch_deep_nesting() {
+        const DEPTH: usize = 100_000;
+        // {0: {0: … {0: 1, 1: 1} …}, 1: 1} merged with {0: {0: … {0: null, 2: 2} …}, 1: 1}
+        let nest = |inner: &[u8]| {
+            let mut bytes = [0xa2u8, 0].repeat(DEPTH);
+            bytes.extend_from_slice(inner);
+            bytes.extend([1u8, 1].repeat(DEPTH));
+            bytes
+        };
+        let target = nest(&[0xa2, 0, 1, 1, 1]);
+        let patch = nest(&[0xa2, 0, 0xf6, 2, 2]);
+        let merged = Cbor::unchecked(&target).merge_patch(Cbor::unchecked(&patch));
+        assert_eq!(merged.as_slice(), nest(&[0xa2, 1, 1, 2, 2]).as_slice());
+    }
+}
@@ -0,0 +1,856 @@
+//! JSONPath-like queries over CBOR items
+//!
+//! A [`Query`](struct.Query.html) is compiled once from an expression and can then be run against
+//! any number of items. The expression consists of a leading `$` followed by steps, where the `$`
+//! may be omitted together with the dot before an initial key (as in `items[0].name`):
+//!
+//!  - `.name` or `['name']` selects the value for a text string key in a dict, where quoted
+//!    strings support the same escapes as in [`index_str`](../fn.index_str.html)
+//!  - `[n]` selects the array item at index `n`, where negative `n` counts from the end;
+//!    for dicts it selects the value for the unsigned integer key `n`
+//!  - `[start:end]` or `[start:end:step]` selects a slice of an array, all three parts are optional
+//!    and `start` and `end` may be negative
+//!  - `.*` or `[*]` selects all array items or dict values
+//!  - `[?(@.path)]` selects those array items or dict values that contain the given path, where
+//!    the path is written as for [`index_str`](../fn.index_str.html), with each step starting with
+//!    `.`, `[`, or `{` (like `@.name`, `@[-1]`, or `@{-3}`)
+//!  - `[?(@.path OP literal)]` selects those array items or dict values for which the value found
+//!    at the given path compares to the literal as specified, with `OP` being one of
+//!    `==`, `!=`, `<`, `<=`, `>`, `>=` and the literal being a number, a quoted string,
+//!    `true`, `false`, or `null`; numbers compare by exact value across all encodings (see
+//!    [`Number`](../value/enum.Number.html)), where literals with fraction or exponent are
+//!    taken as 64-bit floating-point numbers
+//!  - `..` instead of `.` before a step (like `..name` or `..[0]`) applies the step at any depth
+//!
+//! Matches are produced in the order in which they appear in the encoded bytes, each item is
+//! reported at most once. The search looks into
+//! [`TAG_CBOR_ITEM`](../constants/constant.TAG_CBOR_ITEM.html) byte strings of definite size,
+//! which is all of them after [`CborOwned::canonical`](../struct.CborOwned.html#method.canonical).
+//!
+//! ```rust
+//! use cbor_data::{CborBuilder, Writer, query::Query};
+//!
+//! let cbor = CborBuilder::new().write_dict(None, |b| {
+//!     b.with_key("items", |b| b.write_array(None, |b| {
+//!         for (id, kind) in [(1, "x"), (2, "y"), (3, "x")] {
+//!             b.write_dict(None, |b| {
+//!                 b.with_key("id", |b| b.write_pos(id, None));
+//!                 b.with_key("kind", |b| b.write_str(kind, None));
+//!             });
+//!         }
+//!     }));
+//! });
+//!
+//! let query = Query::new(r#"$.items[?(@.kind == "x")].id"#).unwrap();
+//! let ids = query.matches(&cbor).map(|(_path, id)| id.to_string()).collect::<Vec<_>>();
+//! assert_eq!(ids, ["1", "3"]);
+//!
+//! let query = Query::new("..id").unwrap();
+//! let (path, id) = query.matches(&cbor).last().unwrap();
+//! assert_eq!(id.to_string(), "3");
+//! assert_eq!(path.len(), 3);
+//! ```
+use crate::{
+    constants::{TAG_BIGDECIMAL, TAG_BIGFLOAT, TAG_CBOR_ITEM},
+    value::{CborValue, Number},
+    visit::Walk,
+    Cbor, IndexStr, IndexStrError, ItemKind, PathElement, TaggedItem, Visitor,
+};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::VecDeque,
+    convert::Infallible,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// A compiled query expression, see the [module documentation](index.html) for the syntax
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    steps: Vec<Step>,
+}
+
+/// Error returned when compiling a malformed query expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    offset: usize,
+    message: &'static str,
+}
+
+impl QueryError {
+    /// Byte offset within the expression at which the problem was found
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    /// whether the selector is applied at any depth below the current item
+    recursive: bool,
+    selector: Selector,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Key(String),
+    Index(i64),
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+        step: u64,
+    },
+    Wildcard,
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Filter {
+    path: Vec<PathElement<'static>>,
+    test: Option<(Op, Literal)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Number(Number<'static>),
+    Str(String),
+    Bool(bool),
+    Null,
+}
+
+/// the position of an item within its array or dict
+enum Child<'a> {
+    Index(u64),
+    Key(TaggedItem<'a>),
+}
+
+impl Selector {
+    /// whether the array length is needed to decide which items are selected
+    fn needs_len(&self) -> bool {
+        matches!(self, Selector::Index(_) | Selector::Slice { .. })
+    }
+
+    /// whether the child is selected, not considering filters
+    fn selects(&self, child: &Child<'_>, len: Option<u64>) -> bool {
+        match (self, child) {
+            (Selector::Wildcard, _) => true,
+            (Selector::Key(k), Child::Key(key)) => matches!(key.kind(), ItemKind::Str(s) if s == k),
+            (Selector::Index(i), Child::Key(key)) => {
+                matches!(key.kind(), ItemKind::Pos(p) if i128::from(p) == i128::from(*i))
+            }
+            (Selector::Index(i), Child::Index(idx)) => {
+                let i = i128::from(*i);
+                let i = match len {
+                    _ if i >= 0 => i,
+                    Some(len) => i + i128::from(len),
+                    None => return false,
+                };
+                i == i128::from(*idx)
+            }
+            (Selector::Slice { start, end, step }, Child::Index(idx)) => {
+                let len = match len {
+                    Some(len) => i128::from(len),
+                    None => return false,
+                };
+                let norm = |x: i64| {
+                    let x = i128::from(x);
+                    if x < 0 {
+                        (x + len).max(0)
+                    } else {
+                        x.min(len)
+                    }
+                };
+                let start = start.map_or(0, norm);
+                let end = end.map_or(len, norm);
+                let idx = i128::from(*idx);
+                idx >= start && idx < end && (idx - start) % i128::from(*step) == 0
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Filter {
+    fn test(&self, cbor: &Cbor) -> bool {
+        let value = match cbor.index(self.path.iter().cloned()) {
+            Some(value) => value,
+            None => return false,
+        };
+        let (op, literal) = match &self.test {
+            Some(test) => test,
+            None => return true,
+        };
+        let item = value.tagged_item();
+        let ordering = match item.kind() {
+            // containers other than big decimals and big floats are not compared, which also
+            // avoids collecting their contents
+            ItemKind::Array(_)
+                if !matches!(item.tags().single(), Some(TAG_BIGDECIMAL | TAG_BIGFLOAT)) =>
+            {
+                None
+            }
+            ItemKind::Dict(_) => None,
+            _ => compare(CborValue::new(item), literal),
+        };
+        match op {
+            Op::Eq => ordering == Some(Ordering::Equal),
+            Op::Ne => ordering != Some(Ordering::Equal),
+            Op::Lt => ordering == Some(Ordering::Less),
+            Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Op::Gt => ordering == Some(Ordering::Greater),
+            Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+/// compare the value to the literal, or `None` if they are not comparable
+fn compare(value: CborValue<'_>, literal: &Literal) -> Option<Ordering> {
+    match (value, literal) {
+        (CborValue::Str(s), Literal::Str(l)) => Some(s.as_ref().cmp(l.as_str())),
+        (CborValue::Number(n), Literal::Number(l)) => n.partial_cmp(l),
+        (CborValue::Bool(b), Literal::Bool(l)) => Some(b.cmp(l)),
+        (CborValue::Null, Literal::Null) => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+impl Query {
+    /// Compile the given expression.
+    pub fn new(expr: &str) -> Result<Self, QueryError> {
+        let mut parser = Parser { s: expr, pos: 0 };
+        let mut steps = Vec::new();
+        if !parser.eat("$") && !expr.is_empty() && !expr.starts_with(['.', '[']) {
+            // like `index_str`, permit the first key without leading dot
+            let key = parser.name(|c| c == '.' || c == '[')?;
+            steps.push(Step {
+                recursive: false,
+                selector: Selector::Key(key),
+            });
+        }
+        while parser.pos < expr.len() {
+            steps.push(parser.step()?);
+        }
+        Ok(Self { steps })
+    }
+
+    /// Find all items within the given item that match this query, together with their paths.
+    ///
+    /// An empty query matches the given item itself, with an empty path. The matches are found
+    /// lazily while iterating, so stopping early also stops the search.
+    pub fn matches<'a>(&self, cbor: &'a Cbor) -> Matches<'_, 'a> {
+        Matches {
+            eval: Eval {
+                steps: &self.steps,
+                frames: Vec::new(),
+                path: Vec::new(),
+                pending: vec![(0, None)],
+                matches: VecDeque::new(),
+                embedded: None,
+            },
+            walks: vec![Walk::new(cbor.tagged_item())],
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+/// Iterator over the matches of a [`Query`](struct.Query.html), yielding the path to each match
+/// and the matching item
+pub struct Matches<'q, 'a> {
+    eval: Eval<'q, 'a>,
+    /// visits of the item and the TAG_CBOR_ITEM byte strings currently being looked into
+    walks: Vec<Walk<'a>>,
+}
+
+impl<'q, 'a> Iterator for Matches<'q, 'a> {
+    type Item = (Vec<PathElement<'a>>, &'a Cbor);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(m) = self.eval.matches.pop_front() {
+                return Some(m);
+            }
+            let walk = self.walks.last_mut()?;
+            let more = match walk.step(&mut self.eval) {
+                Ok(more) => more,
+                Err(never) => match never {},
+            };
+            if !more {
+                self.walks.pop();
+            }
+            if let Some(embedded) = self.eval.embedded.take() {
+                self.walks.push(Walk::new(embedded.tagged_item()));
+            }
+        }
+    }
+}
+
+struct Parser<'s> {
+    s: &'s str,
+    pos: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn rest(&self) -> &'s str {
+        &self.s[self.pos..]
+    }
+
+    fn error(&self, message: &'static str) -> QueryError {
+        QueryError {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<(), QueryError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// take characters up to (excluding) the first one for which `end` is true
+    fn take_until(&mut self, end: impl Fn(char) -> bool) -> &'s str {
+        let rest = self.rest();
+        let len = rest.find(end).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn step(&mut self) -> Result<Step, QueryError> {
+        let recursive = self.eat("..");
+        let dot = recursive || self.eat(".");
+        let selector = if self.eat("[") {
+            self.skip_ws();
+            let selector = self.bracket()?;
+            self.skip_ws();
+            self.expect("]", "expected `]`")?;
+            selector
+        } else if !dot {
+            return Err(self.error("expected `.`, `..`, or `[`"));
+        } else if self.eat("*") {
+            Selector::Wildcard
+        } else {
+            Selector::Key(self.name(|c| c == '.' || c == '[')?)
+        };
+        Ok(Step {
+            recursive,
+            selector,
+        })
+    }
+
+    fn name(&mut self, end: impl Fn(char) -> bool) -> Result<String, QueryError> {
+        let name = self.take_until(end);
+        if name.is_empty() {
+            Err(self.error("expected key"))
+        } else {
+            Ok(name.to_owned())
+        }
+    }
+
+    fn bracket(&mut self) -> Result<Selector, QueryError> {
+        if self.eat("*") {
+            return Ok(Selector::Wildcard);
+        }
+        if self.eat("?") {
+            self.skip_ws();
+            self.expect("(", "expected `(`")?;
+            let filter = self.filter()?;
+            self.expect(")", "expected `)`")?;
+            return Ok(Selector::Filter(filter));
+        }
+        if let Some(key) = self.quoted()? {
+            return Ok(Selector::Key(key));
+        }
+        let start = self.int()?;
+        self.skip_ws();
+        if !self.eat(":") {
+            return start
+                .map(Selector::Index)
+                .ok_or_else(|| self.error("expected index, slice, key, `*`, or filter"));
+        }
+        self.skip_ws();
+        let end = self.int()?;
+        self.skip_ws();
+        let step = if self.eat(":") {
+            self.skip_ws();
+            let pos = self.pos;
+            match self.int()? {
+                None => 1,
+                Some(step) if step > 0 => step as u64,
+                Some(_) => {
+                    self.pos = pos;
+                    return Err(self.error("slice step must be positive"));
+                }
+            }
+        } else {
+            1
+        };
+        Ok(Selector::Slice { start, end, step })
+    }
+
+    fn int(&mut self) -> Result<Option<i64>, QueryError> {
+        let start = self.pos;
+        self.eat("-");
+        let digits = self.take_until(|c| !c.is_ascii_digit());
+        if digits.is_empty() {
+            self.pos = start;
+            return Ok(None);
+        }
+        match self.s[start..self.pos].parse() {
+            Ok(n) => Ok(Some(n)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("number out of range"))
+            }
+        }
+    }
+
+    /// parse a string in single or double quotes, with the escapes of quoted keys in
+    /// [`index_str`](../fn.index_str.html)
+    fn quoted(&mut self) -> Result<Option<String>, QueryError> {
+        if !self.rest().starts_with(['\'', '"']) {
+            return Ok(None);
+        }
+        let (s, len) = IndexStr::parse_quoted(self.rest()).map_err(|e| self.index_error(e))?;
+        self.pos += len;
+        Ok(Some(s.into_owned()))
+    }
+
+    /// translate an error from parsing the rest of the expression
+    fn index_error(&self, error: IndexStrError) -> QueryError {
+        QueryError {
+            offset: self.pos + error.offset(),
+            message: error.message(),
+        }
+    }
+
+    fn filter(&mut self) -> Result<Filter, QueryError> {
+        self.skip_ws();
+        self.expect("@", "expected `@`")?;
+        let (path, len) =
+            IndexStr::parse_prefix(self.rest(), |c| c.is_whitespace() || ")=!<>".contains(c))
+                .map_err(|e| self.index_error(e))?;
+        self.pos += len;
+        let path = path.into_iter().map(PathElement::into_owned).collect();
+        self.skip_ws();
+        let op = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ]
+        .iter()
+        .find(|(token, _)| self.eat(token))
+        .map(|(_, op)| *op);
+        let test = match op {
+            Some(op) => {
+                self.skip_ws();
+                let literal = self.literal()?;
+                self.skip_ws();
+                Some((op, literal))
+            }
+            None => None,
+        };
+        Ok(Filter { path, test })
+    }
+
+    fn literal(&mut self) -> Result<Literal, QueryError> {
+        if let Some(s) = self.quoted()? {
+            return Ok(Literal::Str(s));
+        }
+        for (token, literal) in [
+            ("true", Literal::Bool(true)),
+            ("false", Literal::Bool(false)),
+            ("null", Literal::Null),
+        ] {
+            if self.eat(token) {
+                return Ok(literal);
+            }
+        }
+        let start = self.pos;
+        let number = self.take_until(|c| !(c.is_ascii_digit() || "+-.eE".contains(c)));
+        if let Ok(n) = number.parse() {
+            Ok(Literal::Number(Number::Int(n)))
+        } else if let Ok(f) = number.parse() {
+            Ok(Literal::Number(Number::IEEE754(f)))
+        } else {
+            self.pos = start;
+            Err(self.error("expected number, string, `true`, `false`, or `null`"))
+        }
+    }
+}
+
+/// an array or dict whose children are being visited
+struct Frame {
+    /// steps to be applied to the children
+    states: Vec<usize>,
+    /// number of array items, if needed by the selectors
+    len: Option<u64>,
+    /// length of the path leading to the array or dict
+    path_len: usize,
+}
+
+/// Evaluate the query in a single pass over the item, tracking the set of steps that apply
+/// to the children of each open array or dict.
+struct Eval<'q, 'a> {
+    steps: &'q [Step],
+    frames: Vec<Frame>,
+    path: Vec<PathElement<'a>>,
+    /// steps that apply to the next item, along with the filter that it needs to pass
+    pending: Vec<(usize, Option<usize>)>,
+    matches: VecDeque<(Vec<PathElement<'a>>, &'a Cbor)>,
+    /// TAG_CBOR_ITEM contents to be visited next
+    embedded: Option<&'a Cbor>,
+}
+
+impl<'q, 'a> Eval<'q, 'a> {
+    /// determine the steps that apply to the children of this item, recording it if it matches
+    fn enter(&mut self, item: TaggedItem<'a>) -> Vec<usize> {
+        let steps = self.steps;
+        let mut states = self
+            .pending
+            .drain(..)
+            .filter(|(_, filter)| match filter {
+                Some(filter) => match &steps[*filter].selector {
+                    Selector::Filter(filter) => filter.test(item.cbor()),
+                    _ => true,
+                },
+                None => true,
+            })
+            .map(|(state, _)| state)
+            .collect::<Vec<_>>();
+        states.sort_unstable();
+        states.dedup();
+        if states.last() == Some(&steps.len()) {
+            states.pop();
+            self.matches.push_back((self.path.clone(), item.cbor()));
+        }
+        states
+    }
+
+    fn open(&mut self, item: TaggedItem<'a>, states: Vec<usize>) -> bool {
+        let len = match item.kind() {
+            ItemKind::Array(iter) if states.iter().any(|s| self.steps[*s].selector.needs_len()) => {
+                Some(iter.size().unwrap_or_else(|| iter.count() as u64))
+            }
+            _ => None,
+        };
+        let descend = !states.is_empty();
+        self.frames.push(Frame {
+            states,
+            len,
+            path_len: self.path.len(),
+        });
+        descend
+    }
+
+    fn close(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            self.path.truncate(frame.path_len);
+        }
+    }
+
+    /// compute the steps that apply to the given child of the current array or dict
+    fn child(&mut self, child: Child<'a>, elem: PathElement<'a>) -> bool {
+        let frame = self.frames.last().unwrap();
+        self.path.truncate(frame.path_len);
+        self.path.push(elem);
+        self.pending.clear();
+        for &state in &frame.states {
+            let step = &self.steps[state];
+            if step.recursive {
+                self.pending.push((state, None));
+            }
+            if let Selector::Filter(_) = step.selector {
+                self.pending.push((state + 1, Some(state)));
+            } else if step.selector.selects(&child, frame.len) {
+                self.pending.push((state + 1, None));
+            }
+        }
+        !self.pending.is_empty()
+    }
+}
+
+impl<'q, 'a> Visitor<'a, Infallible> for Eval<'q, 'a> {
+    fn visit_simple(&mut self, item: TaggedItem<'a>) -> Result<(), Infallible> {
+        if let (Some(TAG_CBOR_ITEM), ItemKind::Bytes(bytes)) = (item.tags().single(), item.kind()) {
+            if let Some(bytes) = bytes.as_slice() {
+                self.embedded = Some(Cbor::unchecked(bytes));
+                return Ok(());
+            }
+        }
+        self.enter(item);
+        Ok(())
+    }
+
+    fn visit_array_begin(
+        &mut self,
+        array: TaggedItem<'a>,
+        _size: Option<u64>,
+    ) -> Result<bool, Infallible> {
+        let states = self.enter(array);
+        Ok(self.open(array, states))
+    }
+
+    fn visit_array_index(
+        &mut self,
+        _array: TaggedItem<'a>,
+        index: u64,
+    ) -> Result<bool, Infallible> {
+        Ok(self.child(Child::Index(index), PathElement::Number(index)))
+    }
+
+    fn visit_array_end(&mut self, _array: TaggedItem<'a>) -> Result<(), Infallible> {
+        self.close();
+        Ok(())
+    }
+
+    fn visit_dict_begin(
+        &mut self,
+        dict: TaggedItem<'a>,
+        _size: Option<u64>,
+    ) -> Result<bool, Infallible> {
+        let states = self.enter(dict);
+        Ok(self.open(dict, states))
+    }
+
+    fn visit_dict_key(
+        &mut self,
+        _dict: TaggedItem<'a>,
+        key: TaggedItem<'a>,
+        _is_first: bool,
+    ) -> Result<bool, Infallible> {
+        let elem = match key.kind() {
+            ItemKind::Str(s) => PathElement::String(s.as_cow()),
+            ItemKind::Pos(n) => PathElement::Number(n),
+            _ => PathElement::Item(Cow::Borrowed(key.cbor())),
+        };
+        Ok(self.child(Child::Key(key), elem))
+    }
+
+    fn visit_dict_end(&mut self, _dict: TaggedItem<'a>) -> Result<(), Infallible> {
+        self.close();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Query;
+    use crate::{Cbor, CborOwned, PathElement};
+
+    fn q(expr: &str, cbor: &CborOwned) -> Vec<String> {
+        Query::new(expr)
+            .unwrap()
+            .matches(cbor)
+            .map(|(_, item)| item.to_string())
+            .collect()
+    }
+
+    fn sample() -> CborOwned {
+        // {"store": {"book": [{"title": "A", "price": 8}, {"title": "B", "price": 12.5, "isbn": "x"},
+        //  {"title": "C", "price": 22}], "bicycle": {"price": 19}}, "title": "T"}
+        let bytes = [
+            0xa2, 0x65, b's', b't', b'o', b'r', b'e', 0xa2, 0x64, b'b', b'o', b'o', b'k', 0x83,
+            0xa2, 0x65, b't', b'i', b't', b'l', b'e', 0x61, b'A', 0x65, b'p', b'r', b'i', b'c',
+            b'e', 8, 0xa3, 0x65, b't', b'i', b't', b'l', b'e', 0x61, b'B', 0x65, b'p', b'r', b'i',
+            b'c', b'e', 0xf9, 0x4a, 0x40, 0x64, b'i', b's', b'b', b'n', 0x61, b'x', 0xa2, 0x65,
+            b't', b'i', b't', b'l', b'e', 0x61, b'C', 0x65, b'p', b'r', b'i', b'c', b'e', 0x16,
+            0x67, b'b', b'i', b'c', b'y', b'c', b'l', b'e', 0xa1, 0x65, b'p', b'r', b'i', b'c',
+            b'e', 0x13, 0x65, b't', b'i', b't', b'l', b'e', 0x61, b'T',
+        ];
+        CborOwned::canonical(bytes).unwrap()
+    }
+
+    #[test]
+    fn steps() {
+        let cbor = sample();
+        assert_eq!(q("$.store.book[0].title", &cbor), [r#""A""#]);
+        assert_eq!(q("store['book'][-1].price", &cbor), ["22"]);
+        assert_eq!(
+            q("$.store.book[*].title", &cbor),
+            [r#""A""#, r#""B""#, r#""C""#]
+        );
+        assert_eq!(q("$.store.book[1:].title", &cbor), [r#""B""#, r#""C""#]);
+        assert_eq!(q("$.store.book[:-1].title", &cbor), [r#""A""#, r#""B""#]);
+        assert_eq!(q("$.store.book[::2].title", &cbor), [r#""A""#, r#""C""#]);
+        assert_eq!(q("$.store.book[5]", &cbor), Vec::<String>::new());
+        assert_eq!(q("$.store.*.price", &cbor), ["19"]);
+        assert_eq!(q("$..price", &cbor), ["8", "12.5", "22", "19"]);
+        assert_eq!(
+            q("..title", &cbor),
+            [r#""A""#, r#""B""#, r#""C""#, r#""T""#]
+        );
+        assert_eq!(q("$..book[0].price", &cbor), ["8"]);
+        assert_eq!(q("$..*..isbn", &cbor), [r#""x""#]);
+        assert_eq!(q("$", &cbor).len(), 1);
+        assert_eq!(q("$.title.x", &cbor), Vec::<String>::new());
+    }
+
+    #[test]
+    fn filters() {
+        let cbor = sample();
+        assert_eq!(q("$..book[?(@.isbn)].title", &cbor), [r#""B""#]);
+        assert_eq!(q("$..book[?(@.price < 10)].title", &cbor), [r#""A""#]);
+        assert_eq!(
+            q("$..book[?(@.price >= 12.5)].title", &cbor),
+            [r#""B""#, r#""C""#]
+        );
+        assert_eq!(q(r#"$..[?(@.title == "C")].price"#, &cbor), ["22"]);
+        assert_eq!(
+            q("$..book[?(@['title'] != 'A')].price", &cbor),
+            ["12.5", "22"]
+        );
+        assert_eq!(q("$.store[?(@.price)]", &cbor), [r#"{"price": 19}"#]);
+        assert_eq!(q("$.store.book[?(@ == 1)]", &cbor), Vec::<String>::new());
+        assert_eq!(
+            q("$.store[?(@[-1].price == 22)][0].title", &cbor),
+            [r#""A""#]
+        );
+        assert_eq!(
+            q(r#"$..book[?(@["\u{74}itle"] == "\u{43}")].price"#, &cbor),
+            ["22"]
+        );
+
+        // [{-3: "a\n"}, {1: "b"}]
+        let cbor = CborOwned::canonical([0x82, 0xa1, 0x22, 0x62, b'a', b'\n', 0xa1, 1, 0x61, b'b'])
+            .unwrap();
+        assert_eq!(q(r#"$[?(@{-3} == "a\n")]"#, &cbor), [r#"{-3: "a\n"}"#]);
+        assert_eq!(q("$[?(@{1})][1]", &cbor), [r#""b""#]);
+    }
+
+    #[test]
+    fn numbers() {
+        // [9007199254740993, 4([-1, 125]), 2(h'010000000000000000'), 5([1, 3]), 0.5]
+        let cbor = CborOwned::canonical([
+            0x85, 0x1b, 0, 0x20, 0, 0, 0, 0, 0, 1, 0xc4, 0x82, 0x20, 0x18, 0x7d, 0xc2, 0x49, 1, 0,
+            0, 0, 0, 0, 0, 0, 0, 0xc5, 0x82, 1, 3, 0xf9, 0x38, 0,
+        ])
+        .unwrap();
+        assert_eq!(
+            q("$[?(@ > 9007199254740992)]", &cbor),
+            ["9007199254740993", "2(h'010000000000000000')"]
+        );
+        assert_eq!(
+            q("$[?(@ == 9007199254740993)]", &cbor),
+            ["9007199254740993"]
+        );
+        assert_eq!(q("$[?(@ == 12.5)]", &cbor), ["4([-1, 125])"]);
+        assert_eq!(q("$[?(@ == 6)]", &cbor), ["5([1, 3])"]);
+        assert_eq!(q("$[?(@ < 6)]", &cbor), ["0.5"]);
+    }
+
+    #[test]
+    fn paths() {
+        let cbor = sample();
+        let query = Query::new("$..book[?(@.price > 20)].title").unwrap();
+        let matches = query.matches(&cbor).collect::<Vec<_>>();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].0,
+            [
+                PathElement::String("store".into()),
+                PathElement::String("book".into()),
+                PathElement::Number(2),
+                PathElement::String("title".into()),
+            ]
+        );
+        assert_eq!(
+            &*cbor.index(matches[0].0.iter().cloned()).unwrap(),
+            matches[0].1
+        );
+    }
+
+    #[test]
+    fn nested_and_indefinite() {
+        // [_ 1, 24(<<[2, 3]>>), [_ 4]]
+        let cbor = CborOwned::unchecked(vec![
+            0x9f, 1, 0xd8, 24, 0x43, 0x82, 2, 3, 0x9f, 4, 0xff, 0xff,
+        ]);
+        assert_eq!(q("[-1][-1]", &cbor), ["4"]);
+        assert_eq!(q("[1][1]", &cbor), ["3"]);
+        assert_eq!(q("[-2:]", &cbor), ["[2, 3]", "[_ 4]"]);
+        assert_eq!(q("..[0]", &cbor), ["1", "2", "4"]);
+    }
+
+    #[test]
+    fn lazy() {
+        // {_ "a": 1, "b": …} where the bytes after the first match are never looked at
+        let cbor = Cbor::unchecked(&[0xbf, 0x61, b'a', 1]);
+        let query = Query::new("a").unwrap();
+        let mut matches = query.matches(cbor);
+        assert_eq!(matches.next().unwrap().1.to_string(), "1");
+
+        let cbor = sample();
+        let query = Query::new("$..price").unwrap();
+        let mut matches = query.matches(&cbor);
+        assert_eq!(matches.next().unwrap().1.to_string(), "8");
+        assert_eq!(matches.count(), 3);
+    }
+
+    #[test]
+    fn errors() {
+        let e = |expr: &str| {
+            let error = Query::new(expr).unwrap_err();
+            (error.offset(), error.to_string())
+        };
+        assert_eq!(e("$x").0, 1);
+        assert_eq!(e("$.a[").0, 4);
+        assert_eq!(e("$.a[1").0, 5);
+        assert_eq!(e("$.a[::0]").0, 6);
+        assert_eq!(e("$.a['x]").0, 4);
+        assert_eq!(e("$.a[?(@.b == )]").0, 13);
+        assert_eq!(e("$.a[?(@. == 1)]").0, 8);
+        assert_eq!(e("$.a[?(@[x])]").0, 8);
+        assert_eq!(e("$.a['\\u{x}']").0, 5);
+        assert_eq!(
+            e("$.a[?(@.b == x)]").1,
+            "expected number, string, `true`, `false`, or `null` at offset 13"
+        );
+        assert_eq!(e("$..").0, 3);
+        assert!("$.a[?(@[0] < -1.5e3)]".parse::<Query>().is_ok());
+    }
+}
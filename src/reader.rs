@@ -29,7 +29,7 @@ pub enum Literal {
 
 #[inline]
 pub(crate) fn major(bytes: &[u8]) -> Option<u8> {
-    Some(*bytes.get(0)? >> 5)
+    Some(*bytes.first()? >> 5)
 }
 
 pub(crate) fn careful_literal(bytes: &[u8]) -> Option<(Literal, &[u8])> {
@@ -39,7 +39,7 @@ pub(crate) fn careful_literal(bytes: &[u8]) -> Option<(Literal, &[u8])> {
         2 => Some((Literal::L1(int as u8), rest)),
         3 => Some((Literal::L2(int as u16), rest)),
         5 => Some((Literal::L4(int as u32), rest)),
-        9 => Some((Literal::L8(int as u64), rest)),
+        9 => Some((Literal::L8(int), rest)),
         _ => None,
     }
 }
@@ -114,7 +114,7 @@ pub(crate) fn float(bytes: &[u8]) -> Option<(f64, &[u8], &[u8])> {
 
 pub(crate) fn tags(bytes: &[u8]) -> Option<(Tags<'_>, &'_ [u8])> {
     let mut remaining = bytes;
-    while let Some(value) = remaining.get(0) {
+    while let Some(value) = remaining.first() {
         if (*value >> 5) != MAJOR_TAG {
             break;
         }
@@ -178,7 +178,7 @@ mod tests {
         ];
 
         for (res, bytes) in cases {
-            let cbor = Cbor::unchecked(&*bytes);
+            let cbor = Cbor::unchecked(&bytes);
             assert!(
                 matches!(cbor.kind(), ItemKind::Str(s) if s == res),
                 "value was {:?}",
@@ -197,7 +197,7 @@ mod tests {
     #[test]
     fn float() {
         let bytes = vec![0xfau8, 0, 0, 51, 17];
-        let cbor = Cbor::unchecked(&*bytes);
+        let cbor = Cbor::unchecked(&bytes);
         assert_eq!(cbor.kind(), ItemKind::Float(1.8319174824118334e-41));
         let cbor = CborOwned::canonical(bytes).unwrap();
         assert_eq!(cbor.kind(), ItemKind::Float(1.8319174824118334e-41));
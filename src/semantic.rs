@@ -0,0 +1,587 @@
+use crate::{
+    constants::{
+        MAJOR_ARRAY, MAJOR_DICT, STOP_BYTE, TAG_BIGNUM_NEG, TAG_BIGNUM_POS, TAG_CBOR_ITEM,
+    },
+    reader::{major, tags},
+    validated::{skip, skip_with},
+    Cbor, ItemKind, TaggedItem,
+};
+use smallvec::SmallVec;
+use std::{
+    borrow::Cow,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ops::{Deref, Range},
+};
+
+/// Hashing according to the CBOR data model instead of the encoding
+///
+/// The hash is consistent with [`Cbor::semantic_eq`](struct.Cbor.html#method.semantic_eq),
+/// i.e. semantically equal items have the same hash, regardless of their encoding.
+/// See [`Semantic`](struct.Semantic.html) for a wrapper that uses this for `Hash` and `Eq`.
+pub trait SemanticHash {
+    fn semantic_hash<H: Hasher>(&self, state: &mut H);
+}
+
+impl SemanticHash for Cbor {
+    fn semantic_hash<H: Hasher>(&self, state: &mut H) {
+        semantic_hash(self, state)
+    }
+}
+
+/// Wrapper that compares and hashes CBOR items according to the data model instead of their
+/// encoding, e.g. for use as `HashMap` key
+///
+/// ```
+/// use cbor_data::{Cbor, Semantic};
+/// use std::collections::HashMap;
+///
+/// let mut map = HashMap::new();
+/// // {"a": 1, "b": 2}
+/// let cbor = Cbor::checked(b"\xa2\x61a\x01\x61b\x02").unwrap();
+/// map.insert(Semantic(cbor), "found");
+///
+/// // {_ "b": 2, "a": 1} with non-preferred encoding of 1
+/// let key = Cbor::checked(b"\xbf\x61b\x02\x61a\x18\x01\xff").unwrap();
+/// assert_eq!(map.get(&Semantic(key)), Some(&"found"));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Semantic<T>(pub T);
+
+impl<T: Deref<Target = Cbor>> PartialEq for Semantic<T> {
+    fn eq(&self, other: &Self) -> bool {
+        semantic_eq(&self.0, &other.0)
+    }
+}
+
+impl<T: Deref<Target = Cbor>> Eq for Semantic<T> {}
+
+impl<T: Deref<Target = Cbor>> Hash for Semantic<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        semantic_hash(&self.0, state)
+    }
+}
+
+/// an item reduced to what matters in the data model
+#[derive(PartialEq, Hash)]
+enum Value<'a> {
+    /// integer that fits into 64 bits, with the same meaning as `ItemKind::Neg` if negative
+    Int(bool, u64),
+    /// bignum magnitude without leading zeros, longer than 8 bytes
+    BigInt(bool, Cow<'a, [u8]>),
+    /// float bits, with all NaNs mapped to the same value
+    Float(u64),
+    Str(Cow<'a, str>),
+    Bytes(Cow<'a, [u8]>),
+    Bool(bool),
+    Null,
+    Undefined,
+    Simple(u8),
+    /// array with the given number of items
+    Array(u64),
+    /// dict with the given number of entries
+    Dict(u64),
+}
+
+impl<'a> Value<'a> {
+    fn into_owned(self) -> Value<'static> {
+        match self {
+            Value::Int(negative, n) => Value::Int(negative, n),
+            Value::BigInt(negative, n) => Value::BigInt(negative, Cow::Owned(n.into_owned())),
+            Value::Float(bits) => Value::Float(bits),
+            Value::Str(s) => Value::Str(Cow::Owned(s.into_owned())),
+            Value::Bytes(b) => Value::Bytes(Cow::Owned(b.into_owned())),
+            Value::Bool(b) => Value::Bool(b),
+            Value::Null => Value::Null,
+            Value::Undefined => Value::Undefined,
+            Value::Simple(s) => Value::Simple(s),
+            Value::Array(n) => Value::Array(n),
+            Value::Dict(n) => Value::Dict(n),
+        }
+    }
+}
+
+/// an item of a [`Tree`](struct.Tree.html), followed by its contents
+struct Node {
+    tags: SmallVec<[u64; 2]>,
+    value: Value<'static>,
+    /// index after the last node of the contents
+    end: usize,
+    /// hash of tags, value, and contents that is consistent with `semantic_eq`
+    digest: u64,
+}
+
+/// the semantic content of an item, flattened in pre-order
+///
+/// Building this with an explicit stack instead of recursion permits arbitrarily deep nesting,
+/// and the digests permit comparing dict entries after sorting them instead of pairwise.
+struct Tree {
+    nodes: Vec<Node>,
+}
+
+/// the input or the contents of a TAG_CBOR_ITEM byte string
+struct Buf<'a> {
+    bytes: Cow<'a, [u8]>,
+    /// end offsets of the arrays and dicts in pre-order, so that each is skipped only once
+    ends: Vec<usize>,
+    /// index of the next array or dict to be encountered
+    next: usize,
+}
+
+impl<'a> Buf<'a> {
+    fn new(bytes: Cow<'a, [u8]>) -> Self {
+        let base = bytes.as_ptr() as usize;
+        let mut ends = Vec::new();
+        let mut open = Vec::new();
+        skip_with(&bytes, |is_open, pos| {
+            if is_open {
+                open.push(ends.len());
+                ends.push(0);
+            } else {
+                ends[open.pop().unwrap()] = pos.as_ptr() as usize - base;
+            }
+        });
+        Self {
+            bytes,
+            ends,
+            next: 0,
+        }
+    }
+}
+
+/// position of the remaining contents of an array or dict within one of the buffers
+struct Cursor {
+    buf: usize,
+    pos: usize,
+    /// number of remaining items (counting keys and values separately for dicts)
+    size: Option<u64>,
+}
+
+impl Cursor {
+    /// position of the next item
+    fn next(&mut self, buf: &Buf<'_>) -> Option<Range<usize>> {
+        let bytes = &buf.bytes[self.pos..];
+        match &mut self.size {
+            Some(0) => return None,
+            Some(n) => *n -= 1,
+            None if bytes[0] == STOP_BYTE => return None,
+            None => {}
+        }
+        let start = self.pos;
+        self.pos = match major(tags(bytes).unwrap().1) {
+            Some(MAJOR_ARRAY) | Some(MAJOR_DICT) => buf.ends[buf.next],
+            _ => start + bytes.len() - skip(bytes).1.len(),
+        };
+        Some(start..self.pos)
+    }
+}
+
+impl Tree {
+    fn new(cbor: &Cbor) -> Self {
+        // the input plus the contents of the TAG_CBOR_ITEM byte strings being looked at
+        let mut bufs = vec![Buf::new(Cow::Borrowed(cbor.as_slice()))];
+        // open arrays and dicts with their node index, number of items seen, and buffers to keep
+        let mut stack = Vec::<(usize, Cursor, u64, usize)>::new();
+        let mut nodes = Vec::<Node>::new();
+        let mut next = Some((0, 0..cbor.as_slice().len()));
+
+        loop {
+            if let Some((buf, range)) = next.take() {
+                let keep = bufs.len();
+                let (buf, range) = embedded(&mut bufs, buf, range);
+                let bytes = &bufs[buf].bytes;
+                let (tags, value, contents) =
+                    normalise(Cbor::unchecked(&bytes[range]).tagged_item());
+                let index = nodes.len();
+                nodes.push(Node {
+                    tags,
+                    value: value.into_owned(),
+                    end: index + 1,
+                    digest: 0,
+                });
+                match contents {
+                    Some((rest, size)) => {
+                        let pos = rest.as_ptr() as usize - bytes.as_ptr() as usize;
+                        bufs[buf].next += 1;
+                        stack.push((index, Cursor { buf, pos, size }, 0, keep));
+                    }
+                    None => {
+                        bufs.truncate(keep);
+                        nodes[index].digest = digest(&nodes, index);
+                    }
+                }
+                continue;
+            }
+
+            let (index, cursor, count, keep) = match stack.last_mut() {
+                Some(open) => open,
+                None => break,
+            };
+            match cursor.next(&bufs[cursor.buf]) {
+                Some(range) => {
+                    *count += 1;
+                    next = Some((cursor.buf, range));
+                }
+                None => {
+                    let (index, count) = (*index, *count);
+                    bufs.truncate(*keep);
+                    stack.pop();
+                    let end = nodes.len();
+                    let node = &mut nodes[index];
+                    match &mut node.value {
+                        Value::Array(n) => *n = count,
+                        Value::Dict(n) => *n = count / 2,
+                        _ => {}
+                    }
+                    node.end = end;
+                    nodes[index].digest = digest(&nodes, index);
+                }
+            }
+        }
+
+        Self { nodes }
+    }
+
+    /// indices of the direct contents of the given node
+    fn contents(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        contents(&self.nodes, index)
+    }
+
+    /// key index, value index, and digest of the entries of the given dict, sorted by digest
+    fn entries(&self, index: usize) -> Vec<(u64, usize, usize)> {
+        let mut entries = Vec::new();
+        let mut contents = self.contents(index);
+        while let (Some(key), Some(value)) = (contents.next(), contents.next()) {
+            entries.push((entry_digest(&self.nodes, key, value), key, value));
+        }
+        entries.sort_unstable_by_key(|(digest, ..)| *digest);
+        entries
+    }
+
+    fn eq_at(&self, index: usize, other: &Tree, other_index: usize) -> bool {
+        let mut pending = vec![(index, other_index)];
+        while let Some((a, b)) = pending.pop() {
+            let (x, y) = (&self.nodes[a], &other.nodes[b]);
+            if x.digest != y.digest || x.tags != y.tags || x.value != y.value {
+                return false;
+            }
+            match x.value {
+                Value::Array(_) => pending.extend(self.contents(a).zip(other.contents(b))),
+                Value::Dict(_) => {
+                    let (xs, ys) = (self.entries(a), other.entries(b));
+                    let mut rest = (&xs[..], &ys[..]);
+                    while let (Some(x), Some(y)) = (rest.0.first(), rest.1.first()) {
+                        let len =
+                            |s: &[(u64, usize, usize)]| s.iter().take_while(|e| e.0 == x.0).count();
+                        let (xn, yn) = (len(rest.0), len(rest.1));
+                        if x.0 != y.0 || xn != yn {
+                            return false;
+                        }
+                        if xn == 1 {
+                            pending.push((x.1, y.1));
+                            pending.push((x.2, y.2));
+                        } else if !self.match_entries(&rest.0[..xn], other, &rest.1[..yn]) {
+                            // only equal entries (or hash collisions) end up here, so the
+                            // recursion depth is logarithmic in the size of the input
+                            return false;
+                        }
+                        rest = (&rest.0[xn..], &rest.1[yn..]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+
+    /// check that the entries can be paired up such that each pair is equal
+    fn match_entries(
+        &self,
+        xs: &[(u64, usize, usize)],
+        other: &Tree,
+        ys: &[(u64, usize, usize)],
+    ) -> bool {
+        // equality is transitive, so pairing each entry with the first equal one suffices
+        let mut matched = vec![false; ys.len()];
+        xs.iter().all(|(_, key, value)| {
+            let found = ys.iter().zip(matched.iter_mut()).find(|((_, k, v), m)| {
+                !**m && self.eq_at(*key, other, *k) && self.eq_at(*value, other, *v)
+            });
+            found.map(|(_, m)| *m = true).is_some()
+        })
+    }
+}
+
+fn contents(nodes: &[Node], index: usize) -> impl Iterator<Item = usize> + '_ {
+    let end = nodes[index].end;
+    let first = Some(index + 1).filter(|i| *i < end);
+    std::iter::successors(first, move |i| Some(nodes[*i].end).filter(|i| *i < end))
+}
+
+fn entry_digest(nodes: &[Node], key: usize, value: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (nodes[key].digest, nodes[value].digest).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// hash of the given node, whose contents’ digests have been computed already
+fn digest(nodes: &[Node], index: usize) -> u64 {
+    let node = &nodes[index];
+    let mut hasher = DefaultHasher::new();
+    (&node.tags, &node.value).hash(&mut hasher);
+    match node.value {
+        Value::Array(_) => {
+            for item in contents(nodes, index) {
+                nodes[item].digest.hash(&mut hasher);
+            }
+        }
+        Value::Dict(_) => {
+            // entry order does not matter, so combine the entry hashes commutatively
+            let mut sum = 0u64;
+            let mut contents = contents(nodes, index);
+            while let (Some(key), Some(value)) = (contents.next(), contents.next()) {
+                sum = sum.wrapping_add(entry_digest(nodes, key, value));
+            }
+            sum.hash(&mut hasher);
+        }
+        _ => {}
+    }
+    hasher.finish()
+}
+
+/// Compare two items according to the CBOR data model instead of their encoding.
+///
+/// Integers, strings and floats are compared by value, regardless of their encoded width or
+/// chunking; arrays item by item; dicts as sets of entries regardless of their order.
+/// [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings compare like their
+/// contents, all other tags need to match.
+///
+/// Following RFC 8949 §3.4.3, bignums are equal to integers of the same value, but integers
+/// are never equal to floats. Floats compare by their IEEE 754 value, i.e. `0.0` and `-0.0` are
+/// different while all NaNs are the same.
+pub(crate) fn semantic_eq(a: &Cbor, b: &Cbor) -> bool {
+    a.as_slice() == b.as_slice() || Tree::new(a).eq_at(0, &Tree::new(b), 0)
+}
+
+/// feed the semantic content of `cbor` into `state`, consistent with `semantic_eq`
+fn semantic_hash<H: Hasher>(cbor: &Cbor, state: &mut H) {
+    Tree::new(cbor).nodes[0].digest.hash(state);
+}
+
+/// split off the tags that carry meaning and reduce the item to its value, plus the encoded
+/// contents and their number of items for arrays and dicts
+#[allow(clippy::type_complexity)]
+fn normalise(
+    item: TaggedItem<'_>,
+) -> (SmallVec<[u64; 2]>, Value<'_>, Option<(&[u8], Option<u64>)>) {
+    let mut tags = item.tags().collect::<SmallVec<[u64; 2]>>();
+    let value = match item.kind() {
+        ItemKind::Pos(n) => Value::Int(false, n),
+        ItemKind::Neg(n) => Value::Int(true, n),
+        ItemKind::Float(f) if f.is_nan() => Value::Float(f64::NAN.to_bits()),
+        ItemKind::Float(f) => Value::Float(f.to_bits()),
+        ItemKind::Str(s) => Value::Str(s.as_cow()),
+        ItemKind::Bytes(b) => match tags.last() {
+            Some(&TAG_BIGNUM_POS) | Some(&TAG_BIGNUM_NEG) => {
+                let negative = tags.pop() == Some(TAG_BIGNUM_NEG);
+                bignum(negative, b.as_cow())
+            }
+            _ => Value::Bytes(b.as_cow()),
+        },
+        ItemKind::Bool(b) => Value::Bool(b),
+        ItemKind::Null => Value::Null,
+        ItemKind::Undefined => Value::Undefined,
+        ItemKind::Simple(s) => Value::Simple(s),
+        ItemKind::Array(iter) => {
+            return (tags, Value::Array(0), Some((iter.as_slice(), iter.size())))
+        }
+        ItemKind::Dict(iter) => {
+            let size = iter.size().map(|n| n * 2);
+            return (tags, Value::Dict(0), Some((iter.as_slice(), size)));
+        }
+    };
+    (tags, value, None)
+}
+
+fn bignum(negative: bool, bytes: Cow<'_, [u8]>) -> Value<'_> {
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    if bytes.len() - zeros <= 8 {
+        let n = bytes[zeros..]
+            .iter()
+            .fold(0u64, |n, b| (n << 8) | u64::from(*b));
+        return Value::Int(negative, n);
+    }
+    let bytes = match bytes {
+        Cow::Borrowed(b) => Cow::Borrowed(&b[zeros..]),
+        Cow::Owned(mut b) => {
+            b.drain(..zeros);
+            Cow::Owned(b)
+        }
+    };
+    Value::BigInt(negative, bytes)
+}
+
+/// strip TAG_CBOR_ITEM byte strings from the item at `range`, putting their contents into new
+/// buffers (copied only if not contiguous in the input)
+fn embedded<'a>(bufs: &mut Vec<Buf<'a>>, buf: usize, range: Range<usize>) -> (usize, Range<usize>) {
+    let (mut buf, mut range) = (buf, range);
+    loop {
+        let bytes = &bufs[buf].bytes;
+        let item = Cbor::unchecked(&bytes[range.clone()]).tagged_item();
+        let contents = match (item.tags().single(), item.kind()) {
+            (Some(TAG_CBOR_ITEM), ItemKind::Bytes(contents)) => contents,
+            _ => return (buf, range),
+        };
+        let contents = match (contents.as_slice(), bytes) {
+            (Some(slice), Cow::Borrowed(bytes)) => {
+                let start = slice.as_ptr() as usize - bytes.as_ptr() as usize;
+                Cow::Borrowed(&bytes[start..start + slice.len()])
+            }
+            _ => Cow::Owned(contents.to_vec()),
+        };
+        range = 0..contents.len();
+        bufs.push(Buf::new(contents));
+        buf = bufs.len() - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn hash(cbor: &Cbor) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        cbor.semantic_hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn c(bytes: &[u8]) -> &Cbor {
+        Cbor::checked(bytes).unwrap()
+    }
+
+    #[test]
+    fn equal() {
+        let pairs: &[(&[u8], &[u8])] = &[
+            // integer widths
+            (&[0x05], &[0x18, 0x05]),
+            (&[0x24], &[0x39, 0x00, 0x04]),
+            // float widths and NaNs
+            (&[0xf9, 0x3e, 0x00], &[0xfb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]),
+            (&[0xf9, 0x7e, 0x00], &[0xfa, 0x7f, 0xc0, 0x00, 0x01]),
+            // bignums
+            (
+                &[0x1b, 1, 0, 0, 0, 0, 0, 0, 0],
+                &[0xc2, 0x48, 1, 0, 0, 0, 0, 0, 0, 0],
+            ),
+            (&[0x20], &[0xc3, 0x41, 0x00]),
+            (&[0xc2, 0x40], &[0x00]),
+            (
+                &[0xc2, 0x49, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+                &[0xc2, 0x4a, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+            ),
+            (&[0xc1, 0xc2, 0x41, 0x07], &[0xc1, 0x07]),
+            // chunked strings
+            (
+                &[0x63, b'a', b'b', b'c'],
+                &[0x7f, 0x61, b'a', 0x62, b'b', b'c', 0xff],
+            ),
+            (&[0x42, 1, 2], &[0x5f, 0x41, 1, 0x40, 0x41, 2, 0xff]),
+            // containers
+            (&[0x82, 1, 2], &[0x9f, 1, 0x18, 2, 0xff]),
+            (
+                &[0xa2, 0x61, b'a', 1, 0x61, b'b', 2],
+                &[0xbf, 0x61, b'b', 2, 0x61, b'a', 1, 0xff],
+            ),
+            (&[0xa2, 1, 1, 1, 2], &[0xa2, 1, 2, 1, 1]),
+            // embedded items
+            (&[0xd8, 24, 0x42, 0x81, 0x01], &[0x81, 0x01]),
+            (&[0x81, 0xd8, 24, 0x41, 0x05], &[0x81, 0x05]),
+            (
+                &[0x82, 0xd8, 24, 0x5f, 0x41, 0x81, 0x41, 0x01, 0xff, 2],
+                &[0x82, 0x81, 1, 2],
+            ),
+        ];
+        for (a, b) in pairs {
+            let (a, b) = (c(a), c(b));
+            assert!(a.semantic_eq(b), "{} != {}", a, b);
+            assert!(b.semantic_eq(a), "{} != {}", b, a);
+            assert_eq!(hash(a), hash(b), "{} vs. {}", a, b);
+        }
+    }
+
+    #[test]
+    fn not_equal() {
+        let pairs: &[(&[u8], &[u8])] = &[
+            (&[0x01], &[0x21]),
+            (&[0x01], &[0xf9, 0x3c, 0x00]),
+            (&[0xf9, 0x00, 0x00], &[0xf9, 0x80, 0x00]),
+            (&[0x20], &[0xc2, 0x41, 0x00]),
+            (&[0xc1, 0x01], &[0x01]),
+            (&[0xc1, 0x01], &[0xc2, 0x01]),
+            (&[0x41, b'a'], &[0x61, b'a']),
+            (&[0xf4], &[0xf6]),
+            (&[0xf6], &[0xf7]),
+            (&[0x82, 1, 2], &[0x82, 2, 1]),
+            (&[0x81, 1], &[0x82, 1, 1]),
+            (&[0xa1, 1, 2], &[0xa1, 2, 1]),
+            (&[0xa2, 1, 1, 1, 2], &[0xa2, 1, 1, 1, 1]),
+            (&[0x80], &[0xa0]),
+        ];
+        for (a, b) in pairs {
+            let (a, b) = (c(a), c(b));
+            assert!(!a.semantic_eq(b), "{} == {}", a, b);
+            assert!(!b.semantic_eq(a), "{} == {}", b, a);
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        const DEPTH: usize = 100_000;
+        let check = |a: &[u8], b: &[u8], equal: bool| {
+            let (a, b) = (Cbor::unchecked(a), Cbor::unchecked(b));
+            assert_eq!(a.semantic_eq(b), equal);
+            assert_eq!(hash(a) == hash(b), equal);
+        };
+
+        // [[[…0…]]] vs. the same with indefinite size and a wider zero
+        let mut a = vec![0x81u8; DEPTH];
+        a.push(0);
+        let mut b = vec![0x9fu8; DEPTH];
+        b.extend([0x18, 0]);
+        b.extend(vec![0xffu8; DEPTH]);
+        check(&a, &b, true);
+        b[DEPTH + 1] = 1;
+        check(&a, &b, false);
+
+        // {0: {0: {…: 0}, 1: 1}, 1: 1} vs. the same with the entries swapped
+        let mut a = [0xa2u8, 0].repeat(DEPTH);
+        a.push(0);
+        a.extend([1u8, 1].repeat(DEPTH));
+        let mut b = [0xa2u8, 1, 1, 0].repeat(DEPTH);
+        b.push(0);
+        check(&a, &b, true);
+        *b.last_mut().unwrap() = 1;
+        check(&a, &b, false);
+    }
+
+    #[test]
+    fn map_key() {
+        let mut map = HashMap::new();
+        map.insert(Semantic(c(&[0x82, 1, 0x61, b'x'])), 1);
+        map.insert(Semantic(c(&[0xa1, 1, 2])), 2);
+        assert_eq!(
+            map.get(&Semantic(c(&[0x9f, 0x18, 1, 0x7f, 0x61, b'x', 0xff, 0xff]))),
+            Some(&1)
+        );
+        assert_eq!(
+            map.get(&Semantic(c(&[0xbf, 0xc2, 0x41, 1, 2, 0xff]))),
+            Some(&2)
+        );
+        assert_eq!(map.get(&Semantic(c(&[0xa1, 2, 1]))), None);
+        assert!(map
+            .insert(Semantic(c(&[0xd8, 24, 0x43, 0xa1, 1, 2])), 3)
+            .is_some());
+        assert_eq!(map.len(), 2);
+    }
+}
@@ -0,0 +1,382 @@
+use crate::{
+    builder::low_level::write_info,
+    codec::WriteCbor,
+    constants::*,
+    reader::{integer, major},
+    CborBuilder, CborOwned, ItemKind, ParseError, StreamDecoder, ValidationLimits,
+};
+use std::io::{self, Read, Write};
+
+pub(crate) const CHUNK_SIZE: usize = 65536;
+
+/// Reader for a [CBOR Sequence](https://www.rfc-editor.org/rfc/rfc8742), i.e. concatenated CBOR items
+///
+/// Each item is validated while reading, error offsets are counted from the start of the stream.
+/// Failures of the underlying reader are reported as [`ErrorKind::Io`](enum.ErrorKind.html#variant.Io),
+/// the original error is available from [`ParseError::io_error`](struct.ParseError.html#method.io_error).
+/// The iterator ends after the first error.
+///
+/// By default, [`TAG_CBOR_MARKER`](constants/constant.TAG_CBOR_MARKER.html) tags are removed from
+/// the start of each item, and an initial [RFC 9277](https://www.rfc-editor.org/rfc/rfc9277)
+/// sequence marker item `55800(h'424f52')` is skipped. Other first items tagged with 55800 are
+/// returned like any other item.
+///
+/// ```rust
+/// use cbor_data::CborSeqReader;
+///
+/// let bytes = [0xd9, 0xd9, 0xf7, 0x01, 0x82, 0x02, 0x03];
+/// let items = CborSeqReader::new(&bytes[..]).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(items.len(), 2);
+/// assert_eq!(items[0].to_string(), "1");
+/// assert_eq!(items[1].to_string(), "[2, 3]");
+/// ```
+pub struct CborSeqReader<R> {
+    reader: R,
+    decoder: StreamDecoder,
+    chunk: Vec<u8>,
+    markers: Markers,
+    done: bool,
+    failed: bool,
+}
+
+impl<R: Read> CborSeqReader<R> {
+    /// Read items without any limits besides well-formedness.
+    pub fn new(reader: R) -> Self {
+        Self::with_limits(reader, &ValidationLimits::new())
+    }
+
+    /// Read items, checking each one against the given limits.
+    pub fn with_limits(reader: R, limits: &ValidationLimits) -> Self {
+        Self {
+            reader,
+            decoder: StreamDecoder::with_limits(limits),
+            chunk: vec![0; CHUNK_SIZE],
+            markers: Markers::new(),
+            done: false,
+            failed: false,
+        }
+    }
+
+    /// Configure whether self-describe markers shall be removed (which is the default).
+    pub fn with_strip_marker(mut self, strip_marker: bool) -> Self {
+        self.markers.strip = strip_marker;
+        self
+    }
+
+    /// Stream offset at which the next item starts
+    pub fn position(&self) -> usize {
+        self.decoder.position()
+    }
+
+    /// Obtain a reference to the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Return the underlying reader, dropping bytes that were read but not yet decoded
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn next_item(&mut self) -> Result<Option<CborOwned>, ParseError> {
+        loop {
+            if let Some(item) = self.decoder.next_item()? {
+                return Ok(Some(item));
+            }
+            if self.done {
+                return Ok(None);
+            }
+            match self.reader.read(&mut self.chunk) {
+                Ok(0) => {
+                    self.done = true;
+                    self.decoder.finish();
+                }
+                Ok(n) => {
+                    self.decoder.feed(&self.chunk[..n]);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    let offset = self.decoder.position() + self.decoder.buffered();
+                    return Err(ParseError::io(offset, e));
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for CborSeqReader<R> {
+    type Item = Result<CborOwned, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            let item = match self.next_item() {
+                Ok(item) => item?,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            };
+            if let Some(item) = self.markers.filter(item) {
+                return Some(Ok(item));
+            }
+        }
+    }
+}
+
+/// Removal of self-describe markers from the items of a sequence
+pub(crate) struct Markers {
+    pub strip: bool,
+    first: bool,
+}
+
+impl Markers {
+    pub fn new() -> Self {
+        Self {
+            strip: true,
+            first: true,
+        }
+    }
+
+    /// strip the item’s markers, or drop it if it is the sequence marker
+    pub fn filter(&mut self, item: CborOwned) -> Option<CborOwned> {
+        if !self.strip {
+            return Some(item);
+        }
+        let first = std::mem::replace(&mut self.first, false);
+        let item = strip_marker(item);
+        if first && is_seq_marker(&item) {
+            None
+        } else {
+            Some(item)
+        }
+    }
+}
+
+/// the RFC 9277 sequence marker is the byte string `BOR` with the single tag 55800
+fn is_seq_marker(item: &CborOwned) -> bool {
+    item.tags().single() == Some(TAG_CBOR_SEQ_MARKER)
+        && matches!(item.kind(), ItemKind::Bytes(b) if b.as_cow() == &b"BOR"[..])
+}
+
+/// remove [`TAG_CBOR_MARKER`](constants/constant.TAG_CBOR_MARKER.html) tags from the start of the item
+fn strip_marker(item: CborOwned) -> CborOwned {
+    let mut rest = item.as_slice();
+    while major(rest) == Some(MAJOR_TAG) {
+        let (tag, _, r) = integer(rest).unwrap();
+        if tag != TAG_CBOR_MARKER {
+            break;
+        }
+        rest = r;
+    }
+    if rest.len() == item.as_slice().len() {
+        item
+    } else {
+        CborOwned::unchecked(rest)
+    }
+}
+
+/// encode the value into the buffer, optionally prefixed with a self-describe marker
+pub(crate) fn encode<T: WriteCbor + ?Sized>(buffer: &mut Vec<u8>, value: &T, marker: bool) {
+    if marker {
+        write_info(buffer, MAJOR_TAG, TAG_CBOR_MARKER);
+    }
+    value.write_cbor(CborBuilder::append_to(buffer));
+}
+
+/// Writer for a [CBOR Sequence](https://www.rfc-editor.org/rfc/rfc8742), i.e. concatenated CBOR items
+///
+/// ```rust
+/// use cbor_data::{CborSeqReader, CborSeqWriter};
+///
+/// let mut writer = CborSeqWriter::new(Vec::new()).with_marker(true);
+/// writer.write(&42i128).unwrap();
+/// writer.write(&"hello").unwrap();
+/// let bytes = writer.into_inner();
+/// assert_eq!(bytes, [0xd9, 0xd9, 0xf7, 0x18, 42, 0xd9, 0xd9, 0xf7, 0x65, b'h', b'e', b'l', b'l', b'o']);
+///
+/// let items = CborSeqReader::new(bytes.as_slice()).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(items[1].to_string(), r#""hello""#);
+/// ```
+pub struct CborSeqWriter<W> {
+    writer: W,
+    buffer: Vec<u8>,
+    marker: bool,
+}
+
+impl<W: Write> CborSeqWriter<W> {
+    /// Write items to the given writer, without self-describe markers.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+            marker: false,
+        }
+    }
+
+    /// Configure whether each item shall be prefixed with a self-describe marker
+    /// [`TAG_CBOR_MARKER`](constants/constant.TAG_CBOR_MARKER.html) (default is `false`).
+    pub fn with_marker(self, marker: bool) -> Self {
+        Self { marker, ..self }
+    }
+
+    /// Encode the given value and write it as the next item.
+    pub fn write<T: WriteCbor + ?Sized>(&mut self, value: &T) -> io::Result<()> {
+        self.buffer.clear();
+        encode(&mut self.buffer, value, self.marker);
+        self.writer.write_all(&self.buffer)
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Obtain a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Return the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CborSeqReader, CborSeqWriter};
+    use crate::{
+        constants::TAG_CBOR_SEQ_MARKER, CborBuilder, ErrorKind::*, ValidationLimits,
+        WhileParsing::*, Writer,
+    };
+    use std::{
+        error::Error,
+        io::{self, Read},
+    };
+
+    /// yields the bytes one by one, then fails if `error` is set
+    struct Trickle<'a>(&'a [u8], bool);
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((b, rest)) => {
+                    buf[0] = *b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                None if self.1 => Err(io::Error::new(
+                    io::ErrorKind::ConnectionReset,
+                    "peer went away",
+                )),
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let array = CborBuilder::new().write_array(None, |b| {
+            b.write_pos(1, None);
+            b.write_str("x", Some(12));
+        });
+        for marker in [false, true] {
+            let mut writer = CborSeqWriter::new(Vec::new()).with_marker(marker);
+            writer.write(&*array).unwrap();
+            writer.write(&5i128).unwrap();
+            writer.write(&array).unwrap();
+            let bytes = writer.into_inner();
+
+            let items = CborSeqReader::new(Trickle(&bytes, false))
+                .map(|x| x.unwrap().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(items, vec![r#"[1, 12("x")]"#, "5", r#"[1, 12("x")]"#]);
+
+            let items = CborSeqReader::new(bytes.as_slice())
+                .with_strip_marker(false)
+                .map(|x| x.unwrap().to_string())
+                .collect::<Vec<_>>();
+            if marker {
+                assert_eq!(items[1], "55799(5)");
+            } else {
+                assert_eq!(items[1], "5");
+            }
+        }
+    }
+
+    #[test]
+    fn sequence_marker() {
+        let mut writer = CborSeqWriter::new(Vec::new()).with_marker(true);
+        writer
+            .write(&*CborBuilder::new().write_bytes(b"BOR", [TAG_CBOR_SEQ_MARKER]))
+            .unwrap();
+        writer.write(&*CborBuilder::new().write_null(None)).unwrap();
+        let bytes = writer.into_inner();
+        assert_eq!(&bytes[..6], &[0xd9, 0xd9, 0xf7, 0xd9, 0xd9, 0xf8]);
+
+        let items = CborSeqReader::new(bytes.as_slice())
+            .map(|x| x.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec!["null"]);
+
+        let items = CborSeqReader::new(bytes.as_slice())
+            .with_strip_marker(false)
+            .map(|x| x.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(items, vec!["55799(55800(h'424f52'))", "55799(null)"]);
+
+        // only the exact marker content is skipped
+        for first in [
+            CborBuilder::new().write_bytes(b"BOB", [TAG_CBOR_SEQ_MARKER]),
+            CborBuilder::new().write_bytes(b"BOR", [TAG_CBOR_SEQ_MARKER, 1]),
+            CborBuilder::new().write_array([TAG_CBOR_SEQ_MARKER], |b| {
+                b.write_bytes(b"BOR", None);
+            }),
+        ] {
+            let mut writer = CborSeqWriter::new(Vec::new());
+            writer.write(&*first).unwrap();
+            writer.write(&*first).unwrap();
+            let items = CborSeqReader::new(writer.into_inner().as_slice())
+                .map(|x| x.unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(items, vec![first.clone(), first]);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let bytes = [1, 2, 0x82, 3];
+        let mut reader = CborSeqReader::new(Trickle(&bytes, false));
+        assert_eq!(reader.next().unwrap().unwrap().to_string(), "1");
+        assert_eq!(reader.next().unwrap().unwrap().to_string(), "2");
+        assert_eq!(reader.position(), 2);
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(
+            (error.offset(), error.kind()),
+            (4, UnexpectedEof(ArrayItem))
+        );
+        assert!(reader.next().is_none());
+
+        let mut reader = CborSeqReader::new(Trickle(&bytes, true));
+        reader.next().unwrap().unwrap();
+        reader.next().unwrap().unwrap();
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(
+            (error.offset(), error.kind()),
+            (4, Io(io::ErrorKind::ConnectionReset))
+        );
+        assert_eq!(error.to_string(), "I/O error `peer went away` at offset 4");
+        let source = error.source().unwrap().downcast_ref::<io::Error>();
+        assert_eq!(source.unwrap().to_string(), "peer went away");
+        assert!(reader.next().is_none());
+
+        let limits = ValidationLimits::new().with_max_items(Some(1));
+        let mut reader = CborSeqReader::with_limits(Trickle(&bytes, false), &limits);
+        reader.next().unwrap().unwrap();
+        reader.next().unwrap().unwrap();
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (2, TooManyItems));
+    }
+}
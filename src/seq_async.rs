@@ -0,0 +1,305 @@
+use crate::{
+    codec::WriteCbor,
+    seq::{encode, Markers, CHUNK_SIZE},
+    CborOwned, ParseError, StreamDecoder, ValidationLimits,
+};
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Asynchronous reader for a [CBOR Sequence](https://www.rfc-editor.org/rfc/rfc8742)
+///
+/// This is the asynchronous counterpart to [`CborSeqReader`](struct.CborSeqReader.html), with the
+/// same treatment of errors and self-describe markers. Use
+/// [`ValidationLimits::with_max_size`](struct.ValidationLimits.html#method.with_max_size)
+/// to bound the number of bytes buffered for a single item; strings that declare a larger size are
+/// rejected before their contents are read.
+///
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// use cbor_data::{AsyncCborSeqReader, ErrorKind, ValidationLimits};
+/// use futures_util::StreamExt;
+///
+/// let limits = ValidationLimits::new().with_max_size(Some(4));
+/// let bytes = [0x01, 0x82, 0x02, 0x03, 0x85, 1, 2, 3, 4, 5];
+/// let mut reader = AsyncCborSeqReader::with_limits(&bytes[..], &limits);
+///
+/// assert_eq!(reader.next().await.unwrap().unwrap().to_string(), "1");
+/// assert_eq!(reader.next().await.unwrap().unwrap().to_string(), "[2, 3]");
+/// let error = reader.next().await.unwrap().unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::ItemTooLarge);
+/// assert_eq!(error.offset(), 8);
+/// assert!(reader.next().await.is_none());
+/// # });
+/// ```
+pub struct AsyncCborSeqReader<R> {
+    reader: R,
+    decoder: StreamDecoder,
+    chunk: Vec<u8>,
+    markers: Markers,
+    done: bool,
+    failed: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncCborSeqReader<R> {
+    /// Read items without any limits besides well-formedness.
+    pub fn new(reader: R) -> Self {
+        Self::with_limits(reader, &ValidationLimits::new())
+    }
+
+    /// Read items, checking each one against the given limits.
+    pub fn with_limits(reader: R, limits: &ValidationLimits) -> Self {
+        Self {
+            reader,
+            decoder: StreamDecoder::with_limits(limits),
+            chunk: vec![0; CHUNK_SIZE],
+            markers: Markers::new(),
+            done: false,
+            failed: false,
+        }
+    }
+
+    /// Configure whether self-describe markers shall be removed (which is the default).
+    pub fn with_strip_marker(mut self, strip_marker: bool) -> Self {
+        self.markers.strip = strip_marker;
+        self
+    }
+
+    /// Stream offset at which the next item starts
+    pub fn position(&self) -> usize {
+        self.decoder.position()
+    }
+
+    /// Obtain a reference to the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Return the underlying reader, dropping bytes that were read but not yet decoded
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncCborSeqReader<R> {
+    type Item = Result<CborOwned, ParseError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.failed {
+            return Poll::Ready(None);
+        }
+        loop {
+            match this.decoder.next_item() {
+                Ok(Some(item)) => {
+                    if let Some(item) = this.markers.filter(item) {
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                    continue;
+                }
+                Ok(None) if this.done => return Poll::Ready(None),
+                Ok(None) => {}
+                Err(e) => {
+                    this.failed = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+            let mut buf = ReadBuf::new(&mut this.chunk);
+            match ready!(Pin::new(&mut this.reader).poll_read(cx, &mut buf)) {
+                Ok(()) if buf.filled().is_empty() => {
+                    this.done = true;
+                    this.decoder.finish();
+                }
+                Ok(()) => {
+                    this.decoder.feed(buf.filled());
+                }
+                Err(e) => {
+                    this.failed = true;
+                    let offset = this.decoder.position() + this.decoder.buffered();
+                    return Poll::Ready(Some(Err(ParseError::io(offset, e))));
+                }
+            }
+        }
+    }
+}
+
+/// Asynchronous writer for a [CBOR Sequence](https://www.rfc-editor.org/rfc/rfc8742)
+///
+/// This is the asynchronous counterpart to [`CborSeqWriter`](struct.CborSeqWriter.html). Items are
+/// encoded into an internal buffer which is written out when it grows large or when flushing.
+///
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// use cbor_data::AsyncCborSeqWriter;
+/// use futures_util::SinkExt;
+///
+/// let mut writer = AsyncCborSeqWriter::new(Vec::new());
+/// writer.send(42i128).await.unwrap();
+/// writer.send("hello").await.unwrap();
+/// assert_eq!(writer.into_inner(), [0x18, 42, 0x65, b'h', b'e', b'l', b'l', b'o']);
+/// # });
+/// ```
+pub struct AsyncCborSeqWriter<W> {
+    writer: W,
+    buffer: Vec<u8>,
+    written: usize,
+    marker: bool,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncCborSeqWriter<W> {
+    /// Write items to the given writer, without self-describe markers.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+            written: 0,
+            marker: false,
+        }
+    }
+
+    /// Configure whether each item shall be prefixed with a self-describe marker
+    /// [`TAG_CBOR_MARKER`](constants/constant.TAG_CBOR_MARKER.html) (default is `false`).
+    pub fn with_marker(self, marker: bool) -> Self {
+        Self { marker, ..self }
+    }
+
+    /// Obtain a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Return the underlying writer, dropping items that have not yet been flushed
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn poll_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.buffer.len() {
+            let n =
+                ready!(Pin::new(&mut self.writer).poll_write(cx, &self.buffer[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        self.buffer.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin, T: WriteCbor> Sink<T> for AsyncCborSeqWriter<W> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.buffer.len() >= CHUNK_SIZE {
+            this.poll_write_buffer(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> io::Result<()> {
+        let this = self.get_mut();
+        encode(&mut this.buffer, &item, this.marker);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AsyncCborSeqReader, AsyncCborSeqWriter};
+    use crate::{CborBuilder, ErrorKind::*, ValidationLimits, WhileParsing::*, Writer};
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::io::{duplex, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn duplex_roundtrip() {
+        let (client, server) = duplex(7);
+        let array = CborBuilder::new().write_array(None, |b| {
+            for i in 0..100 {
+                b.write_pos(i * 1000, None);
+            }
+        });
+
+        let write = async move {
+            let mut writer = AsyncCborSeqWriter::new(client).with_marker(true);
+            for i in 0..10u64 {
+                writer.feed(&*array).await.unwrap();
+                writer.feed(i128::from(i)).await.unwrap();
+            }
+            // the item type is only needed to pick a `Sink` implementation
+            SinkExt::<i128>::close(&mut writer).await.unwrap();
+        };
+        let read = async move {
+            AsyncCborSeqReader::new(server)
+                .map(|item| item.unwrap())
+                .collect::<Vec<_>>()
+                .await
+        };
+        let ((), items) = tokio::join!(write, read);
+
+        assert_eq!(items.len(), 20);
+        for (i, pair) in items.chunks(2).enumerate() {
+            assert_eq!(pair[0].as_slice()[..3], [0x98, 100, 0]);
+            assert_eq!(pair[1].to_string(), i.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let (mut client, server) = duplex(64);
+        let write = async move {
+            client.write_all(&[1, 0x82, 2]).await.unwrap();
+            client.write_all(&[3, 0x7f]).await.unwrap();
+            client.shutdown().await.unwrap();
+        };
+        let read = async move { AsyncCborSeqReader::new(server).collect::<Vec<_>>().await };
+        let ((), items) = tokio::join!(write, read);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[1].as_ref().unwrap().to_string(), "[2, 3]");
+        let error = items[2].as_ref().unwrap_err();
+        assert_eq!(
+            (error.offset(), error.kind()),
+            (5, UnexpectedEof(StringFragment))
+        );
+
+        let limits = ValidationLimits::new().with_max_size(Some(100));
+        let (mut client, server) = duplex(256);
+        let mut reader = AsyncCborSeqReader::with_limits(server, &limits);
+        client.write_all(&[1, 0x98, 200]).await.unwrap();
+        assert_eq!(reader.next().await.unwrap().unwrap().to_string(), "1");
+        client.write_all(&[0; 60]).await.unwrap();
+        client.write_all(&[0; 60]).await.unwrap();
+        let error = reader.next().await.unwrap().unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (101, ItemTooLarge));
+        assert!(reader.next().await.is_none());
+
+        // the declared length of a string is rejected without waiting for its contents
+        let (mut client, server) = duplex(256);
+        let mut reader = AsyncCborSeqReader::with_limits(server, &limits);
+        client.write_all(&[1, 0x59, 1, 0]).await.unwrap();
+        assert_eq!(reader.next().await.unwrap().unwrap().to_string(), "1");
+        let error = reader.next().await.unwrap().unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (1, ItemTooLarge));
+        assert!(reader.next().await.is_none());
+    }
+}
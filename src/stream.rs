@@ -0,0 +1,278 @@
+use crate::{
+    check::{Parser, Stop},
+    CborOwned, ParseError, ValidationLimits,
+};
+
+/// Incremental decoder for a stream of CBOR items that arrives in chunks
+///
+/// Bytes are added using [`feed`](#method.feed), which yields all items that have been completed
+/// by the new bytes. The parsing progress within an incomplete item is retained between calls, so
+/// each byte is examined only once regardless of how the stream is split into chunks. Once the
+/// stream has ended, call [`finish`](#method.finish) to learn whether an incomplete item remains.
+///
+/// Error offsets are counted from the beginning of the stream. After an error has been reported,
+/// the decoder does not yield any further items and discards all bytes fed to it.
+///
+/// ```rust
+/// use cbor_data::{ErrorKind, StreamDecoder, WhileParsing};
+///
+/// let mut decoder = StreamDecoder::new();
+///
+/// // the array is split across chunks
+/// let items = decoder.feed(&[0x01, 0x82, 0x02]).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(items.len(), 1);
+/// assert_eq!(items[0].to_string(), "1");
+///
+/// let items = decoder.feed(&[0x03, 0x61]).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(items.len(), 1);
+/// assert_eq!(items[0].to_string(), "[2, 3]");
+///
+/// // the stream ends in the middle of the string
+/// let error = decoder.finish().next().unwrap().unwrap_err();
+/// assert_eq!(error.kind(), ErrorKind::UnexpectedEof(WhileParsing::StringValue));
+/// assert_eq!(error.offset(), 5);
+/// ```
+pub struct StreamDecoder {
+    parser: Parser,
+    buffer: Vec<u8>,
+    /// start of the current item within the buffer
+    start: usize,
+    /// stream offset of the start of the buffer
+    offset: usize,
+    finished: bool,
+    error: Option<ParseError>,
+}
+
+impl Default for StreamDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamDecoder {
+    /// Create a decoder that only rejects malformed bytes.
+    pub fn new() -> Self {
+        Self::with_limits(&ValidationLimits::new())
+    }
+
+    /// Create a decoder that checks each item against the given limits.
+    pub fn with_limits(limits: &ValidationLimits) -> Self {
+        Self {
+            parser: Parser::new(*limits),
+            buffer: Vec::new(),
+            start: 0,
+            offset: 0,
+            finished: false,
+            error: None,
+        }
+    }
+
+    /// Add the next chunk of the stream, returning an iterator over the items completed by it.
+    ///
+    /// Items not taken from the iterator remain available for the next call to `feed`,
+    /// [`finish`](#method.finish), or [`next_item`](#method.next_item).
+    ///
+    /// # Panics
+    ///
+    /// if called after [`finish`](#method.finish)
+    pub fn feed(&mut self, bytes: &[u8]) -> StreamItems<'_> {
+        assert!(!self.finished, "StreamDecoder fed after finish()");
+        if self.error.is_some() {
+            // nothing after the error will be parsed, so don't keep it
+            return StreamItems(self);
+        }
+        if self.start > 0 {
+            // only the bytes of the incomplete item need to be moved
+            self.buffer.drain(..self.start);
+            self.offset += self.start;
+            self.start = 0;
+        }
+        self.buffer.extend_from_slice(bytes);
+        StreamItems(self)
+    }
+
+    /// Declare the end of the stream, returning an iterator over the remaining items.
+    ///
+    /// If the stream ends within an item, the iterator’s last element is an
+    /// [`UnexpectedEof`](enum.ErrorKind.html#variant.UnexpectedEof) error.
+    pub fn finish(&mut self) -> StreamItems<'_> {
+        self.finished = true;
+        StreamItems(self)
+    }
+
+    /// Return the next complete item, or `None` if more bytes are needed.
+    ///
+    /// After [`finish`](#method.finish), `None` signals the regular end of the stream.
+    pub fn next_item(&mut self) -> Result<Option<CborOwned>, ParseError> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        let input = &self.buffer[self.start..];
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match self.parser.parse(input, None) {
+            Ok(end) => {
+                let item = CborOwned::unchecked(&input[..end]);
+                self.start += end;
+                self.parser.reset();
+                Ok(Some(item))
+            }
+            Err(Stop::Incomplete(_)) if !self.finished => Ok(None),
+            Err(stop) => {
+                let error = stop.into_error().shifted(self.position());
+                self.error = Some(error.clone());
+                Err(error)
+            }
+        }
+    }
+
+    /// Stream offset at which the next item starts
+    pub fn position(&self) -> usize {
+        self.offset + self.start
+    }
+
+    /// Number of bytes received but not yet returned as part of an item
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+}
+
+/// Iterator over the items decoded by a [`StreamDecoder`](struct.StreamDecoder.html)
+///
+/// The iterator ends when more bytes are needed or after yielding an error.
+pub struct StreamItems<'a>(&'a mut StreamDecoder);
+
+impl<'a> Iterator for StreamItems<'a> {
+    type Item = Result<CborOwned, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.error.is_some() {
+            return None;
+        }
+        self.0.next_item().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamDecoder;
+    use crate::{
+        CborBuilder, DuplicateKeys, ErrorKind::*, ValidationLimits, WhileParsing::*, Writer,
+    };
+
+    fn sequence() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(CborBuilder::new().write_pos(500, None).as_slice());
+        bytes.extend_from_slice(&[0x7f, 0x62, b'a', b'b', 0x61, b'c', 0xff]);
+        bytes.extend_from_slice(&[0xd8, 24, 0x5f, 0x41, 0x82, 0x42, 1, 0x9f, 0x41, 0xff, 0xff]);
+        bytes.extend_from_slice(&[0xbf, 0x61, b'x', 0x80, 0xff]);
+        bytes
+    }
+
+    #[test]
+    fn chunk_sizes() {
+        let bytes = sequence();
+        for chunk in 1..=bytes.len() {
+            let mut decoder = StreamDecoder::new();
+            let mut items = Vec::new();
+            for c in bytes.chunks(chunk) {
+                for item in decoder.feed(c) {
+                    items.push(item.unwrap().to_string());
+                }
+            }
+            assert_eq!(decoder.finish().count(), 0);
+            assert_eq!(
+                items,
+                vec!["500", r#"(_ "ab", "c")"#, "<_ [1, [_ ]]>", r#"{_ "x": []}"#],
+                "chunk size {}",
+                chunk
+            );
+            assert_eq!(decoder.position(), bytes.len());
+            assert_eq!(decoder.buffered(), 0);
+        }
+    }
+
+    #[test]
+    fn incomplete() {
+        let bytes = sequence();
+        for len in 0..bytes.len() {
+            let mut decoder = StreamDecoder::new();
+            let complete = decoder.feed(&bytes[..len]).collect::<Result<Vec<_>, _>>();
+            let complete = complete.unwrap().len();
+            let rest = decoder.finish().collect::<Vec<_>>();
+            let expected = [0, 3, 10, 21, 26].iter().filter(|x| **x <= len).count() - 1;
+            assert_eq!(complete, expected, "len {}", len);
+            if [3, 10, 21].contains(&len) || len == 0 {
+                assert!(rest.is_empty(), "len {}", len);
+            } else {
+                let error = rest[0].as_ref().unwrap_err();
+                assert!(matches!(error.kind(), UnexpectedEof(_)), "len {}", len);
+                assert_eq!(rest.len(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut decoder = StreamDecoder::new();
+        assert_eq!(decoder.feed(&[1, 2, 0x82, 1]).count(), 2);
+        assert_eq!(decoder.feed(&[0x7f]).count(), 0);
+        let error = decoder.feed(&[0x41]).next().unwrap().unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (5, InvalidStringFragment));
+        assert_eq!(decoder.feed(&[]).count(), 0);
+        assert_eq!(decoder.next_item().unwrap_err(), error);
+        let buffered = decoder.buffered();
+        for _ in 0..3 {
+            assert_eq!(decoder.feed(&[1; 1000]).count(), 0);
+            assert_eq!(decoder.buffered(), buffered);
+        }
+
+        let mut decoder = StreamDecoder::new();
+        assert_eq!(decoder.feed(&[1, 0x18]).count(), 1);
+        assert_eq!(decoder.feed(&[]).count(), 0);
+        let error = decoder.finish().next().unwrap().unwrap_err();
+        assert_eq!(
+            (error.offset(), error.kind()),
+            (2, UnexpectedEof(HeaderValue))
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let mut decoder =
+            StreamDecoder::with_limits(&ValidationLimits::new().with_max_string_bytes(Some(3)));
+        assert_eq!(decoder.feed(&[0x43, 1, 2, 3, 0x82]).count(), 1);
+        // the declared length is rejected before its bytes arrive
+        let error = decoder
+            .feed(&[0x5a, 0, 1, 0, 0])
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (5, TooManyStringBytes));
+
+        let mut decoder =
+            StreamDecoder::with_limits(&ValidationLimits::new().with_max_size(Some(4)));
+        assert_eq!(decoder.feed(&[0x83, 1, 2, 3, 0x9f, 1]).count(), 1);
+        assert_eq!(decoder.feed(&[2]).count(), 0);
+        // the stop byte would be the fifth byte of the item
+        let error = decoder.feed(&[3]).next().unwrap().unwrap_err();
+        assert_eq!((error.offset(), error.kind()), (8, ItemTooLarge));
+
+        let limits = ValidationLimits::new().with_duplicate_keys(DuplicateKeys::Reject);
+        let bytes = [
+            0xa2, 0, 0xa1, 0, 0, 1, 0, 0xbf, 0, 0x61, b'a', 0, 0x61, b'a', 0xff,
+        ];
+        for chunk in 1..=bytes.len() {
+            let mut decoder = StreamDecoder::with_limits(&limits);
+            let items = bytes
+                .chunks(chunk)
+                .flat_map(|c| decoder.feed(c).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            assert_eq!(items.len(), 2, "chunk size {}", chunk);
+            assert_eq!(items[0].as_ref().unwrap().to_string(), "{0: {0: 0}, 1: 0}");
+            let error = items[1].as_ref().unwrap_err();
+            assert_eq!((error.offset(), error.kind()), (11, DuplicateKey));
+        }
+    }
+}
@@ -143,7 +143,7 @@ pub(crate) fn hex(s: &str) -> Vec<u8> {
 fn str_to_cbor(s: &str, trusting: bool) -> CborOwned {
     let v = hex(s);
     if trusting {
-        Cbor::checked(&*v).unwrap().to_owned()
+        Cbor::checked(&v).unwrap().to_owned()
     } else {
         CborOwned::canonical(v).unwrap()
     }
@@ -344,3 +344,349 @@ fn roundtrip_non_string_map() {
         "4([5, {\"1\": -667, 1(2): h'646566646566'}, [false, \"hello\"], 12345(null)])"
     );
 }
+
+#[test]
+fn views() {
+    // {_ "a": 1, 1(2): 3, "a": 4, "b": [_ 5, 6]}
+    let cbor = Cbor::checked(&[
+        0xbf, 0x61, b'a', 1, 0xc1, 2, 3, 0x61, b'a', 4, 0x61, b'b', 0x9f, 5, 6, 0xff, 0xff,
+    ])
+    .unwrap();
+    let dict = cbor.as_dict_view().unwrap();
+    assert_eq!(dict.len(), 4);
+    assert!(!dict.is_empty());
+    assert_eq!(dict.get("a").unwrap().to_string(), "1");
+    assert_eq!(dict.get("c"), None);
+    assert_eq!(
+        dict.get_item(Cbor::unchecked(&[0xc1, 2]))
+            .unwrap()
+            .to_string(),
+        "3"
+    );
+    assert_eq!(dict.get_item(Cbor::unchecked(&[2])), None);
+    let (key, value) = dict.get_index(3).unwrap();
+    assert_eq!(
+        (key.to_string(), value.to_string()),
+        (r#""b""#.to_owned(), "[_ 5, 6]".to_owned())
+    );
+    assert_eq!(dict.get_index(4), None);
+    assert_eq!(format!("{:?}", dict).matches("Cbor(").count(), 8);
+    assert!(cbor.as_array_view().is_none());
+
+    let array = dict.get("b").unwrap().as_array_view().unwrap();
+    assert_eq!(array.len(), 2);
+    assert_eq!(array.get_index(1).unwrap().to_string(), "6");
+    assert_eq!(array.get_index(2), None);
+    assert_eq!(array.into_iter().count(), 2);
+    assert!(array.get_index(0).unwrap().as_dict_view().is_none());
+
+    // TAG_CBOR_ITEM is looked through only if no allocation is needed
+    let nested = Cbor::checked(&[0xd8, 24, 0x41, 0x80]).unwrap();
+    assert!(nested.as_array_view().unwrap().is_empty());
+    let nested = Cbor::checked(&[0xd8, 24, 0x5f, 0x41, 0x82, 0x42, 1, 2, 0xff]).unwrap();
+    assert!(nested.as_array_view().is_none());
+    assert!(Cbor::checked(&[0xc1, 0x80])
+        .unwrap()
+        .as_array_view()
+        .is_none());
+}
+
+#[test]
+fn view_encodings() {
+    // definite and indefinite size encodings yield the same views
+    let definite = Cbor::checked(&[0x83, 1, 0x61, b'x', 0xf6]).unwrap();
+    let indefinite = Cbor::checked(&[0x9f, 1, 0x7f, 0x61, b'x', 0xff, 0xf6, 0xff]).unwrap();
+    for cbor in [definite, indefinite] {
+        let array = cbor.as_array_view().unwrap();
+        assert_eq!(array.len(), 3);
+        assert_eq!(array.get_index(1).unwrap().decode().as_str().unwrap(), "x");
+        assert_eq!(array.get_index(3), None);
+        assert_eq!(array.get_index(usize::MAX), None);
+    }
+    let empty = Cbor::checked(&[0x9f, 0xff])
+        .unwrap()
+        .as_array_view()
+        .unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(empty.len(), 0);
+    assert_eq!(empty.get_index(0), None);
+
+    let dict = Cbor::checked(&[0xbf, 0xff])
+        .unwrap()
+        .as_dict_view()
+        .unwrap();
+    assert!(dict.is_empty());
+    assert_eq!(dict.get_index(0), None);
+    // {_ 1("a"): 1, (_ "b"): 2}
+    let dict = Cbor::checked(&[0xbf, 0xc1, 0x61, b'a', 1, 0x7f, 0x61, b'b', 0xff, 2, 0xff])
+        .unwrap()
+        .as_dict_view()
+        .unwrap();
+    assert_eq!(dict.len(), 2);
+    // text string keys match regardless of tags and chunking, items only by their encoding
+    assert_eq!(dict.get("a").unwrap().to_string(), "1");
+    assert_eq!(dict.get("b").unwrap().to_string(), "2");
+    assert_eq!(dict.get_item(Cbor::unchecked(&[0x61, b'a'])), None);
+    assert_eq!(
+        dict.get_item(Cbor::unchecked(&[0xc1, 0x61, b'a']))
+            .unwrap()
+            .to_string(),
+        "1"
+    );
+    assert_eq!(dict.get_index(2), None);
+
+    // nested TAG_CBOR_ITEM byte strings of definite size are unwrapped
+    let nested = Cbor::checked(&[0xd8, 24, 0x46, 0xd8, 24, 0x43, 0xa1, 0xf6, 0xf5]).unwrap();
+    let dict = nested.as_dict_view().unwrap();
+    assert_eq!(dict.len(), 1);
+    assert_eq!(dict.get_index(0).unwrap().1.to_string(), "true");
+    assert!(nested.as_array_view().is_none());
+    // other tags on the container are not looked through
+    assert!(Cbor::checked(&[0xd8, 24, 0x42, 0xc1, 0xa0])
+        .unwrap()
+        .as_dict_view()
+        .is_none());
+}
+
+#[test]
+fn index_str_syntax() {
+    use crate::{index_str, IndexStr};
+
+    fn path(s: &str) -> Vec<PathElement<'_>> {
+        index_str(s).collect()
+    }
+    fn s(s: &str) -> PathElement<'_> {
+        PathElement::String(Cow::Borrowed(s))
+    }
+    let o = |s: &str| PathElement::String(Cow::Owned(s.to_owned()));
+    let item = |bytes: &[u8]| PathElement::Item(Cow::Owned(CborOwned::unchecked(bytes)));
+
+    assert_eq!(path("a.b[1]"), [s("a"), s("b"), PathElement::Number(1)]);
+    assert_eq!(path(".a..b."), [s("a"), s("b")]);
+    assert_eq!(path("a]b"), [s("a]b")]);
+    assert_eq!(path(r#"["a.b"]['c"d'][""]"#), [s("a.b"), s("c\"d"), s("")]);
+    assert_eq!(path(r#"a\.b["x\"y"]"#), [o("a.b"), o("x\"y")]);
+    assert_eq!(path(r"a\n\u{e9}\\"), [o("a\né\\")]);
+    assert_eq!(
+        path("[-1][-20]"),
+        [PathElement::FromEnd(1), PathElement::FromEnd(20)]
+    );
+    assert_eq!(path("{3}{-1}"), [item(&[3]), item(&[0x20])]);
+    assert_eq!(path("a{24}"), [s("a"), item(&[0x18, 24])]);
+    assert_eq!(path(r#"["a{24}"]"#), [s("a{24}")]);
+    assert_eq!(
+        path("{-18446744073709551616}"),
+        [item(&[0x3b, 255, 255, 255, 255, 255, 255, 255, 255])]
+    );
+    assert_eq!(
+        path("[h'00FF'][h'']"),
+        [item(&[0x42, 0, 255]), item(&[0x40])]
+    );
+    assert_eq!(
+        path("[18446744073709551615]"),
+        [PathElement::Number(u64::MAX)]
+    );
+
+    let e = |s: &str| IndexStr::parse(s).map(|_| ()).unwrap_err().to_string();
+    assert_eq!(e("a[1"), "expected `]` at offset 3");
+    assert_eq!(
+        e("a[x]"),
+        "expected index, quoted key, or byte string at offset 2"
+    );
+    assert_eq!(
+        e("[-0]"),
+        "index from the end must be at least 1 at offset 2"
+    );
+    assert_eq!(
+        e("[18446744073709551616]"),
+        "number out of range at offset 1"
+    );
+    assert_eq!(
+        e("{-18446744073709551617}"),
+        "number out of range at offset 2"
+    );
+    assert_eq!(e("{}"), "expected number at offset 1");
+    assert_eq!(e("a{b}"), "expected number at offset 2");
+    assert_eq!(e("{1]"), "expected `}` at offset 2");
+    assert_eq!(e("[h'0g']"), "expected hex digit at offset 4");
+    assert_eq!(e("[h'0']"), "expected hex digit at offset 4");
+    assert_eq!(e("[h'00"), "expected `'` at offset 5");
+    assert_eq!(e(r#"["abc]"#), "unterminated string at offset 1");
+    assert_eq!(e(r"ab\"), "incomplete escape sequence at offset 2");
+    assert_eq!(e(r"a\u{d800}"), "invalid unicode escape at offset 1");
+    assert!(crate::try_index_str("a[").is_none());
+}
+
+#[test]
+fn json_pointer() {
+    use crate::{render_pointer, PointerStr, ValidationLimits};
+
+    fn path(s: &str) -> Vec<PathElement<'_>> {
+        PointerStr::parse(s).unwrap().collect()
+    }
+    assert_eq!(path(""), []);
+    assert_eq!(path("/"), [PathElement::String("".into())]);
+    assert_eq!(
+        path("/a~1b/m~0n/0/10/01/-/~01"),
+        [
+            PathElement::String("a/b".into()),
+            PathElement::String("m~n".into()),
+            PathElement::Number(0),
+            PathElement::Number(10),
+            PathElement::String("01".into()),
+            PathElement::String("-".into()),
+            PathElement::String("~1".into()),
+        ]
+    );
+    assert_eq!(PointerStr::parse("a").unwrap_err().offset(), 0);
+    assert_eq!(PointerStr::parse("/a~2").unwrap_err().offset(), 2);
+    assert_eq!(PointerStr::parse("/a~").unwrap_err().offset(), 2);
+
+    // {"a": [10, 24((_ h'a1', h'6178', h'01'))], 1: "int", "1": "str"}
+    let cbor = Cbor::checked(&[
+        0xa3, 0x61, b'a', 0x82, 10, 0xd8, 24, 0x5f, 0x41, 0xa1, 0x42, 0x61, b'x', 0x41, 1, 0xff, 1,
+        0x63, b'i', b'n', b't', 0x61, b'1', 0x63, b's', b't', b'r',
+    ])
+    .unwrap();
+    let p = |s: &str| cbor.pointer(s).map(|c| c.to_string());
+    assert_eq!(p("/1").unwrap(), r#""str""#);
+    assert_eq!(p("/a/0").unwrap(), "10");
+    assert_eq!(p("/a/1/x").unwrap(), "1");
+    assert!(matches!(cbor.pointer("/a/1/x"), Some(Cow::Owned(_))));
+    assert!(matches!(cbor.pointer("/a/0"), Some(Cow::Borrowed(_))));
+    assert_eq!(p("/a/2"), None);
+    assert_eq!(p("/a/x"), None);
+    assert_eq!(p("/b"), None);
+    assert_eq!(p("a"), None);
+    let owned = cbor.pointer_owned("/a").unwrap();
+    assert_eq!(owned.to_string(), r#"[10, <_ {"x": 1}>]"#);
+
+    let error = Cbor::checked_with(
+        &[0x81, 0xa1, 0x61, b'/', 0x61, 0xff],
+        &ValidationLimits::new().with_error_path(true),
+    )
+    .unwrap_err();
+    assert_eq!(render_pointer(error.path()), "/0/~1");
+}
+
+#[test]
+fn updates() {
+    use crate::{index_str, Update};
+
+    // [_ {"a": 1, 2: [3, 4]}, 24(<<{"x": [_ 5]}>>), 24((_ h'81', h'06'))]
+    let cbor = Cbor::checked(&[
+        0x9f, 0xa2, 0x61, b'a', 1, 2, 0x82, 3, 4, 0xd8, 24, 0x46, 0xa1, 0x61, b'x', 0x9f, 5, 0xff,
+        0xd8, 24, 0x5f, 0x41, 0x81, 0x41, 6, 0xff, 0xff,
+    ])
+    .unwrap();
+    let value = CborBuilder::new().write_str("v", None);
+
+    let set = |path: &str| {
+        cbor.with_set(index_str(path), &value)
+            .map(|c| c.to_string())
+    };
+    assert_eq!(set("").unwrap(), r#""v""#);
+    assert_eq!(
+        set("[0].a").unwrap(),
+        r#"[_ {"a": "v", 2: [3, 4]}, <{"x": [_ 5]}>, <_ [6]>]"#
+    );
+    assert_eq!(
+        set("[0]{2}[-1]").unwrap(),
+        r#"[_ {"a": 1, 2: [3, "v"]}, <{"x": [_ 5]}>, <_ [6]>]"#
+    );
+    assert_eq!(
+        set("[0].b").unwrap(),
+        r#"[_ {"a": 1, 2: [3, 4], "b": "v"}, <{"x": [_ 5]}>, <_ [6]>]"#
+    );
+    assert_eq!(
+        set("[1].x[0]").unwrap(),
+        r#"[_ {"a": 1, 2: [3, 4]}, <{"x": [_ "v"]}>, <_ [6]>]"#
+    );
+    // the assembled byte string is written with definite size
+    assert_eq!(
+        set("[2][0]").unwrap(),
+        r#"[_ {"a": 1, 2: [3, 4]}, <{"x": [_ 5]}>, <["v"]>]"#
+    );
+    assert_eq!(set("[3]"), None);
+    // numbers are dict keys as well
+    assert_eq!(
+        set("[0][0]").unwrap(),
+        r#"[_ {"a": 1, 2: [3, 4], 0: "v"}, <{"x": [_ 5]}>, <_ [6]>]"#
+    );
+    assert_eq!(set("[-4]"), None);
+    assert_eq!(set("[0].a.b"), None);
+
+    let insert = |path: &str| {
+        cbor.with_inserted(index_str(path), &value)
+            .map(|c| c.to_string())
+    };
+    assert_eq!(
+        insert("[1].x[0]").unwrap(),
+        r#"[_ {"a": 1, 2: [3, 4]}, <{"x": [_ "v", 5]}>, <_ [6]>]"#
+    );
+    assert_eq!(
+        insert("[0]{2}[-3]").unwrap(),
+        r#"[_ {"a": 1, 2: ["v", 3, 4]}, <{"x": [_ 5]}>, <_ [6]>]"#
+    );
+    assert_eq!(
+        insert("[0]{2}[2]").unwrap(),
+        r#"[_ {"a": 1, 2: [3, 4, "v"]}, <{"x": [_ 5]}>, <_ [6]>]"#
+    );
+    assert_eq!(
+        insert("[0]{3}").unwrap(),
+        r#"[_ {"a": 1, 2: [3, 4], 3: "v"}, <{"x": [_ 5]}>, <_ [6]>]"#
+    );
+    assert_eq!(insert("[0]{2}[3]"), None);
+    assert_eq!(insert("[0]{2}[-4]"), None);
+    assert_eq!(insert("[0].a"), None);
+    assert_eq!(insert(""), None);
+
+    let remove = |path: &str| cbor.with_removed(index_str(path)).map(|c| c.to_string());
+    assert_eq!(
+        remove("[0].a").unwrap(),
+        r#"[_ {2: [3, 4]}, <{"x": [_ 5]}>, <_ [6]>]"#
+    );
+    assert_eq!(remove("[0]").unwrap(), r#"[_ <{"x": [_ 5]}>, <_ [6]>]"#);
+    assert_eq!(
+        remove("[1].x[0]").unwrap(),
+        r#"[_ {"a": 1, 2: [3, 4]}, <{"x": [_ ]}>, <_ [6]>]"#
+    );
+    assert_eq!(remove("[0].b"), None);
+    assert_eq!(remove(""), None);
+
+    // untouched items keep their exact bytes, e.g. non-minimal integer encoding
+    let cbor = Cbor::checked(&[0x82, 0x18, 1, 0x81, 2]).unwrap();
+    let updated = cbor.with_set(index_str("[1][0]"), &value).unwrap();
+    assert_eq!(updated.as_slice(), [0x82, 0x18, 1, 0x81, 0x61, b'v']);
+
+    // with a builder the encoding follows its max_definite setting
+    let updated = CborBuilder::new()
+        .with_max_definite_size(Some(1))
+        .write_updated(cbor, index_str("[1][0]"), Update::Set(&value))
+        .unwrap();
+    assert_eq!(updated.as_slice(), [0x9f, 0x18, 1, 0x81, 0x61, b'v', 0xff]);
+    let mut v = vec![0];
+    assert!(CborBuilder::append_to(&mut v)
+        .write_updated(cbor, index_str("[2]"), Update::Remove)
+        .is_none());
+    assert_eq!(v, [0]);
+    // large headers are rewritten with the correct length
+    let inner = CborBuilder::new().write_array(None, |b| {
+        for _ in 0..23 {
+            b.write_bytes(&[0; 10], None);
+        }
+    });
+    let big = CborBuilder::new().write_bytes(inner.as_slice(), [TAG_CBOR_ITEM]);
+    let updated = big.with_inserted(index_str("[-1]"), &value).unwrap();
+    assert_eq!(&updated.as_slice()[..6], [0xd8, 24, 0x59, 1, 1, 0x98]);
+    assert_eq!(Cbor::checked(updated.as_slice()).unwrap(), &*updated);
+    assert_eq!(updated.index(index_str("[23]")).unwrap().as_ref(), &*value);
+    let array = CborBuilder::new().write_array(None, |b| {
+        for _ in 0..23 {
+            b.write_pos(0, None);
+        }
+    });
+    let updated = array.with_inserted(index_str("[-1]"), &value).unwrap();
+    assert_eq!(&updated.as_slice()[..2], [0x98, 24]);
+    assert_eq!(Cbor::checked(updated.as_slice()).unwrap(), &*updated);
+}
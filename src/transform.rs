@@ -0,0 +1,275 @@
+use crate::{
+    builder::low_level::{finish_array, write_indefinite},
+    constants::{MAJOR_ARRAY, MAJOR_DICT},
+    reader::tags,
+    validated::Lengths,
+    ArrayIter, Cbor, CborOwned, DictIter, ItemKind, PathElement,
+};
+
+/// Decision of a [`Transformer`](trait.Transformer.html) about what to do with an item
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transform {
+    /// Copy the item unchanged, without looking at its contents.
+    Keep,
+    /// Leave out the item; for a dict value this removes the whole entry.
+    Drop,
+    /// Write the given item instead.
+    Replace(CborOwned),
+    /// Write the item with its tags, transforming the contents of arrays and dicts.
+    ///
+    /// For all other items this is the same as `Keep`.
+    Descend,
+}
+
+/// Rewriting of CBOR items, see [`Cbor::transform`](struct.Cbor.html#method.transform)
+///
+/// Each item is presented together with its path from the transformed item, where array
+/// elements are identified by their index in the input and dict values by
+/// [`PathElement::from_key`](enum.PathElement.html#method.from_key) of their key in the input.
+/// [`TAG_CBOR_ITEM`](constants/constant.TAG_CBOR_ITEM.html) byte strings are treated as
+/// opaque values, i.e. `Descend` does not look into them.
+#[allow(unused_variables)]
+pub trait Transformer<'a> {
+    /// Decide what to do with the given item. The default is to descend into all items.
+    fn transform(&mut self, path: &[PathElement<'a>], item: &'a Cbor) -> Transform {
+        Transform::Descend
+    }
+
+    /// Return a replacement for the key of a dict entry whose value is not dropped, with
+    /// `path` being the same as for the value. The default is to keep the key.
+    ///
+    /// Entries are written in the order of the input, renaming may thus lead to duplicate keys.
+    fn rename_key(&mut self, path: &[PathElement<'a>], key: &'a Cbor) -> Option<CborOwned> {
+        None
+    }
+}
+
+enum Iter<'a> {
+    Array(ArrayIter<'a>, u64),
+    Dict(DictIter<'a>),
+}
+
+/// an array or dict whose contents are currently being written
+struct Frame<'a> {
+    iter: Iter<'a>,
+    /// position of the header in the output
+    pos: usize,
+    /// number of items or entries written
+    count: u64,
+    /// path length of the container’s contents
+    depth: usize,
+}
+
+/// Write `cbor` into `bytes` as directed by the transformer, given the decision for `cbor` itself.
+pub(crate) fn transform<'a>(
+    cbor: &'a Cbor,
+    decision: Transform,
+    transformer: &mut impl Transformer<'a>,
+    max_definite: Option<u64>,
+    bytes: &mut Vec<u8>,
+) {
+    let mut lengths = Lengths::default();
+    let mut path = Vec::new();
+    let mut stack = Vec::new();
+    stack.extend(apply(cbor, decision, bytes, 0));
+
+    while let Some(frame) = stack.last_mut() {
+        path.truncate(frame.depth);
+        let next = match &mut frame.iter {
+            Iter::Array(iter, index) => iter.next_with(&mut lengths).map(|item| {
+                path.push(PathElement::Number(*index));
+                *index += 1;
+                (item, transformer.transform(&path, item))
+            }),
+            Iter::Dict(iter) => iter.next_with(&mut lengths).map(|(key, value)| {
+                path.push(PathElement::from_key(key));
+                let decision = transformer.transform(&path, value);
+                if decision != Transform::Drop {
+                    let renamed = transformer.rename_key(&path, key);
+                    bytes.extend_from_slice(renamed.as_ref().map_or(key, |k| &**k).as_slice());
+                }
+                (value, decision)
+            }),
+        };
+        match next {
+            Some((_, Transform::Drop)) => {}
+            Some((item, decision)) => {
+                frame.count += 1;
+                let depth = path.len();
+                stack.extend(apply(item, decision, bytes, depth));
+            }
+            None => {
+                let major = match frame.iter {
+                    Iter::Array(..) => MAJOR_ARRAY,
+                    Iter::Dict(_) => MAJOR_DICT,
+                };
+                finish_array(frame.count, bytes, frame.pos, major, max_definite);
+                stack.pop();
+            }
+        }
+    }
+}
+
+/// write the item or, when descending into an array or dict, its header
+fn apply<'a>(
+    cbor: &'a Cbor,
+    decision: Transform,
+    bytes: &mut Vec<u8>,
+    depth: usize,
+) -> Option<Frame<'a>> {
+    let iter = match decision {
+        Transform::Keep => None,
+        Transform::Drop => return None,
+        Transform::Replace(item) => {
+            bytes.extend_from_slice(item.as_slice());
+            return None;
+        }
+        Transform::Descend => match cbor.kind() {
+            ItemKind::Array(iter) => Some((Iter::Array(iter, 0), MAJOR_ARRAY)),
+            ItemKind::Dict(iter) => Some((Iter::Dict(iter), MAJOR_DICT)),
+            _ => None,
+        },
+    };
+    match iter {
+        Some((iter, major)) => {
+            let rest = tags(cbor.as_slice())?.1;
+            bytes.extend_from_slice(&cbor.as_slice()[..cbor.as_slice().len() - rest.len()]);
+            let pos = bytes.len();
+            write_indefinite(bytes, major);
+            Some(Frame {
+                iter,
+                pos,
+                count: 0,
+                depth,
+            })
+        }
+        None => {
+            bytes.extend_from_slice(cbor.as_slice());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{index_str, render_pointer, CborBuilder, Writer};
+    use pretty_assertions::assert_eq;
+
+    /// decisions by JSON Pointer, descending everywhere else
+    struct ByPath(Vec<(&'static str, Transform)>, Vec<String>);
+
+    impl<'a> Transformer<'a> for ByPath {
+        fn transform(&mut self, path: &[PathElement<'a>], _item: &'a Cbor) -> Transform {
+            let pointer = render_pointer(path);
+            let decision = self.0.iter().find(|(p, _)| *p == pointer);
+            self.1.push(pointer);
+            decision.map_or(Transform::Descend, |(_, t)| t.clone())
+        }
+
+        fn rename_key(&mut self, path: &[PathElement<'a>], key: &'a Cbor) -> Option<CborOwned> {
+            match path.last() {
+                Some(PathElement::String(k)) if k.starts_with('_') => {
+                    Some(CborBuilder::new().write_str(&k[1..], key.tags().map(|t| t + 1)))
+                }
+                _ => None,
+            }
+        }
+    }
+
+    fn cbor(bytes: &[u8]) -> &Cbor {
+        Cbor::checked(bytes).unwrap()
+    }
+
+    #[test]
+    fn decisions() {
+        // 1([{"a": 1, "_b": 5("x"), 3: [_ 2, 3]}, [_ 4, 5], 6])
+        let input = cbor(&[
+            0xc1, 0x83, 0xa3, 0x61, b'a', 1, 0x62, b'_', b'b', 0xc5, 0x61, b'x', 3, 0x9f, 2, 3,
+            0xff, 0x9f, 4, 5, 0xff, 6,
+        ]);
+        let seven = CborBuilder::new().write_pos(7, [9]);
+
+        let mut t = ByPath(Vec::new(), Vec::new());
+        let output = input.transform(&mut t, CborBuilder::new()).unwrap();
+        assert_eq!(
+            output.to_string(),
+            r#"1([{"a": 1, "b": 5("x"), 3: [2, 3]}, [4, 5], 6])"#
+        );
+        assert_eq!(
+            t.1,
+            ["", "/0", "/0/a", "/0/_b", "/0/3", "/0/3/0", "/0/3/1", "/1", "/1/0", "/1/1", "/2"]
+        );
+
+        let mut t = ByPath(
+            vec![
+                ("/0/a", Transform::Drop),
+                ("/0/3", Transform::Keep),
+                ("/1/0", Transform::Replace(seven.clone())),
+                ("/1/1", Transform::Drop),
+                ("/2", Transform::Drop),
+            ],
+            Vec::new(),
+        );
+        let output = input.transform(&mut t, CborBuilder::new()).unwrap();
+        assert_eq!(
+            output.to_string(),
+            r#"1([{"b": 5("x"), 3: [_ 2, 3]}, [9(7)]])"#
+        );
+        assert_eq!(
+            t.1,
+            ["", "/0", "/0/a", "/0/_b", "/0/3", "/1", "/1/0", "/1/1", "/2"]
+        );
+        // containers that were descended into use definite size encoding
+        assert_eq!(output.as_slice()[..3], [0xc1, 0x82, 0xa2]);
+
+        let mut t = ByPath(vec![("", Transform::Drop)], Vec::new());
+        assert_eq!(input.transform(&mut t, CborBuilder::new()), None);
+        let mut t = ByPath(vec![("", Transform::Replace(seven.clone()))], Vec::new());
+        assert_eq!(input.transform(&mut t, CborBuilder::new()).unwrap(), seven);
+        let mut t = ByPath(vec![("", Transform::Keep)], Vec::new());
+        assert_eq!(
+            input
+                .transform(&mut t, CborBuilder::new())
+                .unwrap()
+                .as_slice(),
+            input.as_slice()
+        );
+    }
+
+    #[test]
+    fn nested_writer() {
+        let input = cbor(&[0x83, 1, 2, 3]);
+        let mut t = ByPath(vec![("/1", Transform::Drop)], Vec::new());
+        let output = CborBuilder::new()
+            .with_max_definite_size(Some(1))
+            .write_array(None, |b| {
+                input.transform(&mut t, &mut *b).unwrap();
+                b.write_null(None);
+            });
+        assert_eq!(output.to_string(), "[_ [_ 1, 3], null]");
+        assert_eq!(output.index(index_str("[0][1]")).unwrap().to_string(), "3");
+    }
+
+    #[test]
+    fn deep_nesting() {
+        const DEPTH: usize = 100_000;
+        // [{0: [{0: … 0 …}] …}]
+        let mut bytes = [0x81u8, 0xa1, 0].repeat(DEPTH);
+        bytes.push(0);
+        // unlike `ByPath` this does not look at the whole path for every item
+        struct Count(usize);
+        impl<'a> Transformer<'a> for Count {
+            fn transform(&mut self, _path: &[PathElement<'a>], _item: &'a Cbor) -> Transform {
+                self.0 += 1;
+                Transform::Descend
+            }
+        }
+        let mut t = Count(0);
+        let output = Cbor::unchecked(&bytes)
+            .transform(&mut t, CborBuilder::new())
+            .unwrap();
+        assert_eq!(output.as_slice(), bytes.as_slice());
+        assert_eq!(t.0, 2 * DEPTH + 1);
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    builder::low_level::{info_len, write_indefinite, write_info},
+    constants::{MAJOR_ARRAY, MAJOR_BYTES, MAJOR_DICT, STOP_BYTE, TAG_CBOR_ITEM},
+    reader::tags,
+    Cbor, CborBuilder, ItemKind, PathElement, Writer,
+};
+use std::borrow::Cow;
+
+/// Modification to apply to the item found at the end of a path
+///
+/// See [`Writer::write_updated`](trait.Writer.html#method.write_updated) and the shortcuts
+/// [`Cbor::with_set`](struct.Cbor.html#method.with_set),
+/// [`Cbor::with_inserted`](struct.Cbor.html#method.with_inserted) and
+/// [`Cbor::with_removed`](struct.Cbor.html#method.with_removed).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Update<'a> {
+    /// Replace the array item or dict value, or add a dict entry if the key is not present.
+    ///
+    /// With an empty path the whole item is replaced.
+    Set(&'a Cbor),
+    /// Insert an array item before the given index, or add a dict entry whose key must not be
+    /// present yet.
+    ///
+    /// [`PathElement::FromEnd(n)`](enum.PathElement.html#variant.FromEnd) places the new item
+    /// such that it becomes the `n`-th item from the end, i.e. `FromEnd(1)` appends it.
+    Insert(&'a Cbor),
+    /// Remove the array item or dict entry.
+    Remove,
+}
+
+/// one level of nesting along the path, from outermost to innermost
+enum Frame<'a> {
+    /// TAG_CBOR_ITEM byte string, rewritten with definite size around its updated contents
+    Embedded { tags: Cow<'a, [u8]> },
+    Container {
+        tags: Cow<'a, [u8]>,
+        major: u8,
+        /// number of items or entries after the update
+        count: u64,
+        definite: bool,
+        /// encoded items preceding the updated part, including the key in case of a dict value
+        before: Cow<'a, [u8]>,
+        /// encoded items following the updated part, without stop byte
+        after: Cow<'a, [u8]>,
+    },
+}
+
+impl<'a> Frame<'a> {
+    fn into_owned(self) -> Frame<'static> {
+        match self {
+            Frame::Embedded { tags } => Frame::Embedded {
+                tags: Cow::Owned(tags.into_owned()),
+            },
+            Frame::Container {
+                tags,
+                major,
+                count,
+                definite,
+                before,
+                after,
+            } => Frame::Container {
+                tags: Cow::Owned(tags.into_owned()),
+                major,
+                count,
+                definite,
+                before: Cow::Owned(before.into_owned()),
+                after: Cow::Owned(after.into_owned()),
+            },
+        }
+    }
+
+    /// encoded length of this level given the encoded length of its updated contents
+    fn len(&self, contents: usize) -> usize {
+        match self {
+            Frame::Embedded { tags } => tags.len() + info_len(contents as u64) + contents,
+            Frame::Container {
+                tags,
+                count,
+                definite,
+                before,
+                after,
+                ..
+            } => {
+                let (head, tail) = if *definite {
+                    (info_len(*count), 0)
+                } else {
+                    (1, 1)
+                };
+                tags.len() + head + before.len() + contents + after.len() + tail
+            }
+        }
+    }
+
+    fn write_head(&self, bytes: &mut Vec<u8>, contents: usize) {
+        match self {
+            Frame::Embedded { tags } => {
+                bytes.extend_from_slice(tags);
+                write_info(bytes, MAJOR_BYTES, contents as u64);
+            }
+            Frame::Container {
+                tags,
+                major,
+                count,
+                definite,
+                before,
+                ..
+            } => {
+                bytes.extend_from_slice(tags);
+                if *definite {
+                    write_info(bytes, *major, *count);
+                } else {
+                    write_indefinite(bytes, *major);
+                }
+                bytes.extend_from_slice(before);
+            }
+        }
+    }
+
+    fn write_tail(&self, bytes: &mut Vec<u8>) {
+        if let Frame::Container {
+            definite, after, ..
+        } = self
+        {
+            bytes.extend_from_slice(after);
+            if !*definite {
+                bytes.push(STOP_BYTE);
+            }
+        }
+    }
+}
+
+enum Next<'a> {
+    /// continue with the contents of a TAG_CBOR_ITEM byte string and the same path element
+    Embedded(Cow<'a, Cbor>),
+    /// continue with the next path element
+    Child(Cow<'a, Cbor>),
+    /// the encoded items or entries that take the place of the updated part
+    Done(Vec<u8>),
+}
+
+impl<'a> Next<'a> {
+    fn into_owned(self) -> Next<'static> {
+        match self {
+            Next::Embedded(cbor) => Next::Embedded(Cow::Owned(cbor.into_owned())),
+            Next::Child(cbor) => Next::Child(Cow::Owned(cbor.into_owned())),
+            Next::Done(bytes) => Next::Done(bytes),
+        }
+    }
+}
+
+/// Write `cbor` with the given update applied at `path` into `bytes`.
+///
+/// Returns `None` without writing anything if the path does not resolve or the update is not
+/// possible. Container headers along the path keep their definite or indefinite size encoding
+/// unless `max_definite` is given, in which case it is applied as for
+/// [`CborBuilder`](struct.CborBuilder.html).
+pub(crate) fn update<'b>(
+    cbor: &Cbor,
+    path: impl IntoIterator<Item = PathElement<'b>>,
+    update: Update<'_>,
+    max_definite: Option<Option<u64>>,
+    bytes: &mut Vec<u8>,
+) -> Option<()> {
+    let mut path = path.into_iter().peekable();
+    let mut elem = path.next();
+    let mut current = Cow::Borrowed(cbor);
+    let mut frames = Vec::new();
+    let contents = loop {
+        let e = match &elem {
+            Some(e) => e,
+            None => match update {
+                Update::Set(value) => break value.as_slice().to_vec(),
+                _ => return None,
+            },
+        };
+        let last = path.peek().is_none().then_some(update);
+        let next = match current {
+            Cow::Borrowed(cbor) => descend(cbor, e, last, &mut frames)?,
+            Cow::Owned(cbor) => {
+                let mut owned = Vec::new();
+                let next = descend(&cbor, e, last, &mut owned)?.into_owned();
+                for frame in owned {
+                    frames.push(frame.into_owned());
+                }
+                next
+            }
+        };
+        current = match next {
+            Next::Embedded(cbor) => cbor,
+            Next::Child(cbor) => {
+                elem = path.next();
+                cbor
+            }
+            Next::Done(contents) => break contents,
+        };
+    };
+
+    if let Some(max_definite) = max_definite {
+        for frame in &mut frames {
+            if let Frame::Container {
+                count, definite, ..
+            } = frame
+            {
+                *definite = Some(*count) <= max_definite;
+            }
+        }
+    }
+    // the length of each level’s contents is needed for rewriting TAG_CBOR_ITEM byte strings
+    let mut lengths = vec![0; frames.len()];
+    let mut len = contents.len();
+    for (frame, length) in frames.iter().zip(lengths.iter_mut()).rev() {
+        *length = len;
+        len = frame.len(len);
+    }
+    bytes.reserve(len);
+    for (frame, length) in frames.iter().zip(lengths) {
+        frame.write_head(bytes, length);
+    }
+    bytes.extend_from_slice(&contents);
+    for frame in frames.iter().rev() {
+        frame.write_tail(bytes);
+    }
+    Some(())
+}
+
+/// take one step along the path, or apply the update if this is the last step
+fn descend<'a>(
+    cbor: &'a Cbor,
+    elem: &PathElement<'_>,
+    update: Option<Update<'_>>,
+    frames: &mut Vec<Frame<'a>>,
+) -> Option<Next<'a>> {
+    let (_, rest) = tags(cbor.as_slice())?;
+    let tags = Cow::Borrowed(&cbor.as_slice()[..cbor.as_slice().len() - rest.len()]);
+    let item = cbor.tagged_item();
+    match (item.tags().single(), item.kind()) {
+        (Some(TAG_CBOR_ITEM), ItemKind::Bytes(contents)) => {
+            frames.push(Frame::Embedded { tags });
+            Some(Next::Embedded(Cbor::from_cow_unchecked(contents.as_cow())))
+        }
+        (_, ItemKind::Array(iter)) => {
+            let definite = iter.size().is_some();
+            let body = iter.as_slice();
+            let len = iter.size().unwrap_or_else(|| iter.count() as u64);
+            let idx = match (elem, update) {
+                (PathElement::Number(idx), Some(Update::Insert(_))) if *idx <= len => *idx,
+                (PathElement::FromEnd(idx), Some(Update::Insert(_)))
+                    if (1..=len + 1).contains(idx) =>
+                {
+                    len + 1 - idx
+                }
+                (PathElement::Number(idx), _) if *idx < len => *idx,
+                (PathElement::FromEnd(idx), _) if (1..=len).contains(idx) => len - idx,
+                _ => return None,
+            };
+            let mut rest = iter;
+            for _ in 0..idx {
+                rest.next();
+            }
+            let before = &body[..body.len() - rest.as_slice().len()];
+            let (count, next) = match update {
+                None => (len, Next::Child(Cow::Borrowed(rest.next()?))),
+                Some(Update::Set(value)) => {
+                    rest.next();
+                    (len, Next::Done(value.as_slice().to_vec()))
+                }
+                Some(Update::Insert(value)) => (len + 1, Next::Done(value.as_slice().to_vec())),
+                Some(Update::Remove) => {
+                    rest.next();
+                    (len - 1, Next::Done(Vec::new()))
+                }
+            };
+            frames.push(Frame::Container {
+                tags,
+                major: MAJOR_ARRAY,
+                count,
+                definite,
+                before: Cow::Borrowed(before),
+                after: Cow::Borrowed(items(rest.as_slice(), definite)),
+            });
+            Some(next)
+        }
+        (_, ItemKind::Dict(iter)) => {
+            let definite = iter.size().is_some();
+            let body = iter.as_slice();
+            let len = iter.size().unwrap_or_else(|| iter.count() as u64);
+            let mut rest = iter;
+            let found = loop {
+                let start = rest.as_slice();
+                match rest.next() {
+                    Some((key, value)) if key_matches(key, elem) => {
+                        break Some((start, key, value))
+                    }
+                    Some(_) => {}
+                    None => break None,
+                }
+            };
+            let (count, before, next) = match (found, update) {
+                (Some((start, key, value)), None) => {
+                    let split = body.len() - start.len() + key.as_slice().len();
+                    (len, &body[..split], Next::Child(Cow::Borrowed(value)))
+                }
+                (Some((start, key, _)), Some(Update::Set(value))) => {
+                    let split = body.len() - start.len() + key.as_slice().len();
+                    (len, &body[..split], Next::Done(value.as_slice().to_vec()))
+                }
+                (Some(_), Some(Update::Insert(_))) => return None,
+                (Some((start, _, _)), Some(Update::Remove)) => {
+                    let split = body.len() - start.len();
+                    (len - 1, &body[..split], Next::Done(Vec::new()))
+                }
+                (None, Some(Update::Set(value))) | (None, Some(Update::Insert(value))) => {
+                    let mut entry = Vec::new();
+                    match elem {
+                        PathElement::String(key) => {
+                            CborBuilder::append_to(&mut entry).write_str(key, None)
+                        }
+                        PathElement::Number(key) => {
+                            CborBuilder::append_to(&mut entry).write_pos(*key, None)
+                        }
+                        PathElement::Item(key) => entry.extend_from_slice(key.as_slice()),
+                        PathElement::FromEnd(_) => return None,
+                    }
+                    entry.extend_from_slice(value.as_slice());
+                    (len + 1, items(body, definite), Next::Done(entry))
+                }
+                (None, _) => return None,
+            };
+            frames.push(Frame::Container {
+                tags,
+                major: MAJOR_DICT,
+                count,
+                definite,
+                before: Cow::Borrowed(before),
+                after: Cow::Borrowed(items(rest.as_slice(), definite)),
+            });
+            Some(next)
+        }
+        _ => None,
+    }
+}
+
+/// same key matching as for [`Cbor::index`](struct.Cbor.html#method.index)
+fn key_matches(key: &Cbor, elem: &PathElement<'_>) -> bool {
+    match elem {
+        PathElement::String(idx) => matches!(key.kind(), ItemKind::Str(s) if s == idx),
+        PathElement::Number(idx) => matches!(key.kind(), ItemKind::Pos(p) if p == *idx),
+        PathElement::Item(idx) => &**idx == key,
+        PathElement::FromEnd(_) => false,
+    }
+}
+
+/// strip the stop byte from the remaining items of an indefinite size container
+fn items(rest: &[u8], definite: bool) -> &[u8] {
+    if definite {
+        rest
+    } else {
+        &rest[..rest.len() - 1]
+    }
+}
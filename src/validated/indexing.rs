@@ -1,5 +1,7 @@
-use crate::{constants::TAG_CBOR_ITEM, Cbor, CborOwned, ItemKind, TaggedItem, Visitor};
-use std::borrow::Cow;
+use crate::{
+    constants::TAG_CBOR_ITEM, Cbor, CborBuilder, CborOwned, ItemKind, TaggedItem, Visitor, Writer,
+};
+use std::{borrow::Cow, fmt::Display};
 
 pub struct IndexVisitor<'a, I: Iterator> {
     iter: Option<I>,
@@ -52,6 +54,14 @@ impl<'a, 'b, I: Iterator<Item = PathElement<'b>>> Visitor<'a, Option<Cow<'a, Cbo
                 PathElement::String(_) => return Err(None),
                 PathElement::Number(x) => x,
                 PathElement::Item(_) => return Err(None),
+                PathElement::FromEnd(x) => {
+                    let size = match (size, item.kind()) {
+                        (Some(size), _) => size,
+                        (None, ItemKind::Array(iter)) => iter.count() as u64,
+                        _ => unreachable!("visiting an array"),
+                    };
+                    size.checked_sub(x).ok_or(None)?
+                }
             };
             if let Some(size) = size {
                 if size <= idx {
@@ -103,6 +113,7 @@ impl<'a, 'b, I: Iterator<Item = PathElement<'b>>> Visitor<'a, Option<Cow<'a, Cbo
             PathElement::String(idx) => matches!(key.kind(), ItemKind::Str(s) if s == idx),
             PathElement::Number(idx) => matches!(key.kind(), ItemKind::Pos(p) if p == *idx),
             PathElement::Item(idx) => &**idx == key.cbor(),
+            PathElement::FromEnd(_) => false,
         })
     }
 
@@ -113,7 +124,7 @@ impl<'a, 'b, I: Iterator<Item = PathElement<'b>>> Visitor<'a, Option<Cow<'a, Cbo
 }
 
 /// Path elements for indexing into CBOR structures
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PathElement<'a> {
     /// matches only text string dictionary keys encoded as major type 3
     String(Cow<'a, str>),
@@ -121,51 +132,338 @@ pub enum PathElement<'a> {
     Number(u64),
     /// matches only dictionary keys of exactly the byte sequence as this element
     Item(Cow<'a, Cbor>),
+    /// matches only array indices counted from the end, where 1 denotes the last item
+    ///
+    /// For arrays of indefinite size this requires counting the items first.
+    FromEnd(u64),
+}
+
+impl<'a> PathElement<'a> {
+    /// The element that selects the value stored under the given dict key
+    ///
+    /// Untagged text strings and unsigned integers are represented as `String` and `Number`,
+    /// all other keys as `Item`.
+    pub fn from_key(key: &'a Cbor) -> Self {
+        match key.kind() {
+            ItemKind::Str(s) if key.tags().is_empty() => PathElement::String(s.as_cow()),
+            ItemKind::Pos(n) if key.tags().is_empty() => PathElement::Number(n),
+            _ => PathElement::Item(Cow::Borrowed(key)),
+        }
+    }
+
+    /// Copy any borrowed key so that this element can outlive its source.
+    pub fn into_owned(self) -> PathElement<'static> {
+        match self {
+            PathElement::String(s) => PathElement::String(Cow::Owned(s.into_owned())),
+            PathElement::Number(n) => PathElement::Number(n),
+            PathElement::Item(c) => PathElement::Item(Cow::Owned(c.into_owned())),
+            PathElement::FromEnd(n) => PathElement::FromEnd(n),
+        }
+    }
+}
+
+/// Error returned by [`IndexStr::parse`](struct.IndexStr.html#method.parse) and
+/// [`PointerStr::parse`](struct.PointerStr.html#method.parse)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexStrError {
+    offset: usize,
+    message: &'static str,
+}
+
+impl IndexStrError {
+    pub(crate) fn new(offset: usize, message: &'static str) -> Self {
+        Self { offset, message }
+    }
+
+    pub(crate) fn message(&self) -> &'static str {
+        self.message
+    }
+
+    /// Byte offset within the path string at which the problem was found
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Display for IndexStrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
 }
 
+impl std::error::Error for IndexStrError {}
+
 /// Iterator returned by [`index_str`](fn.index_str.html)
+///
+/// See [`try_index_str`](fn.try_index_str.html) for the syntax. Since version 0.9 an opening
+/// brace ends an unquoted key: `a{1}` now denotes the integer key 1 within the value of key `a`
+/// instead of the key `a{1}`, and `a{b}` is rejected. Quote such keys as in `["a{b}"]`.
 #[derive(Debug, Clone, PartialEq)]
-pub struct IndexStr<'a>(&'a str);
+pub struct IndexStr<'a> {
+    s: &'a str,
+    pos: usize,
+}
 
 impl<'a> IndexStr<'a> {
     pub fn new(s: &'a str) -> Option<Self> {
-        let mut test = Self(s);
-        (&mut test).count();
-        if test.0.is_empty() {
-            Some(Self(s))
-        } else {
-            None
+        Self::parse(s).ok()
+    }
+
+    /// Check the syntax of the whole path string, see [`try_index_str`](fn.try_index_str.html).
+    pub fn parse(s: &'a str) -> Result<Self, IndexStrError> {
+        let mut test = Self { s, pos: 0 };
+        while test.step(|_| false)?.is_some() {}
+        Ok(Self { s, pos: 0 })
+    }
+
+    /// Parse the steps at the start of `s` that begin with `.`, `[`, or `{`, where unquoted keys
+    /// also end at characters matching `stop`; returns the steps and the number of bytes parsed.
+    pub(crate) fn parse_prefix(
+        s: &'a str,
+        stop: fn(char) -> bool,
+    ) -> Result<(Vec<PathElement<'a>>, usize), IndexStrError> {
+        let mut this = Self { s, pos: 0 };
+        let mut path = Vec::new();
+        while this.rest().starts_with(['.', '[', '{']) {
+            let key = this.rest().trim_start_matches('.');
+            if key.is_empty() || key.starts_with(stop) {
+                this.pos = s.len() - key.len();
+                return Err(this.error("expected key"));
+            }
+            path.extend(this.step(stop)?);
         }
+        Ok((path, this.pos))
     }
-}
 
-impl<'a> Iterator for IndexStr<'a> {
-    type Item = PathElement<'a>;
+    /// Parse the string in single or double quotes at the start of `s`, with the same escapes as
+    /// quoted keys; returns the string and the number of bytes parsed.
+    pub(crate) fn parse_quoted(s: &'a str) -> Result<(Cow<'a, str>, usize), IndexStrError> {
+        let mut this = Self { s, pos: 1 };
+        let key = this.key(s.chars().next(), |_| false)?;
+        Ok((key, this.pos + 1))
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        while self.0.starts_with('.') {
-            self.0 = &self.0[1..];
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn error(&self, message: &'static str) -> IndexStrError {
+        IndexStrError::new(self.pos, message)
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), IndexStrError> {
+        if self.rest().starts_with(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(match c {
+                ']' => "expected `]`",
+                '}' => "expected `}`",
+                _ => "expected `'`",
+            }))
         }
-        if self.0.is_empty() {
-            return None;
+    }
+
+    fn step(&mut self, stop: fn(char) -> bool) -> Result<Option<PathElement<'a>>, IndexStrError> {
+        while self.rest().starts_with('.') {
+            self.pos += 1;
         }
+        let elem = match self.rest().chars().next() {
+            None => return Ok(None),
+            Some('[') => {
+                self.pos += 1;
+                let elem = self.bracket()?;
+                self.expect(']')?;
+                elem
+            }
+            Some('{') => {
+                self.pos += 1;
+                let elem = self.int_key()?;
+                self.expect('}')?;
+                elem
+            }
+            Some(_) => PathElement::String(self.key(None, stop)?),
+        };
+        Ok(Some(elem))
+    }
+
+    fn bracket(&mut self) -> Result<PathElement<'a>, IndexStrError> {
+        let rest = self.rest();
+        if rest.starts_with('"') || rest.starts_with('\'') {
+            let quote = rest.chars().next();
+            self.pos += 1;
+            let key = self.key(quote, |_| false)?;
+            self.pos += 1;
+            Ok(PathElement::String(key))
+        } else if rest.starts_with("h'") {
+            self.pos += 2;
+            let bytes = self.hex()?;
+            self.expect('\'')?;
+            let key = CborBuilder::new().write_bytes(&bytes, None);
+            Ok(PathElement::Item(Cow::Owned(key)))
+        } else if rest.starts_with('-') {
+            self.pos += 1;
+            let start = self.pos;
+            match self.digits(u64::MAX.into())? {
+                0 => {
+                    self.pos = start;
+                    Err(self.error("index from the end must be at least 1"))
+                }
+                n => Ok(PathElement::FromEnd(n as u64)),
+            }
+        } else {
+            match rest.chars().next() {
+                Some(c) if c.is_ascii_digit() => {
+                    Ok(PathElement::Number(self.digits(u64::MAX.into())? as u64))
+                }
+                _ => Err(self.error("expected index, quoted key, or byte string")),
+            }
+        }
+    }
 
-        if self.0.starts_with('[') {
-            let end = self.0.find(']')?;
-            let idx: u64 = self.0[1..end].parse().ok()?;
-            self.0 = &self.0[end + 1..];
-            Some(PathElement::Number(idx))
+    fn int_key(&mut self) -> Result<PathElement<'a>, IndexStrError> {
+        let negative = self.rest().starts_with('-');
+        if negative {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        // an `Item` only matches dict keys, so `{3}` does not select an array item
+        let key = if !negative {
+            let n = self.digits(u64::MAX.into())?;
+            CborBuilder::new().write_pos(n as u64, None)
+        } else if self.digits(1 << 64)? == 0 {
+            self.pos = start;
+            return Err(self.error("number out of range"));
         } else {
-            let mut pos = self.0.len();
-            for (p, ch) in self.0.char_indices() {
-                if ch == '.' || ch == '[' {
-                    pos = p;
-                    break;
+            let n = self.s[start..self.pos].parse::<u128>().unwrap();
+            CborBuilder::new().write_neg((n - 1) as u64, None)
+        };
+        Ok(PathElement::Item(Cow::Owned(key)))
+    }
+
+    /// parse decimal digits, permitting values up to `max`
+    fn digits(&mut self, max: u128) -> Result<u128, IndexStrError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected number"));
+        }
+        match rest[..len].parse::<u128>() {
+            Ok(n) if n <= max => {
+                self.pos += len;
+                Ok(n)
+            }
+            _ => Err(self.error("number out of range")),
+        }
+    }
+
+    fn hex(&mut self) -> Result<Vec<u8>, IndexStrError> {
+        let mut bytes = Vec::new();
+        loop {
+            let rest = self.rest().as_bytes();
+            let digit = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+            match rest.first() {
+                Some(b'\'') => return Ok(bytes),
+                Some(b) => {
+                    let hi = digit(*b).ok_or_else(|| self.error("expected hex digit"))?;
+                    self.pos += 1;
+                    let lo = rest
+                        .get(1)
+                        .and_then(|b| digit(*b))
+                        .ok_or_else(|| self.error("expected hex digit"))?;
+                    self.pos += 1;
+                    bytes.push(hi << 4 | lo);
+                }
+                None => return Err(self.error("expected `'`")),
+            }
+        }
+    }
+
+    /// parse a key up to the closing quote (not consumed) or, if unquoted, up to `.`, `[`, `{`, or
+    /// a character matching `stop`
+    fn key(
+        &mut self,
+        quote: Option<char>,
+        stop: fn(char) -> bool,
+    ) -> Result<Cow<'a, str>, IndexStrError> {
+        let start = self.pos;
+        let mut owned = None::<String>;
+        let mut chars = self.rest().char_indices();
+        while let Some((idx, c)) = chars.next() {
+            let end = match quote {
+                Some(q) => c == q,
+                None => c == '.' || c == '[' || c == '{' || stop(c),
+            };
+            if end {
+                self.pos = start + idx;
+                return Ok(match owned {
+                    Some(s) => Cow::Owned(s),
+                    None => Cow::Borrowed(&self.s[start..start + idx]),
+                });
+            }
+            if c != '\\' {
+                if let Some(s) = owned.as_mut() {
+                    s.push(c);
+                }
+                continue;
+            }
+            let s = owned.get_or_insert_with(|| self.s[start..start + idx].to_owned());
+            let escaped = match chars.next() {
+                Some((_, 'n')) => '\n',
+                Some((_, 't')) => '\t',
+                Some((_, 'r')) => '\r',
+                Some((_, '0')) => '\0',
+                Some((pos, 'u')) => {
+                    let rest = &self.s[start + pos + 1..];
+                    let code = rest
+                        .strip_prefix('{')
+                        .and_then(|r| r.split_once('}'))
+                        .and_then(|(hex, _)| u32::from_str_radix(hex, 16).ok())
+                        .and_then(char::from_u32);
+                    match code {
+                        Some(c) => {
+                            let len = rest.find('}').unwrap() + 1;
+                            for _ in 0..len {
+                                chars.next();
+                            }
+                            c
+                        }
+                        None => {
+                            self.pos = start + idx;
+                            return Err(self.error("invalid unicode escape"));
+                        }
+                    }
+                }
+                Some((_, c)) => c,
+                None => {
+                    self.pos = start + idx;
+                    return Err(self.error("incomplete escape sequence"));
                 }
+            };
+            s.push(escaped);
+        }
+        match quote {
+            Some(_) => {
+                self.pos = start - 1;
+                Err(self.error("unterminated string"))
+            }
+            None => {
+                self.pos = self.s.len();
+                Ok(match owned {
+                    Some(s) => Cow::Owned(s),
+                    None => Cow::Borrowed(&self.s[start..]),
+                })
             }
-            let ret = PathElement::String(Cow::Borrowed(&self.0[..pos]));
-            self.0 = &self.0[pos..];
-            Some(ret)
         }
     }
 }
+
+impl<'a> Iterator for IndexStr<'a> {
+    type Item = PathElement<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step(|_| false).ok().flatten()
+    }
+}
@@ -1,4 +1,4 @@
-use super::CborIter;
+use super::{CborIter, Lengths};
 use crate::Cbor;
 use std::{
     borrow::Cow,
@@ -277,6 +277,16 @@ impl<'a> ArrayIter<'a> {
     pub fn size(&self) -> Option<u64> {
         self.0.size()
     }
+
+    /// remaining encoded items, including the stop byte in case of indefinite size encoding
+    pub(crate) fn as_slice(&self) -> &'a [u8] {
+        (self.0).0
+    }
+
+    /// like `next`, but skipping each nested array or dict only once across calls
+    pub(crate) fn next_with(&mut self, lengths: &mut Lengths<'a>) -> Option<&'a Cbor> {
+        self.0.next_with(lengths)
+    }
 }
 
 impl<'a> Debug for ArrayIter<'a> {
@@ -306,6 +316,16 @@ impl<'a> DictIter<'a> {
     pub fn size(&self) -> Option<u64> {
         self.0.size().map(|x| x / 2)
     }
+
+    /// remaining encoded items, including the stop byte in case of indefinite size encoding
+    pub(crate) fn as_slice(&self) -> &'a [u8] {
+        (self.0).0
+    }
+
+    /// like `next`, but skipping each nested array or dict only once across calls
+    pub(crate) fn next_with(&mut self, lengths: &mut Lengths<'a>) -> Option<(&'a Cbor, &'a Cbor)> {
+        Some((self.0.next_with(lengths)?, self.0.next_with(lengths)?))
+    }
 }
 
 impl<'a> Debug for DictIter<'a> {
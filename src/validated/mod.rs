@@ -5,11 +5,15 @@ use crate::{
     reader::{float, indefinite, integer, major, tags},
     Cbor, ItemKind, Tags,
 };
+use smallvec::SmallVec;
+use std::{collections::HashMap, marker::PhantomData};
 
 pub mod indexing;
 pub mod item;
 pub mod iterators;
+pub mod pointer;
 pub mod tags;
+pub mod views;
 
 fn skip_bytes(bytes: &[u8]) -> (Option<&[u8]>, &[u8]) {
     let (len, _, mut rest) = integer(bytes).or_else(|| indefinite(bytes)).unwrap();
@@ -27,44 +31,117 @@ fn skip_bytes(bytes: &[u8]) -> (Option<&[u8]>, &[u8]) {
     }
 }
 
-fn skip(bytes: &[u8]) -> (Option<&[u8]>, &[u8]) {
-    match major(bytes).unwrap() {
-        MAJOR_POS | MAJOR_NEG | MAJOR_LIT => (None, integer(bytes).unwrap().2),
-        MAJOR_STR | MAJOR_BYTES => skip_bytes(bytes),
-        MAJOR_TAG => skip(integer(bytes).unwrap().2),
-        MAJOR_ARRAY => {
-            let (len, _, mut rest) = integer(bytes).or_else(|| indefinite(bytes)).unwrap();
-            if len == u64::MAX {
-                // since an item takes at least 1 byte, u64::MAX is an impossible size
-                while rest[0] != STOP_BYTE {
-                    rest = skip(rest).1;
+pub(crate) fn skip(bytes: &[u8]) -> (Option<&[u8]>, &[u8]) {
+    skip_with(bytes, |_, _| {})
+}
+
+/// Skip over the item at the start of `bytes`, returning the contents if it is a definite size string.
+///
+/// Arrays and dicts are traversed using an explicit stack, so arbitrarily deep nesting is fine.
+/// `container` is called with `true` and the bytes starting at the tags of each array or dict,
+/// and with `false` and the bytes following it once the end has been reached.
+#[inline]
+pub(crate) fn skip_with<'a>(
+    bytes: &'a [u8],
+    mut container: impl FnMut(bool, &'a [u8]),
+) -> (Option<&'a [u8]>, &'a [u8]) {
+    // number of items still to be skipped per open array or dict, u64::MAX for indefinite size
+    let mut stack = SmallVec::<[u64; 16]>::new();
+    let mut rest = bytes;
+    let mut item = bytes;
+    loop {
+        match major(rest).unwrap() {
+            MAJOR_POS | MAJOR_NEG | MAJOR_LIT => rest = integer(rest).unwrap().2,
+            MAJOR_STR | MAJOR_BYTES => {
+                let (value, r) = skip_bytes(rest);
+                if stack.is_empty() {
+                    return (value, r);
                 }
-                rest = &rest[1..];
-            } else {
-                for _ in 0..len {
-                    rest = skip(rest).1;
+                rest = r;
+            }
+            MAJOR_TAG => {
+                rest = integer(rest).unwrap().2;
+                continue;
+            }
+            major => {
+                let (len, _, r) = integer(rest).or_else(|| indefinite(rest)).unwrap();
+                rest = r;
+                container(true, item);
+                // since an item takes at least 1 byte, u64::MAX is an impossible size
+                let len = match (len, major) {
+                    (u64::MAX, _) | (_, MAJOR_ARRAY) => len,
+                    _ => len.saturating_mul(2),
+                };
+                if len == u64::MAX && rest[0] == STOP_BYTE {
+                    rest = &rest[1..];
+                    container(false, rest);
+                } else if len == 0 {
+                    container(false, rest);
+                } else {
+                    stack.push(len);
+                    item = rest;
+                    continue;
                 }
             }
-            (None, rest)
         }
-        MAJOR_DICT => {
-            let (len, _, mut rest) = integer(bytes).or_else(|| indefinite(bytes)).unwrap();
-            if len == u64::MAX {
-                // since an item takes at least 1 byte, u64::MAX is an impossible size
-                while rest[0] != STOP_BYTE {
-                    rest = skip(rest).1;
-                    rest = skip(rest).1;
+        // an item has been completed, which may complete the enclosing containers as well
+        loop {
+            match stack.last_mut() {
+                None => return (None, rest),
+                Some(n) if *n == u64::MAX => {
+                    if rest[0] != STOP_BYTE {
+                        break;
+                    }
+                    rest = &rest[1..];
                 }
-                rest = &rest[1..];
-            } else {
-                for _ in 0..len {
-                    rest = skip(rest).1;
-                    rest = skip(rest).1;
+                Some(n) => {
+                    *n -= 1;
+                    if *n > 0 {
+                        break;
+                    }
                 }
             }
-            (None, rest)
+            stack.pop();
+            container(false, rest);
         }
-        _ => unreachable!(),
+        item = rest;
+    }
+}
+
+/// Lengths of the arrays and dicts that have been skipped over, keyed by their address
+///
+/// Skipping an item requires skipping all its contents, so iterating over the contents of each
+/// nested item in turn would take time quadratic in the nesting depth. Recording the lengths
+/// found along the way lets each array or dict be skipped only once.
+#[derive(Default)]
+pub(crate) struct Lengths<'a> {
+    known: HashMap<usize, usize>,
+    _ph: PhantomData<&'a [u8]>,
+}
+
+impl<'a> Lengths<'a> {
+    /// Split the item at the start of `bytes` off the rest.
+    pub fn split(&mut self, bytes: &'a [u8]) -> (&'a Cbor, &'a [u8]) {
+        let len = match self.known.get(&(bytes.as_ptr() as usize)) {
+            Some(len) => *len,
+            None => {
+                let known = &mut self.known;
+                let mut open = Vec::new();
+                let rest = skip_with(bytes, |is_open, pos| {
+                    let addr = pos.as_ptr() as usize;
+                    if is_open {
+                        open.push(addr);
+                    } else {
+                        let start = open.pop().unwrap();
+                        known.insert(start, addr - start);
+                    }
+                })
+                .1;
+                bytes.len() - rest.len()
+            }
+        };
+        let (item, rest) = bytes.split_at(len);
+        (Cbor::unchecked(item), rest)
     }
 }
 
@@ -84,9 +161,12 @@ fn bytes_iter(bytes: &[u8]) -> BytesIter<'_> {
     }
 }
 
-fn item(bytes: &[u8]) -> ItemKind<'_> {
+fn item(mut bytes: &[u8]) -> ItemKind<'_> {
     use ItemKind::*;
 
+    while major(bytes) == Some(MAJOR_TAG) {
+        bytes = integer(bytes).unwrap().2;
+    }
     match major(bytes).unwrap() {
         MAJOR_POS => Pos(integer(bytes).unwrap().0),
         MAJOR_NEG => Neg(integer(bytes).unwrap().0),
@@ -102,7 +182,6 @@ fn item(bytes: &[u8]) -> ItemKind<'_> {
             x if x < 24 => Simple(x),
             _ => unreachable!(),
         },
-        MAJOR_TAG => item(integer(bytes).unwrap().2),
         MAJOR_ARRAY => {
             let (len, _, arr) = integer(bytes).or_else(|| indefinite(bytes)).unwrap();
             let len = if len == u64::MAX { None } else { Some(len) };
@@ -138,12 +217,28 @@ impl<'a> CborIter<'a> {
     }
 }
 
+impl<'a> CborIter<'a> {
+    /// like `next`, but taking and recording the lengths of arrays and dicts
+    fn next_with(&mut self, lengths: &mut Lengths<'a>) -> Option<&'a Cbor> {
+        let CborIter(b, elems) = self;
+        if *elems == Some(0) || elems.is_none() && b[0] == STOP_BYTE {
+            return None;
+        }
+        let (item, rest) = lengths.split(b);
+        if let Some(x) = elems.as_mut() {
+            *x -= 1;
+        }
+        *b = rest;
+        Some(item)
+    }
+}
+
 impl<'a> Iterator for CborIter<'a> {
     type Item = (Option<&'a [u8]>, &'a Cbor);
 
     fn next(&mut self) -> Option<Self::Item> {
         let CborIter(b, elems) = self;
-        if *elems == Some(0) || *elems == None && b[0] == STOP_BYTE {
+        if *elems == Some(0) || elems.is_none() && b[0] == STOP_BYTE {
             None
         } else {
             let (value, rest) = skip(b);
@@ -0,0 +1,103 @@
+use super::indexing::{IndexStrError, PathElement};
+use std::{
+    borrow::{Borrow, Cow},
+    fmt::Write,
+};
+
+/// Iterator over the reference tokens of a [JSON Pointer](https://www.rfc-editor.org/rfc/rfc6901)
+///
+/// Tokens that are valid array indices according to the RFC (i.e. `0` or decimal digits without
+/// leading zero) are yielded as [`PathElement::Number`](enum.PathElement.html#variant.Number),
+/// all others as [`PathElement::String`](enum.PathElement.html#variant.String) with `~1` and `~0`
+/// replaced by `/` and `~`. Since `PathElement::Number` also matches unsigned integer dict keys
+/// while the RFC demands that a token is always a text string key when applied to a dict, use
+/// [`Cbor::pointer`](struct.Cbor.html#method.pointer) for exact resolution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointerStr<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> PointerStr<'a> {
+    pub fn new(s: &'a str) -> Option<Self> {
+        Self::parse(s).ok()
+    }
+
+    /// Check the syntax of the whole pointer, which must be empty or start with `/`.
+    pub fn parse(s: &'a str) -> Result<Self, IndexStrError> {
+        if !s.is_empty() && !s.starts_with('/') {
+            return Err(IndexStrError::new(0, "expected `/`"));
+        }
+        if let Some(pos) = s
+            .match_indices('~')
+            .map(|(pos, _)| pos)
+            .find(|pos| !matches!(s.as_bytes().get(pos + 1), Some(b'0' | b'1')))
+        {
+            return Err(IndexStrError::new(pos, "expected `~0` or `~1`"));
+        }
+        Ok(Self { s, pos: 0 })
+    }
+}
+
+impl<'a> Iterator for PointerStr<'a> {
+    type Item = PathElement<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.s.len() {
+            return None;
+        }
+        let rest = &self.s[self.pos + 1..];
+        let token = &rest[..rest.find('/').unwrap_or(rest.len())];
+        self.pos += token.len() + 1;
+        let is_index = token == "0"
+            || !token.starts_with('0')
+                && !token.is_empty()
+                && token.bytes().all(|b| b.is_ascii_digit());
+        if let Some(index) = token.parse().ok().filter(|_| is_index) {
+            Some(PathElement::Number(index))
+        } else if token.contains('~') {
+            Some(PathElement::String(Cow::Owned(
+                token.replace("~1", "/").replace("~0", "~"),
+            )))
+        } else {
+            Some(PathElement::String(Cow::Borrowed(token)))
+        }
+    }
+}
+
+/// Render a sequence of path elements as a [JSON Pointer](https://www.rfc-editor.org/rfc/rfc6901)
+///
+/// Text string keys and array indices are rendered as described in the RFC, so that the result
+/// can be resolved using [`Cbor::pointer`](struct.Cbor.html#method.pointer). JSON Pointers have
+/// no syntax for the other path elements, so they are rendered with the brackets and braces of
+/// [`index_str`](fn.index_str.html) to set them apart from text keys and indices: other dict keys
+/// in diagnostic notation within braces, like `{-1}`, and
+/// [`PathElement::FromEnd`](enum.PathElement.html#variant.FromEnd) as a negative index in
+/// brackets, like `[-1]`. Such pointers are only meant for display, e.g. in error messages, as
+/// `Cbor::pointer` treats these tokens as text keys.
+///
+/// ```rust
+/// use cbor_data::{render_pointer, index_str};
+///
+/// assert_eq!(render_pointer(index_str(r#"a["b/c"][3]{-1}[h'00'][-1]"#)), "/a/b~1c/3/{-1}/{h'00'}/[-1]");
+/// assert_eq!(render_pointer(index_str("")), "");
+/// ```
+pub fn render_pointer<'a, P: Borrow<PathElement<'a>>>(path: impl IntoIterator<Item = P>) -> String {
+    let mut s = String::new();
+    for elem in path {
+        s.push('/');
+        let start = s.len();
+        match elem.borrow() {
+            PathElement::String(key) => s.push_str(key),
+            PathElement::Number(n) => write!(s, "{}", n).unwrap(),
+            PathElement::Item(item) => write!(s, "{{{}}}", item).unwrap(),
+            PathElement::FromEnd(n) => write!(s, "[-{}]", n).unwrap(),
+        }
+        if s[start..].contains(['~', '/']) {
+            let token = s[start..].replace('~', "~0").replace('/', "~1");
+            s.truncate(start);
+            s.push_str(&token);
+        }
+    }
+    s
+}
@@ -0,0 +1,126 @@
+use super::iterators::{ArrayIter, DictIter};
+use crate::{Cbor, ItemKind};
+use std::fmt::{Debug, Formatter};
+
+/// Borrowed view of an array, see [`Cbor::as_array_view`](struct.Cbor.html#method.as_array_view)
+///
+/// Accessing the items does not allocate, it only skips over the encoded bytes.
+#[derive(Clone, Copy)]
+pub struct ArrayView<'a>(ArrayIter<'a>);
+
+impl<'a> ArrayView<'a> {
+    pub(crate) fn new(iter: ArrayIter<'a>) -> Self {
+        Self(iter)
+    }
+
+    /// Number of items, which requires a scan for indefinite size encoding
+    pub fn len(&self) -> usize {
+        match self.0.size() {
+            Some(size) => size as usize,
+            None => self.0.count(),
+        }
+    }
+
+    /// Whether the array has no items
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Item at the given index
+    pub fn get_index(&self, index: usize) -> Option<&'a Cbor> {
+        if let Some(size) = self.0.size() {
+            // no need to skip over all items if the index is out of range
+            if index as u64 >= size {
+                return None;
+            }
+        }
+        self.iter().nth(index)
+    }
+
+    /// Iterate over the items
+    pub fn iter(&self) -> ArrayIter<'a> {
+        self.0
+    }
+}
+
+impl<'a> IntoIterator for ArrayView<'a> {
+    type Item = &'a Cbor;
+    type IntoIter = ArrayIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0
+    }
+}
+
+impl<'a> Debug for ArrayView<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Borrowed view of a dict, see [`Cbor::as_dict_view`](struct.Cbor.html#method.as_dict_view)
+///
+/// Looking up keys does not allocate, it scans the entries in order and returns the first match.
+/// This is the same value that [`Cbor::index`](struct.Cbor.html#method.index) and
+/// [`Cbor::try_dict`](struct.Cbor.html#method.try_dict) yield for duplicate keys.
+#[derive(Clone, Copy)]
+pub struct DictView<'a>(DictIter<'a>);
+
+impl<'a> DictView<'a> {
+    pub(crate) fn new(iter: DictIter<'a>) -> Self {
+        Self(iter)
+    }
+
+    /// Number of entries, which requires a scan for indefinite size encoding
+    pub fn len(&self) -> usize {
+        match self.0.size() {
+            Some(size) => size as usize,
+            None => self.0.count(),
+        }
+    }
+
+    /// Whether the dict has no entries
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Value for the given text string key
+    ///
+    /// As with [`PathElement::String`](enum.PathElement.html#variant.String) this matches text
+    /// string keys regardless of their tags or encoding.
+    pub fn get(&self, key: &str) -> Option<&'a Cbor> {
+        self.iter()
+            .find(|(k, _)| matches!(k.kind(), ItemKind::Str(s) if s == key))
+            .map(|(_, v)| v)
+    }
+
+    /// Value for the key that is encoded exactly as the given item
+    pub fn get_item(&self, key: &Cbor) -> Option<&'a Cbor> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Key and value of the entry at the given position
+    pub fn get_index(&self, index: usize) -> Option<(&'a Cbor, &'a Cbor)> {
+        self.iter().nth(index)
+    }
+
+    /// Iterate over the keys and values
+    pub fn iter(&self) -> DictIter<'a> {
+        self.0
+    }
+}
+
+impl<'a> IntoIterator for DictView<'a> {
+    type Item = (&'a Cbor, &'a Cbor);
+    type IntoIter = DictIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0
+    }
+}
+
+impl<'a> Debug for DictView<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
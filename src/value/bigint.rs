@@ -0,0 +1,223 @@
+use std::cmp::Ordering;
+
+/// Minimal arbitrary precision unsigned integer for exact computations on numbers
+///
+/// The limbs are stored in little-endian order without trailing zero limbs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BigUint(Vec<u32>);
+
+/// largest power of five that fits into a limb
+const POW5_13: u32 = 1_220_703_125;
+/// largest power of ten that fits into a limb
+const POW10_9: u32 = 1_000_000_000;
+
+impl BigUint {
+    pub fn from_be_bytes(bytes: &[u8]) -> Self {
+        let mut limbs = bytes
+            .rchunks(4)
+            .map(|chunk| chunk.iter().fold(0u32, |l, b| (l << 8) | u32::from(*b)))
+            .collect::<Vec<_>>();
+        trim(&mut limbs);
+        Self(limbs)
+    }
+
+    pub fn from_u128(mut n: u128) -> Self {
+        let mut limbs = Vec::new();
+        while n > 0 {
+            limbs.push(n as u32);
+            n >>= 32;
+        }
+        Self(limbs)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// number of significant bits
+    pub fn bits(&self) -> u64 {
+        match self.0.last() {
+            Some(last) => self.0.len() as u64 * 32 - u64::from(last.leading_zeros()),
+            None => 0,
+        }
+    }
+
+    /// binary logarithm computed from the leading 96 bits, negative infinity for zero
+    pub fn log2(&self) -> f64 {
+        let n = self.0.len().min(3);
+        let top = self.0[self.0.len() - n..]
+            .iter()
+            .rev()
+            .fold(0u128, |x, l| (x << 32) | u128::from(*l));
+        (top as f64).log2() + (32 * (self.0.len() - n)) as f64
+    }
+
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.0.len() > 4 {
+            return None;
+        }
+        Some(
+            self.0
+                .iter()
+                .rev()
+                .fold(0u128, |n, l| (n << 32) | u128::from(*l)),
+        )
+    }
+
+    /// number of trailing zero bits, zero for the value zero
+    pub fn trailing_zeros(&self) -> u64 {
+        match self.0.iter().position(|l| *l != 0) {
+            Some(idx) => idx as u64 * 32 + u64::from(self.0[idx].trailing_zeros()),
+            None => 0,
+        }
+    }
+
+    pub fn bit(&self, idx: u64) -> bool {
+        let limb = (idx / 32) as usize;
+        limb < self.0.len() && self.0[limb] & (1 << (idx % 32)) != 0
+    }
+
+    pub fn add_small(&mut self, n: u32) {
+        let mut carry = n;
+        for limb in &mut self.0 {
+            if carry == 0 {
+                return;
+            }
+            let (sum, overflow) = limb.overflowing_add(carry);
+            *limb = sum;
+            carry = overflow as u32;
+        }
+        if carry > 0 {
+            self.0.push(carry);
+        }
+    }
+
+    pub fn mul_small(&mut self, n: u32) {
+        let mut carry = 0u64;
+        for limb in &mut self.0 {
+            let prod = u64::from(*limb) * u64::from(n) + carry;
+            *limb = prod as u32;
+            carry = prod >> 32;
+        }
+        if carry > 0 {
+            self.0.push(carry as u32);
+        }
+        trim(&mut self.0);
+    }
+
+    /// divide in place, returning the remainder
+    pub fn div_small(&mut self, n: u32) -> u32 {
+        let mut rem = 0u64;
+        for limb in self.0.iter_mut().rev() {
+            let cur = (rem << 32) | u64::from(*limb);
+            *limb = (cur / u64::from(n)) as u32;
+            rem = cur % u64::from(n);
+        }
+        trim(&mut self.0);
+        rem as u32
+    }
+
+    pub fn mul_pow5(&mut self, mut exp: u64) {
+        while exp >= 13 {
+            self.mul_small(POW5_13);
+            exp -= 13;
+        }
+        self.mul_small(5u32.pow(exp as u32));
+    }
+
+    /// divide by `5.pow(exp)` in place, returning whether the division was exact
+    pub fn div_pow5(&mut self, mut exp: u64) -> bool {
+        let mut exact = true;
+        while exp >= 13 && !self.is_zero() {
+            exact &= self.div_small(POW5_13) == 0;
+            exp -= 13;
+        }
+        if self.is_zero() {
+            return exact;
+        }
+        exact & (self.div_small(5u32.pow(exp as u32)) == 0)
+    }
+
+    /// remove as many factors of five as possible, up to `max`, returning how many were removed
+    pub fn remove_factors_of_5(&mut self, max: u64) -> u64 {
+        let mut count = 0;
+        while count < max && !self.is_zero() {
+            let mut quotient = self.clone();
+            if quotient.div_small(5) != 0 {
+                break;
+            }
+            *self = quotient;
+            count += 1;
+        }
+        count
+    }
+
+    pub fn shl(&mut self, bits: u64) {
+        if self.is_zero() {
+            return;
+        }
+        let (limbs, bits) = ((bits / 32) as usize, (bits % 32) as u32);
+        if bits > 0 {
+            let mut carry = 0u32;
+            for limb in &mut self.0 {
+                let next = *limb >> (32 - bits);
+                *limb = (*limb << bits) | carry;
+                carry = next;
+            }
+            if carry > 0 {
+                self.0.push(carry);
+            }
+        }
+        self.0.splice(0..0, std::iter::repeat_n(0, limbs));
+    }
+
+    pub fn shr(&mut self, bits: u64) {
+        let limbs = (bits / 32).min(self.0.len() as u64) as usize;
+        self.0.drain(..limbs);
+        let bits = (bits % 32) as u32;
+        if bits > 0 {
+            let mut carry = 0u32;
+            for limb in self.0.iter_mut().rev() {
+                let next = *limb << (32 - bits);
+                *limb = (*limb >> bits) | carry;
+                carry = next;
+            }
+        }
+        trim(&mut self.0);
+    }
+
+    /// render in decimal notation
+    pub fn to_decimal(&self) -> String {
+        let mut chunks = Vec::new();
+        let mut n = self.clone();
+        while !n.is_zero() {
+            chunks.push(n.div_small(POW10_9));
+        }
+        let mut s = chunks.pop().unwrap_or_default().to_string();
+        for chunk in chunks.iter().rev() {
+            s.push_str(&format!("{:09}", chunk));
+        }
+        s
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
@@ -6,11 +6,16 @@ use std::{
     fmt::Debug,
 };
 
+mod bigint;
 mod number;
+mod ordered;
 mod timestamp;
+mod tree;
 
 pub use number::{Exponential, Number};
+pub use ordered::OrderedDict;
 pub use timestamp::{Precision, Timestamp};
+pub use tree::CborTree;
 
 /// Lifted navigation structure for a CborValue.
 ///
@@ -244,7 +249,7 @@ mod tests {
     fn test_str_lifetime() {
         fn _check_compile<'a, 'err: 'a>(value: &'a CborValue<'err>) -> &'err str {
             match value.as_str().unwrap() {
-                std::borrow::Cow::Borrowed(b) => *b,
+                std::borrow::Cow::Borrowed(b) => b,
                 std::borrow::Cow::Owned(_) => todo!(),
             }
         }
@@ -254,7 +259,7 @@ mod tests {
     fn test_bytes_lifetime() {
         fn _check_compile<'a, 'err: 'a>(value: &'a CborValue<'err>) -> &'err [u8] {
             match value.as_bytes().unwrap() {
-                std::borrow::Cow::Borrowed(b) => *b,
+                std::borrow::Cow::Borrowed(b) => b,
                 std::borrow::Cow::Owned(_) => todo!(),
             }
         }
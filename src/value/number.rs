@@ -1,9 +1,26 @@
 use self::Number::*;
+use super::bigint::BigUint;
 use crate::{constants::*, Encoder, ItemKind, TaggedItem, Writer};
-use std::{borrow::Cow, marker::PhantomData};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+    marker::PhantomData,
+};
 
 /// Representation of a number extracted from a CBOR item
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Numbers are compared by their exact mathematical value across all variants, so that for
+/// example `Int(57)` equals the big decimal `570e-1` and `IEEE754(0.5)` is less than the big float
+/// `3 * 2.pow(-2)`. NaN is not equal to anything, see [`total_cmp`](#method.total_cmp) for an
+/// ordering that includes it.
+///
+/// The only exception to exact comparison are big decimals whose exponent differs by more than
+/// 65536 from the other number’s power of ten (which is zero for all other variants): these are
+/// ordered by binary logarithms computed with `f64` precision, so values whose logarithms differ
+/// by less than about `1e-15` times their magnitude may be misordered or compare equal.
+#[derive(Debug, Clone)]
 pub enum Number<'a> {
     /// an integer number from major types 0 or 1
     Int(i128),
@@ -131,6 +148,373 @@ impl<'a> Number<'a> {
             Float(_) => "big float",
         }
     }
+
+    /// Total ordering by value across all variants
+    ///
+    /// This extends the `PartialOrd` ordering by placing negative NaNs below and positive NaNs above all other numbers. In
+    /// contrast to [`f64::total_cmp`](https://doc.rust-lang.org/std/primitive.f64.html#method.total_cmp),
+    /// `-0.0` and `0.0` are equal.
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        let rank = |n: &Self| match n {
+            IEEE754(f) if f.is_nan() => {
+                if f.is_sign_negative() {
+                    0
+                } else {
+                    2
+                }
+            }
+            _ => 1,
+        };
+        match (rank(self), rank(other)) {
+            (1, 1) => self.partial_cmp(other).unwrap_or(Ordering::Equal),
+            (a, b) => a.cmp(&b),
+        }
+    }
+
+    /// Convert to an integer if the value is integral and fits into an `i128`
+    pub fn to_i128_exact(&self) -> Option<i128> {
+        if let Int(i) = self {
+            return Some(*i);
+        }
+        let (negative, magnitude) = self.exact()?.into_integer()?;
+        let magnitude = magnitude.to_u128()?;
+        if negative {
+            if magnitude > 1 << 127 {
+                None
+            } else {
+                Some((magnitude as i128).wrapping_neg())
+            }
+        } else {
+            i128::try_from(magnitude).ok()
+        }
+    }
+
+    /// Convert to an integer if the value is integral and fits into a `u64`
+    pub fn to_u64_exact(&self) -> Option<u64> {
+        u64::try_from(self.to_i128_exact()?).ok()
+    }
+
+    /// Convert to the nearest `f64`, rounding half to even
+    ///
+    /// Values beyond the range of `f64` become infinities, values too small to be represented
+    /// become zero.
+    pub fn to_f64_lossy(&self) -> f64 {
+        match self {
+            IEEE754(f) => *f,
+            _ => self.exact().map(Exact::into_f64).unwrap_or(f64::NAN),
+        }
+    }
+
+    /// Convert to an `f64` if this is possible without loss of precision
+    ///
+    /// `IEEE754` values are always returned, including NaNs and infinities.
+    pub fn to_f64_exact(&self) -> Option<f64> {
+        let x = match self {
+            IEEE754(f) => return Some(*f),
+            _ => self.exact()?,
+        };
+        let Exact {
+            negative,
+            mut magnitude,
+            mut exp2,
+            exp5,
+        } = x;
+        if magnitude.is_zero() {
+            return Some(0.0);
+        }
+        if exp5 < 0 {
+            // the factors of five must cancel out
+            let k = u64::try_from(-exp5).ok()?;
+            if magnitude.remove_factors_of_5(k) < k {
+                return None;
+            }
+        } else if exp5 > 0 {
+            // the odd part of the result must fit into 53 bits, and 5.pow(23) does not
+            if exp5 > 22 {
+                return None;
+            }
+            magnitude.mul_pow5(exp5 as u64);
+        }
+        let tz = magnitude.trailing_zeros();
+        magnitude.shr(tz);
+        exp2 = exp2.checked_add(i128::from(tz))?;
+        let bits = magnitude.bits();
+        if bits > 53 || exp2 < -1074 || exp2 + bits as i128 > 1024 {
+            return None;
+        }
+        let f = magnitude.to_u128()? as f64 * pow2(exp2 as i32);
+        Some(if negative { -f } else { f })
+    }
+
+    /// exact value of a finite number, `None` for NaN and infinities
+    fn exact(&self) -> Option<Exact> {
+        match self {
+            Int(i) => Some(Exact {
+                negative: *i < 0,
+                magnitude: BigUint::from_u128(i.unsigned_abs()),
+                exp2: 0,
+                exp5: 0,
+            }),
+            IEEE754(f) => {
+                if !f.is_finite() {
+                    return None;
+                }
+                let bits = f.to_bits();
+                let exp = ((bits >> 52) & 0x7ff) as i128;
+                let frac = bits & ((1 << 52) - 1);
+                let (mantissa, exp2) = if exp == 0 {
+                    (frac, -1074)
+                } else {
+                    (frac | (1 << 52), exp - 1075)
+                };
+                Some(Exact {
+                    negative: f.is_sign_negative(),
+                    magnitude: BigUint::from_u128(mantissa.into()),
+                    exp2,
+                    exp5: 0,
+                })
+            }
+            Decimal(e) => Some(Exact::new(e)),
+            Float(e) => Some(Exact::new(e)),
+        }
+    }
+}
+
+impl<'a> PartialEq for Number<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl<'a> PartialOrd for Number<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Int(a), Int(b)) => Some(a.cmp(b)),
+            (IEEE754(a), IEEE754(b)) => a.partial_cmp(b),
+            _ => match (self.exact(), other.exact()) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                (None, Some(_)) => non_finite_cmp(self),
+                (Some(_), None) => non_finite_cmp(other).map(Ordering::reverse),
+                (None, None) => self.to_f64_lossy().partial_cmp(&other.to_f64_lossy()),
+            },
+        }
+    }
+}
+
+/// compare a NaN or infinity with any finite number
+fn non_finite_cmp(n: &Number) -> Option<Ordering> {
+    n.to_f64_lossy().partial_cmp(&0.0)
+}
+
+/// exact value `±magnitude * 2.pow(exp2) * 5.pow(exp5)` of a finite number
+struct Exact {
+    negative: bool,
+    magnitude: BigUint,
+    exp2: i128,
+    exp5: i128,
+}
+
+/// difference of the powers of five beyond which comparisons rely on logarithms instead of exact
+/// arithmetic
+const MAX_EXACT_EXP5: i128 = 1 << 16;
+const LOG2_5: f64 = 2.321928094887362;
+
+impl Exact {
+    fn new<B: Base>(e: &Exponential<'_, B>) -> Self {
+        let mut magnitude = BigUint::from_be_bytes(e.mantissa());
+        if e.inverted() {
+            magnitude.add_small(1);
+        }
+        Self {
+            negative: e.inverted(),
+            magnitude,
+            exp2: e.exponent(),
+            exp5: if B::BASE == 10 { e.exponent() } else { 0 },
+        }
+    }
+
+    fn signum(&self) -> i8 {
+        if self.magnitude.is_zero() {
+            0
+        } else if self.negative {
+            -1
+        } else {
+            1
+        }
+    }
+
+    /// approximate binary logarithm of the absolute value
+    fn log2(&self) -> f64 {
+        self.magnitude.log2() + self.exp2 as f64 + self.exp5 as f64 * LOG2_5
+    }
+
+    fn cmp(&self, other: &Self) -> Ordering {
+        let signum = self.signum();
+        match signum.cmp(&other.signum()) {
+            Ordering::Equal => {}
+            o => return o,
+        }
+        match signum {
+            0 => Ordering::Equal,
+            1 => self.cmp_magnitude(other),
+            _ => other.cmp_magnitude(self),
+        }
+    }
+
+    /// compare absolute values of non-zero numbers
+    fn cmp_magnitude(&self, other: &Self) -> Ordering {
+        let d5 = self.exp5.saturating_sub(other.exp5);
+        if d5.abs() > MAX_EXACT_EXP5 {
+            return self
+                .log2()
+                .partial_cmp(&other.log2())
+                .unwrap_or(Ordering::Equal);
+        }
+        // the binary logarithms are `bits + exp2 + exp5 * LOG2_5` minus less than one, so their
+        // difference is `d + d5 * LOG2_5` up to less than one
+        let d2 = self.exp2.saturating_sub(other.exp2);
+        let bits = |x: &Self| i128::from(x.magnitude.bits());
+        let d = (bits(self) - bits(other)).saturating_add(d2);
+        let bound = 2 + (d5.abs() as f64 * LOG2_5).ceil() as i128;
+        if d > bound {
+            return Ordering::Greater;
+        } else if d < -bound {
+            return Ordering::Less;
+        }
+        let diff = d as f64 + d5 as f64 * LOG2_5;
+        if diff > 1.001 {
+            return Ordering::Greater;
+        } else if diff < -1.001 {
+            return Ordering::Less;
+        }
+        // now `d2` is bounded by the sizes of the magnitudes and `d5`
+        let scale = |x: &Self, e2: i128, e5: i128| {
+            let mut m = x.magnitude.clone();
+            m.shl(e2.max(0) as u64);
+            m.mul_pow5(e5.max(0) as u64);
+            m
+        };
+        let a = scale(self, d2, d5);
+        let b = scale(other, -d2, -d5);
+        a.cmp(&b)
+    }
+
+    /// sign and magnitude if the value is integral
+    fn into_integer(self) -> Option<(bool, BigUint)> {
+        let Exact {
+            negative,
+            mut magnitude,
+            exp2,
+            exp5,
+        } = self;
+        if magnitude.is_zero() {
+            return Some((false, magnitude));
+        }
+        if exp5 < 0 {
+            let k = u64::try_from(-exp5).ok()?;
+            if k > magnitude.bits() || magnitude.remove_factors_of_5(k) < k {
+                return None;
+            }
+        }
+        if exp2 < 0 {
+            let k = u64::try_from(-exp2).ok()?;
+            if magnitude.trailing_zeros() < k {
+                return None;
+            }
+            magnitude.shr(k);
+        }
+        // any larger factor exceeds the range of the callers anyway
+        if exp5 > 0 {
+            if exp5 > 128 {
+                return None;
+            }
+            magnitude.mul_pow5(exp5 as u64);
+        }
+        if exp2 > 0 {
+            if exp2 > 128 {
+                return None;
+            }
+            magnitude.shl(exp2 as u64);
+        }
+        Some((negative, magnitude))
+    }
+
+    fn into_f64(self) -> f64 {
+        let sign = if self.negative { -1.0 } else { 1.0 };
+        if self.magnitude.is_zero() {
+            return 0.0;
+        }
+        let log2 = self.log2();
+        if log2 > 1100.0 {
+            return sign * f64::INFINITY;
+        } else if log2 < -1100.0 {
+            return sign * 0.0;
+        }
+        let Exact {
+            negative,
+            mut magnitude,
+            mut exp2,
+            exp5,
+        } = self;
+        let mut sticky = false;
+        if exp5 > 0 {
+            magnitude.mul_pow5(exp5 as u64);
+        } else if exp5 < 0 {
+            // keep enough quotient bits for correct rounding, but no more: a big mantissa is
+            // shortened first, remembering whether nonzero bits were dropped
+            let k = (-exp5) as u64;
+            let target = 70 + (k as f64 * LOG2_5).ceil() as u64;
+            let bits = magnitude.bits();
+            if bits < target {
+                magnitude.shl(target - bits);
+            } else {
+                sticky = magnitude.trailing_zeros() < bits - target;
+                magnitude.shr(bits - target);
+            }
+            exp2 -= i128::from(target) - i128::from(bits);
+            sticky |= !magnitude.div_pow5(k);
+        }
+        round_to_f64(negative, magnitude, exp2, sticky)
+    }
+}
+
+/// round `±magnitude * 2.pow(exp2)` to the nearest `f64`, where `sticky` indicates that the true
+/// magnitude is slightly larger than given
+fn round_to_f64(negative: bool, magnitude: BigUint, exp2: i128, sticky: bool) -> f64 {
+    let sign = if negative { -1.0 } else { 1.0 };
+    let bits = magnitude.bits() as i128;
+    let top = bits - 1 + exp2;
+    if top > 1023 {
+        return sign * f64::INFINITY;
+    }
+    let lsb = (top - 52).max(-1074);
+    let shift = lsb - exp2;
+    let mantissa = if shift <= 0 {
+        (magnitude.to_u128().unwrap_or_default() << -shift) as u64
+    } else if shift > bits {
+        0
+    } else {
+        let shift = shift as u64;
+        let half = magnitude.bit(shift - 1);
+        let below = sticky || magnitude.trailing_zeros() < shift - 1;
+        let mut q = magnitude;
+        q.shr(shift);
+        let mut mantissa = q.to_u128().unwrap_or_default() as u64;
+        if half && (below || mantissa & 1 == 1) {
+            mantissa += 1;
+        }
+        mantissa
+    };
+    sign * (mantissa as f64 * pow2(lsb as i32))
+}
+
+/// `2.pow(exp)` for `-1074 <= exp <= 1023`
+fn pow2(exp: i32) -> f64 {
+    if exp >= -1022 {
+        f64::from_bits(((exp + 1023) as u64) << 52)
+    } else {
+        f64::from_bits(1 << (exp + 1074))
+    }
 }
 
 /// A representation of a bignum
@@ -214,6 +598,61 @@ impl<'a, B: Base> Exponential<'a, B> {
     }
 }
 
+/// exponents up to this magnitude are rendered in positional notation, which covers all `f64`
+const MAX_POSITIONAL_EXP: u64 = 1100;
+
+/// Renders the exact value in positional decimal notation, like `-12.5` or `1200`
+///
+/// No precision is lost, so the output grows with the magnitude of the exponent; a big float
+/// with exponent `-n` has `n` fractional digits. Therefore, exponents beyond ±1100 are rendered
+/// as the mantissa followed by `e` and the exponent for big decimals (like `-3e-2000`) or by `p`
+/// and the exponent for big floats (like `5p4000`, meaning `5 * 2.pow(4000)`).
+impl<'a, B: Base> Display for Exponential<'a, B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut magnitude = BigUint::from_be_bytes(self.mantissa());
+        if self.inverted() {
+            magnitude.add_small(1);
+            f.write_str("-")?;
+        } else if magnitude.is_zero() {
+            return f.write_str("0");
+        }
+        let exp = self.exponent();
+        let k = match u64::try_from(exp.unsigned_abs()) {
+            Ok(k) if k <= MAX_POSITIONAL_EXP => k,
+            _ => {
+                let marker = if B::BASE == 2 { 'p' } else { 'e' };
+                return write!(f, "{}{}{}", magnitude.to_decimal(), marker, exp);
+            }
+        };
+        // the value is `digits * 10.pow(-scale)`
+        let mut scale = 0;
+        if exp < 0 {
+            if B::BASE == 2 {
+                magnitude.mul_pow5(k);
+            }
+            scale = k as usize;
+        } else if B::BASE == 2 {
+            magnitude.shl(k);
+        }
+        let mut digits = magnitude.to_decimal();
+        while scale > 0 && digits.ends_with('0') {
+            digits.pop();
+            scale -= 1;
+        }
+        if exp > 0 && B::BASE == 10 {
+            digits.extend(std::iter::repeat_n('0', k as usize));
+        }
+        if scale == 0 {
+            f.write_str(&digits)
+        } else if digits.len() <= scale {
+            write!(f, "0.{:0>width$}", digits, width = scale)
+        } else {
+            let (int, frac) = digits.split_at(digits.len() - scale);
+            write!(f, "{}.{}", int, frac)
+        }
+    }
+}
+
 pub trait Base {
     const BASE: u64;
     const TAG: u64;
@@ -419,4 +858,165 @@ mod tests {
             "5([-1, 3(h'010203040506070809')])"
         );
     }
+
+    fn d(exp: i128, mant: &str, inv: bool) -> Number<'static> {
+        Decimal(Exponential::new(exp, hex(mant).into(), inv))
+    }
+    fn f(exp: i128, mant: &str, inv: bool) -> Number<'static> {
+        Float(Exponential::new(exp, hex(mant).into(), inv))
+    }
+
+    #[test]
+    fn compare() {
+        assert_eq!(Int(57), d(-1, "023a", false));
+        assert_eq!(Int(-2), d(0, "01", true));
+        assert_eq!(IEEE754(0.5), d(-1, "05", false));
+        assert_eq!(IEEE754(-0.0), Int(0));
+        assert_eq!(f(-1, "01", false), d(-1, "05", false));
+        assert_eq!(d(-1, "", false), f(3, "", false));
+        assert!(IEEE754(0.5) < f(-2, "03", false));
+        assert!(d(1, "01", false) > f(3, "01", false));
+        assert!(d(-1, "01", false) < IEEE754(0.1));
+        assert!(d(-1, "01", true) < IEEE754(-0.1));
+        assert!(d(400, "01", false) > IEEE754(f64::MAX));
+        assert!(d(400, "01", false) < IEEE754(f64::INFINITY));
+        assert!(d(400, "01", true) > IEEE754(f64::NEG_INFINITY));
+        assert!(d(-400, "01", false) > Int(0));
+        assert!(d(-400, "01", false) < IEEE754(5e-324));
+        assert!(d(100_000, "01", false) > f(300_000, "01", false));
+        assert!(d(100_000, "01", false) < f(400_000, "01", false));
+        assert!(d(-100_000, "01", false) > f(-400_000, "01", false));
+        // 10.pow(100_000) is about 2.pow(332_192.81)
+        assert!(d(100_000, "01", false) > f(332_192, "01", false));
+        assert!(d(100_000, "01", false) < f(332_193, "01", false));
+        assert!(d(1 << 40, "01", true) < f(3 << 40, "01", true));
+        assert!(f(1 << 100, "01", false) > f((1 << 100) - (1 << 40), "01", false));
+        assert!(f(1 << 100, "01", false) < f((1 << 100) - 1, "03", false));
+        assert_eq!(f(1 << 100, "01", false), f((1 << 100) - 2, "04", false));
+        assert!(d(1 << 100, "01", false) < d((1 << 100) + 1, "01", false));
+        assert_ne!(IEEE754(f64::NAN), IEEE754(f64::NAN));
+        assert_eq!(IEEE754(f64::NAN).partial_cmp(&d(1, "01", false)), None);
+
+        let mut numbers = [
+            IEEE754(f64::NAN),
+            d(-1, "05", false),
+            IEEE754(f64::INFINITY),
+            Int(-3),
+            IEEE754(-f64::NAN),
+            f(-2, "03", false),
+            IEEE754(f64::NEG_INFINITY),
+            d(0, "02", true),
+        ];
+        numbers.sort_by(Number::total_cmp);
+        let sorted = numbers.iter().map(Number::to_f64_lossy).collect::<Vec<_>>();
+        assert!(sorted[0].is_nan() && sorted[0].is_sign_negative());
+        assert_eq!(
+            &sorted[1..7],
+            [f64::NEG_INFINITY, -3.0, -3.0, 0.5, 0.75, f64::INFINITY]
+        );
+        assert!(sorted[7].is_nan() && sorted[7].is_sign_positive());
+    }
+
+    #[test]
+    fn convert() {
+        assert_eq!(d(2, "03", false).to_i128_exact(), Some(300));
+        assert_eq!(d(-1, "0f", false).to_i128_exact(), None);
+        assert_eq!(d(-1, "14", false).to_i128_exact(), Some(2));
+        assert_eq!(d(-1000, "", false).to_i128_exact(), Some(0));
+        assert_eq!(f(-1, "06", false).to_i128_exact(), Some(3));
+        assert_eq!(f(127, "", true).to_i128_exact(), Some(i128::MIN));
+        assert_eq!(f(127, "01", false).to_i128_exact(), None);
+        assert_eq!(IEEE754(2.5).to_i128_exact(), None);
+        assert_eq!(IEEE754(-4.0).to_i128_exact(), Some(-4));
+        assert_eq!(IEEE754(f64::INFINITY).to_i128_exact(), None);
+        assert_eq!(Int(-1).to_u64_exact(), None);
+        assert_eq!(f(63, "01", false).to_u64_exact(), Some(1 << 63));
+        assert_eq!(f(64, "01", false).to_u64_exact(), None);
+
+        assert_eq!(d(-1, "05", false).to_f64_exact(), Some(0.5));
+        assert_eq!(d(-1, "01", false).to_f64_exact(), None);
+        assert_eq!(d(22, "01", true).to_f64_exact(), Some(-2e22));
+        assert_eq!(d(23, "01", false).to_f64_exact(), None);
+        assert_eq!(f(-1074, "01", false).to_f64_exact(), Some(5e-324));
+        assert_eq!(f(-1075, "01", false).to_f64_exact(), None);
+        assert_eq!(f(1023, "01", false).to_f64_exact(), Some(2f64.powi(1023)));
+        assert_eq!(f(1024, "01", false).to_f64_exact(), None);
+        assert_eq!(Int((1 << 53) + 1).to_f64_exact(), None);
+        assert_eq!(Int(1 << 53).to_f64_exact(), Some(9007199254740992.0));
+
+        assert_eq!(d(-1, "01", false).to_f64_lossy(), 0.1);
+        assert_eq!(d(-400, "01", false).to_f64_lossy(), 0.0);
+        assert_eq!(d(-325, "03", false).to_f64_lossy(), 0.0);
+        assert_eq!(d(-324, "05", false).to_f64_lossy(), 5e-324);
+        assert_eq!(d(400, "01", true).to_f64_lossy(), f64::NEG_INFINITY);
+        assert_eq!(d(292, "3fddec7f2faf35", false).to_f64_lossy(), f64::MAX);
+        assert_eq!(Int((1 << 53) + 1).to_f64_lossy(), 9007199254740992.0);
+        assert_eq!(Int((1 << 53) + 3).to_f64_lossy(), 9007199254740996.0);
+
+        // a big mantissa with a matching negative exponent, 2.pow(80_000) * 10.pow(-24_000)
+        let mut mant = BigUint::from_u128(1);
+        mant.shl(80_000);
+        let n = d(-24_000, &format!("01{}", "00".repeat(10_000)), false);
+        let expected = format!("{}e-24000", mant.to_decimal())
+            .parse::<f64>()
+            .unwrap();
+        assert_eq!(n.to_f64_lossy(), expected);
+        let n = d(-24_000, &format!("01{}01", "00".repeat(9_999)), false);
+        assert_eq!(n.to_f64_lossy(), expected);
+
+        // compare against the standard library’s correctly rounded parser
+        let mut state = 0x2545f4914f6cdd1du64;
+        for _ in 0..2000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let mant = state >> (state % 40);
+            let exp = (state % 700) as i128 - 350;
+            let n = d(exp, &format!("{:016x}", mant), false);
+            let expected = format!("{}e{}", mant, exp).parse::<f64>().unwrap();
+            assert_eq!(n.to_f64_lossy(), expected, "{}e{}", mant, exp);
+            if let Some(x) = n.to_f64_exact() {
+                assert_eq!(x, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn display() {
+        fn s(n: Number) -> String {
+            match n {
+                Decimal(e) => e.to_string(),
+                Float(e) => e.to_string(),
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(s(d(-2, "3039", false)), "123.45");
+        assert_eq!(s(d(-3, "", true)), "-0.001");
+        assert_eq!(s(d(2, "0c", false)), "1200");
+        assert_eq!(s(d(-2, "0a", false)), "0.1");
+        assert_eq!(s(d(-1, "64", false)), "10");
+        assert_eq!(s(d(5, "", false)), "0");
+        assert_eq!(s(d(-2, "", false)), "0");
+        assert_eq!(s(f(-3, "01", false)), "0.125");
+        assert_eq!(s(f(-3, "01", true)), "-0.25");
+        assert_eq!(s(f(4, "03", false)), "48");
+        assert_eq!(
+            s(d(-10, "ffffffffffffffffffffffffffffffffffffffff", false)),
+            "146150163733090291820368483271628301965.5932542975"
+        );
+        assert_eq!(s(f(-20, "01", false)), "0.00000095367431640625");
+
+        // beyond the positional notation bound
+        let exp = -(1i128 << 64);
+        assert_eq!(s(d(exp, "03", false)), "3e-18446744073709551616");
+        assert_eq!(s(d(exp, "02", true)), "-3e-18446744073709551616");
+        assert_eq!(s(d(exp, "", false)), "0");
+        assert_eq!(s(d(1 << 40, "01", false)), "1e1099511627776");
+        assert_eq!(s(f(1 << 40, "01", false)), "1p1099511627776");
+        assert_eq!(s(f(i128::MIN, "0c", true)), format!("-13p{}", i128::MIN));
+        assert_eq!(s(d(1100, "01", false)), format!("1{}", "0".repeat(1100)));
+        assert_eq!(s(d(1101, "01", false)), "1e1101");
+        assert_eq!(s(f(-1100, "01", false)).len(), 1102);
+        assert_eq!(s(f(-1101, "01", false)), "1p-1101");
+    }
 }
@@ -0,0 +1,196 @@
+use super::ms;
+use crate::{constants::TAG_CBOR_ITEM, Cbor, CborOwned, ItemKind, TaggedItem};
+use std::{
+    borrow::Cow,
+    collections::{btree_map::Entry, BTreeMap},
+};
+
+/// Decoded dict that retains the order of its entries as well as duplicate keys
+///
+/// In contrast to [`CborValue::Dict`](enum.CborValue.html#variant.Dict), iteration yields the
+/// entries in the order in which they were encoded. Keys are compared by their encoded bytes,
+/// like for `CborValue::Dict`: only the first occurrence of each key is an entry of this map,
+/// later occurrences are available from [`duplicates`](#method.duplicates).
+///
+/// You can obtain this using [`Cbor::decode_ordered()`](../struct.Cbor.html#method.decode_ordered).
+///
+/// ```
+/// use cbor_data::{Cbor, CborOwned};
+///
+/// // {"b": 1, "a": 2, "b": 3}
+/// let cbor = Cbor::checked(b"\xa3\x61b\x01\x61a\x02\x61b\x03").unwrap();
+/// let dict = cbor.decode_ordered().unwrap();
+///
+/// let entries = dict.iter().map(|(k, v)| format!("{}: {}", k, v)).collect::<Vec<_>>();
+/// assert_eq!(entries, [r#""b": 1"#, r#""a": 2"#]);
+/// let b = CborOwned::canonical(b"\x61b").unwrap();
+/// assert_eq!(dict.get(&b).unwrap().to_string(), "1");
+/// assert_eq!(dict.duplicates()[0].1.to_string(), "3");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderedDict<'a> {
+    entries: Vec<(Cow<'a, Cbor>, Cow<'a, Cbor>)>,
+    duplicates: Vec<(Cow<'a, Cbor>, Cow<'a, Cbor>)>,
+    /// indices into `entries` in the order of their keys, for lookups
+    by_key: Vec<usize>,
+}
+
+impl<'a> OrderedDict<'a> {
+    /// Decode the item if it is a dict, possibly wrapped in
+    /// [`TAG_CBOR_ITEM`](../constants/constant.TAG_CBOR_ITEM.html) byte strings.
+    ///
+    /// Returns `None` for all other items, including dicts with other tags.
+    pub fn new(item: TaggedItem<'a>) -> Option<Self> {
+        match (item.tags().single(), item.kind()) {
+            (None, ItemKind::Dict(iter)) => {
+                let mut seen = BTreeMap::new();
+                let (mut entries, mut duplicates) = (Vec::new(), Vec::new());
+                for (key, value) in iter {
+                    let entry = (Cow::Borrowed(key), Cow::Borrowed(value));
+                    match seen.entry(key) {
+                        Entry::Vacant(e) => {
+                            e.insert(entries.len());
+                            entries.push(entry);
+                        }
+                        Entry::Occupied(_) => duplicates.push(entry),
+                    }
+                }
+                Some(Self {
+                    entries,
+                    duplicates,
+                    by_key: seen.into_values().collect(),
+                })
+            }
+            (Some(TAG_CBOR_ITEM), ItemKind::Bytes(b)) => {
+                if let Some(b) = b.as_slice() {
+                    Self::new(Cbor::unchecked(b).tagged_item())
+                } else {
+                    let cbor = CborOwned::unchecked(b.to_vec());
+                    OrderedDict::new(cbor.tagged_item()).map(OrderedDict::make_static)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Number of distinct keys
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Look up the value for the first occurrence of the given key
+    pub fn get(&self, key: &Cbor) -> Option<&Cow<'a, Cbor>> {
+        let pos = self
+            .by_key
+            .binary_search_by(|idx| self.entries[*idx].0.as_ref().cmp(key))
+            .ok()?;
+        Some(&self.entries[self.by_key[pos]].1)
+    }
+
+    /// Iterate over the entries in the order in which they were encoded, skipping duplicates
+    pub fn iter(&self) -> impl Iterator<Item = (&Cow<'a, Cbor>, &Cow<'a, Cbor>)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    /// The entries in the order in which they were encoded, skipping duplicates
+    pub fn as_slice(&self) -> &[(Cow<'a, Cbor>, Cow<'a, Cbor>)] {
+        self.entries.as_slice()
+    }
+
+    /// Entries whose key was already encoded earlier in the dict, in encoding order
+    pub fn duplicates(&self) -> &[(Cow<'a, Cbor>, Cow<'a, Cbor>)] {
+        self.duplicates.as_slice()
+    }
+
+    /// Take the entries, skipping duplicates
+    pub fn into_entries(self) -> Vec<(Cow<'a, Cbor>, Cow<'a, Cbor>)> {
+        self.entries
+    }
+
+    /// Cut all ties to the underlying byte slice, which often implies allocations
+    pub fn make_static(self) -> OrderedDict<'static> {
+        let ms_all = |entries: Vec<(Cow<'a, Cbor>, Cow<'a, Cbor>)>| {
+            entries
+                .into_iter()
+                .map(|(k, v)| (ms(k), ms(v)))
+                .collect::<Vec<_>>()
+        };
+        OrderedDict {
+            entries: ms_all(self.entries),
+            duplicates: ms_all(self.duplicates),
+            by_key: self.by_key,
+        }
+    }
+}
+
+impl<'a> IntoIterator for OrderedDict<'a> {
+    type Item = (Cow<'a, Cbor>, Cow<'a, Cbor>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Cbor;
+
+    fn entries(pairs: &[(std::borrow::Cow<Cbor>, std::borrow::Cow<Cbor>)]) -> Vec<String> {
+        pairs.iter().map(|(k, v)| format!("{}: {}", k, v)).collect()
+    }
+
+    #[test]
+    fn order_and_duplicates() {
+        // {_ 3: 1, "a": 2, 3: 3, 1: 4, "a": 5}
+        let bytes = [0xbf, 3, 1, 0x61, b'a', 2, 3, 3, 1, 4, 0x61, b'a', 5, 0xff];
+        let dict = Cbor::checked(&bytes).unwrap().decode_ordered().unwrap();
+        assert_eq!(dict.len(), 3);
+        assert_eq!(entries(dict.as_slice()), ["3: 1", r#""a": 2"#, "1: 4"]);
+        assert_eq!(entries(dict.duplicates()), ["3: 3", r#""a": 5"#]);
+        assert_eq!(
+            dict.get(Cbor::checked(&[3]).unwrap()).unwrap().to_string(),
+            "1"
+        );
+        assert_eq!(dict.get(Cbor::checked(&[4]).unwrap()), None);
+        for (key, value) in dict.iter() {
+            assert_eq!(dict.get(key), Some(value));
+        }
+
+        // the BTreeMap-based decoding keeps the same entries
+        let value = Cbor::checked(&bytes).unwrap().decode();
+        let sorted = value.as_dict().unwrap();
+        assert_eq!(sorted.len(), dict.len());
+        assert!(dict.iter().all(|(k, v)| sorted.get(k) == Some(v)));
+
+        let dict = dict.make_static();
+        assert_eq!(
+            dict.into_iter()
+                .map(|(k, _)| k.to_string())
+                .collect::<Vec<_>>(),
+            ["3", r#""a""#, "1"]
+        );
+    }
+
+    #[test]
+    fn tags() {
+        // 24(h'a1f6f5') and 24((_ h'a1', h'f6f5'))
+        let embedded = Cbor::checked(&[0xd8, 24, 0x43, 0xa1, 0xf6, 0xf5]).unwrap();
+        let dict = embedded.decode_ordered().unwrap();
+        assert_eq!(entries(dict.as_slice()), ["null: true"]);
+        let chunked = Cbor::checked(&[0xd8, 24, 0x5f, 0x41, 0xa1, 0x42, 0xf6, 0xf5, 0xff]).unwrap();
+        assert_eq!(chunked.decode_ordered(), Some(dict));
+
+        assert_eq!(Cbor::checked(&[0xc1, 0xa0]).unwrap().decode_ordered(), None);
+        assert_eq!(Cbor::checked(&[0x80]).unwrap().decode_ordered(), None);
+        assert!(Cbor::checked(&[0xa0])
+            .unwrap()
+            .decode_ordered()
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,690 @@
+use crate::{
+    builder::low_level::{
+        write_bool, write_bytes, write_indefinite, write_info, write_lit, write_neg, write_null,
+        write_positive, write_str, write_tags, write_undefined,
+    },
+    constants::{MAJOR_ARRAY, MAJOR_DICT, STOP_BYTE},
+    validated::Lengths,
+    ArrayIter, Cbor, CborBuilder, DictIter, ItemKind, Literal, PathElement, Tags, Writer,
+};
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+    mem::{replace, take},
+};
+
+/// Fully decoded CBOR item that owns all its contents
+///
+/// In contrast to [`CborValue`](enum.CborValue.html), which decodes only one level and
+/// interprets well-known tags, this is a faithful recursive representation of the CBOR data
+/// model: tags are retained as [`Tagged`](#variant.Tagged) wrappers (from outer to inner)
+/// and dicts keep their entries in order, including duplicate keys. This makes it suitable for
+/// walking and modifying whole documents, which can then be written back using
+/// [`write`](#method.write).
+///
+/// You can obtain this using [`Cbor::to_tree()`](../struct.Cbor.html#method.to_tree).
+///
+/// ```
+/// use cbor_data::{index_str, CborBuilder, CborOwned, CborTree};
+///
+/// // {"a": [1, 2(h'01')]}
+/// let cbor = CborOwned::canonical([0xa1, 0x61, b'a', 0x82, 1, 0xc2, 0x41, 1]).unwrap();
+/// let mut tree = cbor.to_tree();
+/// *tree.index_mut(index_str("a[0]")).unwrap() = CborTree::Str("x".into());
+/// tree.as_dict_mut().unwrap().push((CborTree::Pos(5), CborTree::Null));
+/// assert_eq!(tree.index(index_str("a[1]")), Some(&CborTree::Tagged(2, Box::new(CborTree::Bytes(vec![1])))));
+///
+/// let cbor = tree.write(CborBuilder::new());
+/// assert_eq!(cbor.to_string(), r#"{"a": ["x", 2(h'01')], 5: null}"#);
+/// ```
+///
+/// Decoding, writing, cloning, comparing, and dropping work with arbitrarily deep nesting.
+/// The latter requires an implementation of `Drop`, which means that contents cannot be moved
+/// out by pattern matching on a `CborTree` value. Use [`into_array`](#method.into_array),
+/// [`into_dict`](#method.into_dict), [`into_string`](#method.into_string), or
+/// [`into_bytes`](#method.into_bytes) instead, or match on a reference.
+#[derive(Debug)]
+pub enum CborTree {
+    /// unsigned integer
+    Pos(u64),
+    /// negative integer, with the value `-1 - n`
+    Neg(u64),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Bool(bool),
+    Null,
+    Undefined,
+    /// simple value other than bool, null, and undefined
+    Simple(u8),
+    Array(Vec<CborTree>),
+    Dict(Vec<(CborTree, CborTree)>),
+    Tagged(u64, Box<CborTree>),
+}
+
+impl CborTree {
+    /// Decode the given item with all its contents.
+    pub fn new(cbor: &Cbor) -> Self {
+        let mut lengths = Lengths::default();
+        let mut stack = Vec::<Open<'_>>::new();
+        let mut cbor = cbor;
+        loop {
+            let item = cbor.tagged_item();
+            let mut done = match item.kind() {
+                ItemKind::Pos(n) => Some(Self::Pos(n)),
+                ItemKind::Neg(n) => Some(Self::Neg(n)),
+                ItemKind::Float(f) => Some(Self::Float(f)),
+                ItemKind::Str(s) => Some(Self::Str(s.as_cow().into_owned())),
+                ItemKind::Bytes(b) => Some(Self::Bytes(b.as_cow().into_owned())),
+                ItemKind::Bool(b) => Some(Self::Bool(b)),
+                ItemKind::Null => Some(Self::Null),
+                ItemKind::Undefined => Some(Self::Undefined),
+                ItemKind::Simple(s) => Some(Self::Simple(s)),
+                ItemKind::Array(iter) => {
+                    stack.push(Open::Array(item.tags(), iter, Vec::new()));
+                    None
+                }
+                ItemKind::Dict(iter) => {
+                    stack.push(Open::Dict(item.tags(), iter, Vec::new(), None, None));
+                    None
+                }
+            }
+            .map(|tree| tree.with_tags(item.tags()));
+            // hand finished items to their parents until one of them has more contents
+            cbor = loop {
+                let open = match stack.last_mut() {
+                    Some(open) => open,
+                    None => return done.unwrap(),
+                };
+                if let Some(tree) = done.take() {
+                    open.push(tree);
+                }
+                match open.next(&mut lengths) {
+                    Some(next) => break next,
+                    None => done = stack.pop().map(Open::finish),
+                }
+            };
+        }
+    }
+
+    fn with_tags(self, tags: impl IntoIterator<Item = u64>) -> Self {
+        let tags = tags.into_iter().collect::<Vec<_>>();
+        tags.into_iter()
+            .rev()
+            .fold(self, |tree, tag| Self::Tagged(tag, Box::new(tree)))
+    }
+
+    /// Write this item to the given [`Writer`](../trait.Writer.html).
+    ///
+    /// Arrays and dicts use definite size encoding according to the writer’s
+    /// [`max_definite`](../trait.Writer.html#tymethod.max_definite) setting, integers use the
+    /// shortest encoding and floats are written in double precision.
+    pub fn write<W: Writer>(&self, mut writer: W) -> W::Output {
+        let max_definite = writer.max_definite();
+        let header = |b: &mut Vec<u8>, major: u8, len: usize| {
+            let len = len as u64;
+            if Some(len) > max_definite {
+                write_indefinite(b, major);
+                true
+            } else {
+                write_info(b, major, len);
+                false
+            }
+        };
+        writer.bytes(|b| {
+            // explicit stack of items still to be written, `None` standing for a stop byte
+            let mut stack = vec![Some(self)];
+            while let Some(tree) = stack.pop() {
+                let mut tree = match tree {
+                    Some(tree) => tree,
+                    None => {
+                        b.push(STOP_BYTE);
+                        continue;
+                    }
+                };
+                let mut tags = Vec::new();
+                while let Self::Tagged(tag, inner) = tree {
+                    tags.push(*tag);
+                    tree = inner;
+                }
+                match tree {
+                    Self::Pos(n) => write_positive(b, *n, tags),
+                    Self::Neg(n) => write_neg(b, *n, tags),
+                    Self::Float(f) => {
+                        write_tags(b, tags);
+                        write_lit(b, Literal::L8(f.to_bits()));
+                    }
+                    Self::Str(s) => write_str(b, s.len(), [s], tags),
+                    Self::Bytes(v) => write_bytes(b, v.len(), [v], tags),
+                    Self::Bool(v) => write_bool(b, *v, tags),
+                    Self::Null => write_null(b, tags),
+                    Self::Undefined => write_undefined(b, tags),
+                    Self::Simple(s) => {
+                        write_tags(b, tags);
+                        write_lit(
+                            b,
+                            if *s < 24 {
+                                Literal::L0(*s)
+                            } else {
+                                Literal::L1(*s)
+                            },
+                        );
+                    }
+                    Self::Array(items) => {
+                        write_tags(b, tags);
+                        if header(b, MAJOR_ARRAY, items.len()) {
+                            stack.push(None);
+                        }
+                        stack.extend(items.iter().rev().map(Some));
+                    }
+                    Self::Dict(entries) => {
+                        write_tags(b, tags);
+                        if header(b, MAJOR_DICT, entries.len()) {
+                            stack.push(None);
+                        }
+                        for (key, value) in entries.iter().rev() {
+                            stack.push(Some(value));
+                            stack.push(Some(key));
+                        }
+                    }
+                    Self::Tagged(..) => unreachable!(),
+                }
+            }
+        });
+        writer.into_output()
+    }
+
+    /// This item without its [`Tagged`](#variant.Tagged) wrappers
+    pub fn untagged(&self) -> &Self {
+        let mut tree = self;
+        while let Self::Tagged(_, inner) = tree {
+            tree = inner;
+        }
+        tree
+    }
+
+    /// This item without its [`Tagged`](#variant.Tagged) wrappers
+    pub fn untagged_mut(&mut self) -> &mut Self {
+        let mut tree = self;
+        while let Self::Tagged(_, inner) = tree {
+            tree = inner;
+        }
+        tree
+    }
+
+    /// The items of an array, ignoring tags
+    pub fn as_array(&self) -> Option<&Vec<CborTree>> {
+        if let Self::Array(a) = self.untagged() {
+            Some(a)
+        } else {
+            None
+        }
+    }
+
+    /// The items of an array, ignoring tags
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<CborTree>> {
+        if let Self::Array(a) = self.untagged_mut() {
+            Some(a)
+        } else {
+            None
+        }
+    }
+
+    /// The entries of a dict, ignoring tags
+    pub fn as_dict(&self) -> Option<&Vec<(CborTree, CborTree)>> {
+        if let Self::Dict(d) = self.untagged() {
+            Some(d)
+        } else {
+            None
+        }
+    }
+
+    /// The entries of a dict, ignoring tags
+    pub fn as_dict_mut(&mut self) -> Option<&mut Vec<(CborTree, CborTree)>> {
+        if let Self::Dict(d) = self.untagged_mut() {
+            Some(d)
+        } else {
+            None
+        }
+    }
+
+    /// The items of an array, ignoring tags
+    pub fn into_array(mut self) -> Option<Vec<CborTree>> {
+        self.as_array_mut().map(take)
+    }
+
+    /// The entries of a dict, ignoring tags
+    pub fn into_dict(mut self) -> Option<Vec<(CborTree, CborTree)>> {
+        self.as_dict_mut().map(take)
+    }
+
+    /// The contents of a text string, ignoring tags
+    pub fn into_string(mut self) -> Option<String> {
+        if let Self::Str(s) = self.untagged_mut() {
+            Some(take(s))
+        } else {
+            None
+        }
+    }
+
+    /// The contents of a byte string, ignoring tags
+    pub fn into_bytes(mut self) -> Option<Vec<u8>> {
+        if let Self::Bytes(b) = self.untagged_mut() {
+            Some(take(b))
+        } else {
+            None
+        }
+    }
+
+    /// Extract a value by indexing into arrays and dicts, with path elements yielded by an
+    /// iterator, see [`Cbor::index`](../struct.Cbor.html#method.index).
+    ///
+    /// Tags on the way are skipped and the first dict entry with a matching key is used. In
+    /// contrast to `Cbor::index`, [`TAG_CBOR_ITEM`](../constants/constant.TAG_CBOR_ITEM.html)
+    /// byte strings are not looked into.
+    /// [`PathElement::Item`](../enum.PathElement.html#variant.Item) matches keys with the same
+    /// tree representation, i.e. regardless of integer encoding width or string chunking.
+    pub fn index<'b>(&self, path: impl IntoIterator<Item = PathElement<'b>>) -> Option<&Self> {
+        let mut tree = self;
+        for elem in path {
+            tree = match tree.untagged() {
+                Self::Array(items) => items.get(array_index(&elem, items.len())?)?,
+                Self::Dict(entries) => {
+                    let key = Key::new(&elem);
+                    &entries.iter().find(|(k, _)| key.matches(k))?.1
+                }
+                _ => return None,
+            };
+        }
+        Some(tree)
+    }
+
+    /// Mutable access to a value found by indexing into arrays and dicts, see
+    /// [`index`](#method.index).
+    pub fn index_mut<'b>(
+        &mut self,
+        path: impl IntoIterator<Item = PathElement<'b>>,
+    ) -> Option<&mut Self> {
+        let mut tree = self;
+        for elem in path {
+            tree = match tree.untagged_mut() {
+                Self::Array(items) => {
+                    let idx = array_index(&elem, items.len())?;
+                    items.get_mut(idx)?
+                }
+                Self::Dict(entries) => {
+                    let key = Key::new(&elem);
+                    &mut entries.iter_mut().find(|(k, _)| key.matches(k))?.1
+                }
+                _ => return None,
+            };
+        }
+        Some(tree)
+    }
+}
+
+impl Clone for CborTree {
+    fn clone(&self) -> Self {
+        let mut stack = Vec::<Copying<'_>>::new();
+        let mut tree = self;
+        loop {
+            let mut tags = Vec::new();
+            while let Self::Tagged(tag, inner) = tree {
+                tags.push(*tag);
+                tree = inner;
+            }
+            let mut done = match tree {
+                Self::Pos(n) => Some(Self::Pos(*n)),
+                Self::Neg(n) => Some(Self::Neg(*n)),
+                Self::Float(f) => Some(Self::Float(*f)),
+                Self::Str(s) => Some(Self::Str(s.clone())),
+                Self::Bytes(b) => Some(Self::Bytes(b.clone())),
+                Self::Bool(b) => Some(Self::Bool(*b)),
+                Self::Null => Some(Self::Null),
+                Self::Undefined => Some(Self::Undefined),
+                Self::Simple(s) => Some(Self::Simple(*s)),
+                Self::Array(items) => {
+                    let todo = items.iter().rev().collect();
+                    stack.push(Copying::new(take(&mut tags), false, todo));
+                    None
+                }
+                Self::Dict(entries) => {
+                    let todo = entries.iter().rev().flat_map(|(k, v)| [v, k]).collect();
+                    stack.push(Copying::new(take(&mut tags), true, todo));
+                    None
+                }
+                Self::Tagged(..) => unreachable!(),
+            }
+            .map(|tree| tree.with_tags(tags));
+            // hand finished items to their parents until one of them has more contents
+            tree = loop {
+                let copying = match stack.last_mut() {
+                    Some(copying) => copying,
+                    None => return done.unwrap(),
+                };
+                copying.done.extend(done.take());
+                match copying.todo.pop() {
+                    Some(next) => break next,
+                    None => done = stack.pop().map(Copying::finish),
+                }
+            };
+        }
+    }
+}
+
+impl PartialEq for CborTree {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            let equal = match pair {
+                (Self::Pos(a), Self::Pos(b)) => a == b,
+                (Self::Neg(a), Self::Neg(b)) => a == b,
+                (Self::Float(a), Self::Float(b)) => a == b,
+                (Self::Str(a), Self::Str(b)) => a == b,
+                (Self::Bytes(a), Self::Bytes(b)) => a == b,
+                (Self::Bool(a), Self::Bool(b)) => a == b,
+                (Self::Null, Self::Null) => true,
+                (Self::Undefined, Self::Undefined) => true,
+                (Self::Simple(a), Self::Simple(b)) => a == b,
+                (Self::Array(a), Self::Array(b)) if a.len() == b.len() => {
+                    stack.extend(a.iter().zip(b).rev());
+                    true
+                }
+                (Self::Dict(a), Self::Dict(b)) if a.len() == b.len() => {
+                    for ((ka, va), (kb, vb)) in a.iter().zip(b).rev() {
+                        stack.push((va, vb));
+                        stack.push((ka, kb));
+                    }
+                    true
+                }
+                (Self::Tagged(a, x), Self::Tagged(b, y)) if a == b => {
+                    stack.push((x, y));
+                    true
+                }
+                _ => false,
+            };
+            if !equal {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Drop for CborTree {
+    fn drop(&mut self) {
+        // move nested items out so that dropping them does not recurse
+        let mut stack = Vec::new();
+        let mut tree = self;
+        let mut owned;
+        loop {
+            match tree {
+                Self::Array(items) => stack.append(items),
+                Self::Dict(entries) => {
+                    for (key, value) in entries.drain(..) {
+                        stack.push(key);
+                        stack.push(value);
+                    }
+                }
+                Self::Tagged(_, inner) => stack.push(replace(&mut **inner, Self::Null)),
+                _ => {}
+            }
+            owned = match stack.pop() {
+                Some(next) => next,
+                None => break,
+            };
+            tree = &mut owned;
+        }
+    }
+}
+
+/// An array or dict being decoded, see [`CborTree::new`](enum.CborTree.html#method.new)
+enum Open<'a> {
+    Array(Tags<'a>, ArrayIter<'a>, Vec<CborTree>),
+    /// with the decoded key of the current entry and its value to be decoded
+    Dict(
+        Tags<'a>,
+        DictIter<'a>,
+        Vec<(CborTree, CborTree)>,
+        Option<CborTree>,
+        Option<&'a Cbor>,
+    ),
+}
+
+impl<'a> Open<'a> {
+    fn push(&mut self, tree: CborTree) {
+        match self {
+            Open::Array(_, _, items) => items.push(tree),
+            Open::Dict(_, _, entries, key, _) => match key.take() {
+                Some(key) => entries.push((key, tree)),
+                None => *key = Some(tree),
+            },
+        }
+    }
+
+    /// the next key or value to be decoded
+    fn next(&mut self, lengths: &mut Lengths<'a>) -> Option<&'a Cbor> {
+        match self {
+            Open::Array(_, iter, _) => iter.next_with(lengths),
+            Open::Dict(_, iter, _, _, value) => value.take().or_else(|| {
+                let (key, v) = iter.next_with(lengths)?;
+                *value = Some(v);
+                Some(key)
+            }),
+        }
+    }
+
+    fn finish(self) -> CborTree {
+        match self {
+            Open::Array(tags, _, items) => CborTree::Array(items).with_tags(tags),
+            Open::Dict(tags, _, entries, _, _) => CborTree::Dict(entries).with_tags(tags),
+        }
+    }
+}
+
+/// An array or dict being cloned, see [`CborTree::clone`](enum.CborTree.html#method.clone)
+struct Copying<'t> {
+    tags: Vec<u64>,
+    is_dict: bool,
+    /// items still to be cloned in reverse order, with keys and values alternating for dicts
+    todo: Vec<&'t CborTree>,
+    done: Vec<CborTree>,
+}
+
+impl<'t> Copying<'t> {
+    fn new(tags: Vec<u64>, is_dict: bool, todo: Vec<&'t CborTree>) -> Self {
+        let done = Vec::with_capacity(todo.len());
+        Self {
+            tags,
+            is_dict,
+            todo,
+            done,
+        }
+    }
+
+    fn finish(self) -> CborTree {
+        let tree = if self.is_dict {
+            let mut items = self.done.into_iter();
+            CborTree::Dict(std::iter::from_fn(|| Some((items.next()?, items.next()?))).collect())
+        } else {
+            CborTree::Array(self.done)
+        };
+        tree.with_tags(self.tags)
+    }
+}
+
+impl From<&Cbor> for CborTree {
+    fn from(cbor: &Cbor) -> Self {
+        Self::new(cbor)
+    }
+}
+
+impl Display for CborTree {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.write(CborBuilder::new()))
+    }
+}
+
+fn array_index(elem: &PathElement<'_>, len: usize) -> Option<usize> {
+    match elem {
+        PathElement::Number(idx) => usize::try_from(*idx).ok(),
+        PathElement::FromEnd(idx) => len.checked_sub(usize::try_from(*idx).ok()?),
+        _ => None,
+    }
+}
+
+/// dict key to look for, same key matching as for `Cbor::index`
+enum Key<'a> {
+    Str(&'a str),
+    Pos(u64),
+    Tree(CborTree),
+    Invalid,
+}
+
+impl<'a> Key<'a> {
+    fn new(elem: &'a PathElement<'_>) -> Self {
+        match elem {
+            PathElement::String(s) => Key::Str(s),
+            PathElement::Number(n) => Key::Pos(*n),
+            PathElement::Item(item) => Key::Tree(CborTree::new(item)),
+            PathElement::FromEnd(_) => Key::Invalid,
+        }
+    }
+
+    fn matches(&self, key: &CborTree) -> bool {
+        match (self, key) {
+            (Key::Str(s), k) => matches!(k.untagged(), CborTree::Str(k) if s == k),
+            (Key::Pos(n), k) => matches!(k.untagged(), CborTree::Pos(k) if n == k),
+            (Key::Tree(t), k) => t == k,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CborTree::*;
+    use crate::{index_str, Cbor, CborBuilder, PathElement};
+    use pretty_assertions::assert_eq;
+    use std::borrow::Cow;
+
+    #[test]
+    fn round_trip() {
+        // 1([_ 2(3), -4, 1.5, "ab", h'01', true, null, undefined, simple(16),
+        //       {_ "k": 5(["x"]), "k": {}}, [_ ]])
+        let bytes = [
+            0xc1, 0x9f, 0xc2, 3, 0x23, 0xf9, 0x3e, 0x00, 0x7f, 0x61, b'a', 0x61, b'b', 0xff, 0x41,
+            1, 0xf5, 0xf6, 0xf7, 0xf0, 0xbf, 0x61, b'k', 0xc5, 0x81, 0x61, b'x', 0x61, b'k', 0xa0,
+            0xff, 0x9f, 0xff, 0xff,
+        ];
+        let cbor = Cbor::checked(&bytes).unwrap();
+        let tree = cbor.to_tree();
+        assert_eq!(
+            tree,
+            Tagged(
+                1,
+                Box::new(Array(vec![
+                    Tagged(2, Box::new(Pos(3))),
+                    Neg(3),
+                    Float(1.5),
+                    Str("ab".into()),
+                    Bytes(vec![1]),
+                    Bool(true),
+                    Null,
+                    Undefined,
+                    Simple(16),
+                    Dict(vec![
+                        (
+                            Str("k".into()),
+                            Tagged(5, Box::new(Array(vec![Str("x".into())])))
+                        ),
+                        (Str("k".into()), Dict(vec![])),
+                    ]),
+                    Array(vec![]),
+                ]))
+            )
+        );
+
+        let written = tree.write(CborBuilder::new());
+        assert_eq!(
+            written.to_string(),
+            r#"1([2(3), -4, 1.5, "ab", h'01', true, null, undefined, simple(16), {"k": 5(["x"]), "k": {}}, []])"#
+        );
+        assert_eq!(written.to_tree(), tree);
+        assert_eq!(tree.to_string(), written.to_string());
+
+        let mut items = tree.clone().into_array().unwrap();
+        assert_eq!(items.len(), 11);
+        let dict = items.remove(9).into_dict().unwrap();
+        assert_eq!(dict[1], (Str("k".into()), Dict(vec![])));
+        assert_eq!(items.remove(4).into_bytes(), Some(vec![1]));
+        assert_eq!(items.remove(3).into_string().as_deref(), Some("ab"));
+        assert_eq!(tree.into_dict(), None);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        const DEPTH: usize = 100_000;
+        // [1({0: [1({0: … null …})] …})]
+        let mut bytes = [0x81u8, 0xc1, 0xa1, 0].repeat(DEPTH);
+        bytes.push(0xf6);
+        let cbor = Cbor::unchecked(&bytes);
+        let tree = cbor.to_tree();
+        assert_eq!(tree.write(CborBuilder::new()).as_slice(), bytes.as_slice());
+        assert_eq!(tree, tree.clone());
+        let mut other = cbor.to_tree();
+        *other
+            .index_mut(vec![PathElement::Number(0); 2 * DEPTH])
+            .unwrap() = Undefined;
+        assert_ne!(tree, other);
+    }
+
+    #[test]
+    fn indexing() {
+        // {"a": 1([10, 11, 12]), 3: "three", [1]: {"b": true}}
+        let bytes = [
+            0xa3, 0x61, b'a', 0xc1, 0x83, 10, 11, 12, 3, 0x65, b't', b'h', b'r', b'e', b'e', 0x81,
+            1, 0xa1, 0x61, b'b', 0xf5,
+        ];
+        let mut tree = Cbor::checked(&bytes).unwrap().to_tree();
+
+        assert_eq!(tree.index(index_str("a[1]")), Some(&Pos(11)));
+        assert_eq!(tree.index(index_str("a[-1]")), Some(&Pos(12)));
+        assert_eq!(tree.index(index_str("a[3]")), None);
+        assert_eq!(tree.index(index_str("a[-4]")), None);
+        assert_eq!(tree.index(index_str("[3]")), Some(&Str("three".into())));
+        let key = || PathElement::Item(Cow::Borrowed(Cbor::checked(&[0x81, 1]).unwrap()));
+        assert_eq!(
+            tree.index(vec![key(), PathElement::String("b".into())]),
+            Some(&Bool(true))
+        );
+        assert_eq!(tree.index(index_str("a.b")), None);
+        assert_eq!(tree.index(index_str("x")), None);
+        assert_eq!(tree.index(None::<PathElement>), Some(&tree));
+        assert_eq!(
+            tree.index(index_str("a"))
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+
+        *tree.index_mut(index_str("a[0]")).unwrap() = Null;
+        tree.index_mut(Some(key()))
+            .unwrap()
+            .as_dict_mut()
+            .unwrap()
+            .clear();
+        tree.index_mut(index_str("a"))
+            .unwrap()
+            .as_array_mut()
+            .unwrap()
+            .pop();
+        assert!(tree.index_mut(index_str("[3][0]")).is_none());
+        assert_eq!(
+            tree.to_string(),
+            r#"{"a": 1([null, 11]), 3: "three", [1]: {}}"#
+        );
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{ItemKind, TaggedItem};
+use crate::{
+    constants::STOP_BYTE,
+    reader::{indefinite, integer, tags},
+    validated::{skip, skip_with},
+    Cbor, ItemKind, PathElement, TaggedItem,
+};
+use std::ops::Range;
 
 /// Visitor for the structure of a CBOR item.
 ///
@@ -6,6 +12,10 @@ use crate::{ItemKind, TaggedItem};
 /// or some of their values can be skipped by returning `false` from the respective
 /// methods.
 ///
+/// Tags can be inspected or intercepted using [`visit_tag`](#method.visit_tag). Visitors that
+/// need to know where items are located in the encoded bytes (e.g. for annotated hex dumps)
+/// can implement the `_at` variants of the methods instead, which receive byte ranges.
+///
 /// **Note the different lifetimes needed on the `impl Visitor`!** The first lifetime
 /// `'a` describes how long the underlying `Cbor` value lives, i.e. how long the items
 /// passed into the visitor’s methods stay available. The second lifetime `'b` denotes
@@ -113,35 +123,492 @@ pub trait Visitor<'a, Err> {
     fn visit_dict_end(&mut self, dict: TaggedItem<'a>) -> Result<(), Err> {
         Ok(())
     }
+    /// Visit a tag of the next item, before any of the other methods is called for that item.
+    /// Tags are visited from outer to inner, with `inner` being the item that the tag applies
+    /// to (i.e. carrying only the remaining inner tags). Return `false` to skip the item, e.g.
+    /// after handling it in this method, meaning that none of the other methods will be called
+    /// for it.
+    ///
+    /// Tags of dict keys are visited before [`visit_dict_key`](#method.visit_dict_key), in which
+    /// case returning `false` skips the whole dict entry.
+    fn visit_tag(&mut self, tag: u64, inner: TaggedItem<'a>) -> Result<bool, Err> {
+        Ok(true)
+    }
+    /// Same as [`visit_simple`](#method.visit_simple), additionally receiving the byte range
+    /// covered by the item (including its tags) within the visited item.
+    ///
+    /// This is the method called during the visit, the default delegates to `visit_simple`.
+    fn visit_simple_at(&mut self, item: TaggedItem<'a>, span: Range<usize>) -> Result<(), Err> {
+        self.visit_simple(item)
+    }
+    /// Same as [`visit_array_begin`](#method.visit_array_begin), additionally receiving the
+    /// byte range covered by the array (including its tags) within the visited item.
+    ///
+    /// This is the method called during the visit, the default delegates to `visit_array_begin`.
+    fn visit_array_begin_at(
+        &mut self,
+        array: TaggedItem<'a>,
+        size: Option<u64>,
+        span: Range<usize>,
+    ) -> Result<bool, Err> {
+        self.visit_array_begin(array, size)
+    }
+    /// Same as [`visit_array_end`](#method.visit_array_end), additionally receiving the byte
+    /// range covered by the array (including its tags) within the visited item.
+    ///
+    /// This is the method called during the visit, the default delegates to `visit_array_end`.
+    fn visit_array_end_at(&mut self, array: TaggedItem<'a>, span: Range<usize>) -> Result<(), Err> {
+        self.visit_array_end(array)
+    }
+    /// Same as [`visit_dict_begin`](#method.visit_dict_begin), additionally receiving the byte
+    /// range covered by the dict (including its tags) within the visited item.
+    ///
+    /// This is the method called during the visit, the default delegates to `visit_dict_begin`.
+    fn visit_dict_begin_at(
+        &mut self,
+        dict: TaggedItem<'a>,
+        size: Option<u64>,
+        span: Range<usize>,
+    ) -> Result<bool, Err> {
+        self.visit_dict_begin(dict, size)
+    }
+    /// Same as [`visit_dict_key`](#method.visit_dict_key), additionally receiving the byte
+    /// range covered by the key (including its tags) within the visited item.
+    ///
+    /// This is the method called during the visit, the default delegates to `visit_dict_key`.
+    fn visit_dict_key_at(
+        &mut self,
+        dict: TaggedItem<'a>,
+        key: TaggedItem<'a>,
+        is_first: bool,
+        span: Range<usize>,
+    ) -> Result<bool, Err> {
+        self.visit_dict_key(dict, key, is_first)
+    }
+    /// Same as [`visit_dict_end`](#method.visit_dict_end), additionally receiving the byte
+    /// range covered by the dict (including its tags) within the visited item.
+    ///
+    /// This is the method called during the visit, the default delegates to `visit_dict_end`.
+    fn visit_dict_end_at(&mut self, dict: TaggedItem<'a>, span: Range<usize>) -> Result<(), Err> {
+        self.visit_dict_end(dict)
+    }
+}
+
+/// Visitor for the structure of a CBOR item that is told where each item is located.
+///
+/// The methods correspond to those of [`Visitor`](trait.Visitor.html), with the same rules for
+/// skipping parts of the structure, but additionally receive the path from the visited item to
+/// the current one as well as the byte range covered by the current item (including its tags)
+/// within the visited item’s bytes. Array elements are identified by
+/// [`PathElement::Number`](enum.PathElement.html#variant.Number) and dict values by
+/// [`PathElement::from_key`](enum.PathElement.html#method.from_key), so that
+/// [`Cbor::index`](struct.Cbor.html#method.index) with the path yields the item.
+///
+/// Use [`Cbor::visit_paths`](struct.Cbor.html#method.visit_paths) to run such a visitor.
+///
+/// ```
+/// use cbor_data::{render_pointer, CborOwned, PathElement, PathVisitor, TaggedItem};
+/// use std::ops::Range;
+///
+/// struct Leaves(Vec<String>);
+/// impl<'a> PathVisitor<'a, ()> for Leaves {
+///     fn visit_simple(
+///         &mut self,
+///         path: &[PathElement<'a>],
+///         range: Range<usize>,
+///         item: TaggedItem<'a>,
+///     ) -> Result<(), ()> {
+///         self.0.push(format!("{} {:?} {}", render_pointer(path), range, item));
+///         Ok(())
+///     }
+/// }
+///
+/// // {"a": [1, "x"], "b": null}
+/// let cbor = CborOwned::canonical([0xa2, 0x61, b'a', 0x82, 1, 0x61, b'x', 0x61, b'b', 0xf6]).unwrap();
+/// let mut leaves = Leaves(Vec::new());
+/// cbor.visit_paths(&mut leaves).unwrap();
+/// assert_eq!(leaves.0, [r#"/a/0 4..5 1"#, r#"/a/1 5..7 "x""#, r#"/b 9..10 null"#]);
+/// ```
+#[allow(unused_variables)]
+pub trait PathVisitor<'a, Err> {
+    /// Visit a simple item, i.e. `item.kind` will neither be `Array` nor `Dict`.
+    fn visit_simple(
+        &mut self,
+        path: &[PathElement<'a>],
+        range: Range<usize>,
+        item: TaggedItem<'a>,
+    ) -> Result<(), Err> {
+        Ok(())
+    }
+    /// Visit the beginning of an array. `size` is None for indefinite size encoding.
+    /// Return `false` to skip this array, meaning that `visit_array_index`
+    /// will NOT be called for it.
+    fn visit_array_begin(
+        &mut self,
+        path: &[PathElement<'a>],
+        range: Range<usize>,
+        array: TaggedItem<'a>,
+        size: Option<u64>,
+    ) -> Result<bool, Err> {
+        Ok(true)
+    }
+    /// Visit an array element at the given index, which is also the last element of `path`.
+    /// Return `false` to skip over the element’s contents.
+    fn visit_array_index(
+        &mut self,
+        path: &[PathElement<'a>],
+        array: TaggedItem<'a>,
+        index: u64,
+    ) -> Result<bool, Err> {
+        Ok(true)
+    }
+    /// Visit the end of the current array.
+    fn visit_array_end(
+        &mut self,
+        path: &[PathElement<'a>],
+        range: Range<usize>,
+        array: TaggedItem<'a>,
+    ) -> Result<(), Err> {
+        Ok(())
+    }
+    /// Visit the beginning of a dict. `size` is None for indefinite size encoding.
+    /// Return `false` to skip this dict, meaning that `visit_dict_key`
+    /// will NOT be called for it.
+    fn visit_dict_begin(
+        &mut self,
+        path: &[PathElement<'a>],
+        range: Range<usize>,
+        dict: TaggedItem<'a>,
+        size: Option<u64>,
+    ) -> Result<bool, Err> {
+        Ok(true)
+    }
+    /// Visit a dict value at the given key, which is also represented by the last element of
+    /// `path`. `key_range` is the byte range covered by the key. Return `false` to skip over
+    /// the value’s contents.
+    fn visit_dict_key(
+        &mut self,
+        path: &[PathElement<'a>],
+        dict: TaggedItem<'a>,
+        key: TaggedItem<'a>,
+        key_range: Range<usize>,
+        is_first: bool,
+    ) -> Result<bool, Err> {
+        Ok(true)
+    }
+    /// Visit the end of the current dict.
+    fn visit_dict_end(
+        &mut self,
+        path: &[PathElement<'a>],
+        range: Range<usize>,
+        dict: TaggedItem<'a>,
+    ) -> Result<(), Err> {
+        Ok(())
+    }
+}
+
+/// Adapter that tracks paths for a [`PathVisitor`](trait.PathVisitor.html)
+pub(crate) struct PathTracker<'a, 'v, V> {
+    visitor: &'v mut V,
+    path: Vec<PathElement<'a>>,
+    /// path lengths of the arrays and dicts currently being visited
+    open: Vec<usize>,
+}
+
+impl<'a, 'v, V> PathTracker<'a, 'v, V> {
+    pub fn new(visitor: &'v mut V) -> Self {
+        Self {
+            visitor,
+            path: Vec::new(),
+            open: Vec::new(),
+        }
+    }
+
+    fn begin(&mut self) {
+        self.open.push(self.path.len());
+    }
+
+    fn element(&mut self, elem: PathElement<'a>) {
+        self.path.truncate(*self.open.last().unwrap());
+        self.path.push(elem);
+    }
+
+    fn end(&mut self) {
+        self.path.truncate(self.open.pop().unwrap());
+    }
+}
+
+impl<'a, 'v, Err, V: PathVisitor<'a, Err>> Visitor<'a, Err> for PathTracker<'a, 'v, V> {
+    fn visit_simple_at(&mut self, item: TaggedItem<'a>, span: Range<usize>) -> Result<(), Err> {
+        self.visitor.visit_simple(&self.path, span, item)
+    }
+
+    fn visit_array_begin_at(
+        &mut self,
+        array: TaggedItem<'a>,
+        size: Option<u64>,
+        span: Range<usize>,
+    ) -> Result<bool, Err> {
+        // `visit_array_end_at` is called even if the array is skipped
+        self.begin();
+        self.visitor
+            .visit_array_begin(&self.path, span, array, size)
+    }
+
+    fn visit_array_index(&mut self, array: TaggedItem<'a>, index: u64) -> Result<bool, Err> {
+        self.element(PathElement::Number(index));
+        self.visitor.visit_array_index(&self.path, array, index)
+    }
+
+    fn visit_array_end_at(&mut self, array: TaggedItem<'a>, span: Range<usize>) -> Result<(), Err> {
+        self.end();
+        self.visitor.visit_array_end(&self.path, span, array)
+    }
+
+    fn visit_dict_begin_at(
+        &mut self,
+        dict: TaggedItem<'a>,
+        size: Option<u64>,
+        span: Range<usize>,
+    ) -> Result<bool, Err> {
+        // `visit_dict_end_at` is called even if the dict is skipped
+        self.begin();
+        self.visitor.visit_dict_begin(&self.path, span, dict, size)
+    }
+
+    fn visit_dict_key_at(
+        &mut self,
+        dict: TaggedItem<'a>,
+        key: TaggedItem<'a>,
+        is_first: bool,
+        span: Range<usize>,
+    ) -> Result<bool, Err> {
+        self.element(PathElement::from_key(key.cbor()));
+        self.visitor
+            .visit_dict_key(&self.path, dict, key, span, is_first)
+    }
+
+    fn visit_dict_end_at(&mut self, dict: TaggedItem<'a>, span: Range<usize>) -> Result<(), Err> {
+        self.end();
+        self.visitor.visit_dict_end(&self.path, span, dict)
+    }
 }
 
 pub fn visit<'a, 'b, Err, V: Visitor<'b, Err>>(v: &'a mut V, c: TaggedItem<'b>) -> Result<(), Err> {
-    match c.kind() {
-        ItemKind::Array(iter) => {
-            if v.visit_array_begin(c, iter.size())? {
-                for (idx, item) in iter.enumerate() {
-                    if v.visit_array_index(c, idx as u64)? {
-                        visit(v, item.tagged_item())?;
+    let mut walk = Walk::new(c);
+    while walk.step(v)? {}
+    Ok(())
+}
+
+/// State of a visit that can be advanced step by step, see [`visit`](fn.visit.html)
+pub(crate) struct Walk<'b> {
+    extents: Extents<'b>,
+    // explicit stack so that arbitrarily deep nesting doesn’t overflow the call stack
+    stack: Vec<Open<'b>>,
+    next: Option<TaggedItem<'b>>,
+}
+
+impl<'b> Walk<'b> {
+    pub fn new(c: TaggedItem<'b>) -> Self {
+        Self {
+            extents: Extents::new(c.cbor().as_slice()),
+            stack: Vec::new(),
+            next: Some(c),
+        }
+    }
+
+    /// Visit the next item or the end of the current array or dict, returning `false` once the
+    /// visit is complete.
+    pub fn step<Err, V: Visitor<'b, Err>>(&mut self, v: &mut V) -> Result<bool, Err> {
+        let Walk {
+            extents,
+            stack,
+            next,
+        } = self;
+        if let Some(item) = next.take() {
+            let bytes = item.cbor().as_slice();
+            let skip = !visit_tags(v, item)?;
+            let span = extents.span(bytes);
+            match item.kind() {
+                _ if skip => extents.pass(bytes),
+                ItemKind::Array(iter) => {
+                    if v.visit_array_begin_at(item, iter.size(), span.clone())? {
+                        stack.push(Open::new(item, iter.size()));
+                    } else {
+                        extents.pass(bytes);
+                        v.visit_array_end_at(item, span)?;
                     }
                 }
+                ItemKind::Dict(iter) => {
+                    if v.visit_dict_begin_at(item, iter.size(), span.clone())? {
+                        stack.push(Open::new(item, iter.size()));
+                    } else {
+                        extents.pass(bytes);
+                        v.visit_dict_end_at(item, span)?;
+                    }
+                }
+                _ => v.visit_simple_at(item, span)?,
+            }
+            return Ok(!stack.is_empty());
+        }
+
+        let open = match stack.last_mut() {
+            Some(open) => open,
+            None => return Ok(false),
+        };
+        let finished = match &mut open.remaining {
+            Some(0) => true,
+            Some(n) => {
+                *n -= 1;
+                false
+            }
+            None => open.rest[0] == STOP_BYTE,
+        };
+        let item = open.item;
+        if finished {
+            stack.pop();
+            let span = extents.span(item.cbor().as_slice());
+            match item.kind() {
+                ItemKind::Array(_) => v.visit_array_end_at(item, span)?,
+                _ => v.visit_dict_end_at(item, span)?,
+            }
+            return Ok(!stack.is_empty());
+        }
+
+        let index = open.index;
+        open.index += 1;
+        let mut rest = open.rest;
+        let descend = match item.kind() {
+            ItemKind::Array(_) => v.visit_array_index(item, index)?,
+            _ => {
+                let (key, r) = extents.take(rest, false);
+                rest = r;
+                let span = extents.span(key.as_slice());
+                let key = TaggedItem::new(key);
+                visit_tags(v, key)? && v.visit_dict_key_at(item, key, index == 0, span)?
             }
+        };
+        let (value, rest) = extents.take(rest, descend);
+        if descend {
+            *next = Some(TaggedItem::new(value));
+        }
+        // the stack has not been modified since `open` was obtained
+        stack.last_mut().unwrap().rest = rest;
+        Ok(true)
+    }
+}
+
+/// Visit the tags of `item` from outer to inner, returning `false` if the item shall be skipped
+fn visit_tags<'b, Err, V: Visitor<'b, Err>>(v: &mut V, item: TaggedItem<'b>) -> Result<bool, Err> {
+    let mut inner = item.cbor().as_slice();
+    for tag in item.tags() {
+        inner = integer(inner).unwrap().2;
+        if !v.visit_tag(tag, TaggedItem::new(Cbor::unchecked(inner)))? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// An array or dict whose elements are being visited
+struct Open<'b> {
+    item: TaggedItem<'b>,
+    /// encoded elements not yet visited
+    rest: &'b [u8],
+    /// number of elements (or pairs) not yet visited, `None` for indefinite size
+    remaining: Option<u64>,
+    index: u64,
+}
 
-            v.visit_array_end(c)
+impl<'b> Open<'b> {
+    fn new(item: TaggedItem<'b>, size: Option<u64>) -> Self {
+        let bytes = tags(item.cbor().as_slice()).unwrap().1;
+        let rest = integer(bytes).or_else(|| indefinite(bytes)).unwrap().2;
+        Self {
+            item,
+            rest,
+            remaining: size,
+            index: 0,
         }
-        ItemKind::Dict(iter) => {
-            if v.visit_dict_begin(c, iter.size())? {
-                let mut is_first = true;
-                for (key, item) in iter {
-                    if v.visit_dict_key(c, key.tagged_item(), is_first)? {
-                        visit(v, item.tagged_item())?;
+    }
+}
+
+/// Extents of the arrays and dicts within the element that is currently being descended into
+///
+/// Finding the end of an element requires skipping over all its contents, so doing that anew
+/// for each nested element would make the visit quadratic in the nesting depth. Therefore,
+/// the extents found while skipping are recorded in pre-order and reused when descending.
+struct Extents<'b> {
+    base: &'b [u8],
+    /// start and end offsets relative to `base`
+    known: Vec<(usize, usize)>,
+    /// index of the next array or dict to be encountered
+    next: usize,
+}
+
+impl<'b> Extents<'b> {
+    fn new(base: &'b [u8]) -> Self {
+        Self {
+            base,
+            known: Vec::new(),
+            next: 0,
+        }
+    }
+
+    fn offset(&self, bytes: &[u8]) -> usize {
+        bytes.as_ptr() as usize - self.base.as_ptr() as usize
+    }
+
+    fn span(&self, item: &[u8]) -> Range<usize> {
+        let start = self.offset(item);
+        start..start + item.len()
+    }
+
+    /// Split off the item at the start of `bytes`, in preparation for descending into it or not.
+    fn take(&mut self, bytes: &'b [u8], descend: bool) -> (&'b Cbor, &'b [u8]) {
+        let start = self.offset(bytes);
+        let len = match self.known.get(self.next) {
+            Some(&(s, end)) if s == start => end - s,
+            _ if descend && self.next == self.known.len() => {
+                self.known.clear();
+                self.next = 0;
+                let base = self.base.as_ptr() as usize;
+                let known = &mut self.known;
+                let mut open = Vec::new();
+                let rest = skip_with(bytes, |is_open, pos| {
+                    let offset = pos.as_ptr() as usize - base;
+                    if is_open {
+                        open.push(known.len());
+                        known.push((offset, 0));
+                    } else {
+                        known[open.pop().unwrap()].1 = offset;
                     }
-                    is_first = false;
-                }
+                })
+                .1;
+                bytes.len() - rest.len()
             }
+            _ => bytes.len() - skip(bytes).1.len(),
+        };
+        let (item, rest) = bytes.split_at(len);
+        if descend && self.known.get(self.next).map(|x| x.0) == Some(start) {
+            self.next += 1;
+        } else {
+            self.pass(item);
+        }
+        (Cbor::unchecked(item), rest)
+    }
 
-            v.visit_dict_end(c)
+    /// Forget about the arrays and dicts contained in `item`, which will not be descended into.
+    fn pass(&mut self, item: &[u8]) {
+        let end = self.offset(item) + item.len();
+        while self
+            .known
+            .get(self.next)
+            .map(|x| x.0 < end)
+            .unwrap_or(false)
+        {
+            self.next += 1;
         }
-        _ => v.visit_simple(c),
     }
 }
 
@@ -226,9 +693,28 @@ impl<'a> Visitor<'a, std::fmt::Error> for &mut std::fmt::Formatter<'_> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{constants::TAG_CBOR_ITEM, CborBuilder, ItemKind, PathElement, TaggedItem, Writer};
+    use crate::{
+        constants::TAG_CBOR_ITEM, Cbor, CborBuilder, ItemKind, PathElement, TaggedItem, Writer,
+    };
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn deep_nesting() {
+        const DEPTH: usize = 1_000_000;
+        let mut bytes = vec![0x81u8; DEPTH];
+        bytes.extend_from_slice(&[0xa2, 0x81, 0x80, 0, 1, 2]);
+        let cbor = Cbor::checked(&bytes).unwrap();
+
+        let s = cbor.to_string();
+        assert_eq!(s.len(), 2 * DEPTH + 15);
+        assert!(s.starts_with("[[[[[["));
+        assert!(s.contains("[{[[]]: 0, 1: 2}]"));
+
+        let path = vec![PathElement::Number(0); DEPTH];
+        let dict = cbor.index(path).unwrap();
+        assert_eq!(dict.to_string(), "{[[]]: 0, 1: 2}");
+    }
+
     #[test]
     fn smoke() {
         let x = CborBuilder::new().write_array([1, 2], |b| {
@@ -367,4 +853,238 @@ mod tests {
             ItemKind::Undefined
         );
     }
+
+    #[test]
+    fn paths() {
+        use crate::{render_pointer, PathVisitor};
+        use std::ops::Range;
+
+        // 1([{"a": 2(h'01'), 3: 4(["x", {_ "b": null}])}, [_ 5, -6], {"skip": [7]}, 8])
+        let bytes = [
+            0xc1, 0x84, 0xa2, 0x61, b'a', 0xc2, 0x41, 1, 3, 0xc4, 0x82, 0x61, b'x', 0xbf, 0x61,
+            b'b', 0xf6, 0xff, 0x9f, 5, 0x25, 0xff, 0xa1, 0x64, b's', b'k', b'i', b'p', 0x81, 7, 8,
+        ];
+        let cbor = Cbor::checked(&bytes).unwrap();
+
+        struct Visitor<'a>(Vec<(Vec<PathElement<'a>>, Range<usize>, &'static str)>);
+        impl<'a> PathVisitor<'a, ()> for Visitor<'a> {
+            fn visit_simple(
+                &mut self,
+                path: &[PathElement<'a>],
+                range: Range<usize>,
+                _item: TaggedItem<'a>,
+            ) -> Result<(), ()> {
+                self.0.push((path.to_vec(), range, "simple"));
+                Ok(())
+            }
+            fn visit_array_begin(
+                &mut self,
+                path: &[PathElement<'a>],
+                range: Range<usize>,
+                _array: TaggedItem<'a>,
+                _size: Option<u64>,
+            ) -> Result<bool, ()> {
+                self.0.push((path.to_vec(), range, "array_begin"));
+                Ok(true)
+            }
+            fn visit_array_end(
+                &mut self,
+                path: &[PathElement<'a>],
+                range: Range<usize>,
+                _array: TaggedItem<'a>,
+            ) -> Result<(), ()> {
+                self.0.push((path.to_vec(), range, "array_end"));
+                Ok(())
+            }
+            fn visit_dict_begin(
+                &mut self,
+                path: &[PathElement<'a>],
+                range: Range<usize>,
+                _dict: TaggedItem<'a>,
+                _size: Option<u64>,
+            ) -> Result<bool, ()> {
+                self.0.push((path.to_vec(), range, "dict_begin"));
+                Ok(!matches!(path.last(), Some(PathElement::Number(2))))
+            }
+            fn visit_dict_key(
+                &mut self,
+                path: &[PathElement<'a>],
+                _dict: TaggedItem<'a>,
+                _key: TaggedItem<'a>,
+                key_range: Range<usize>,
+                _is_first: bool,
+            ) -> Result<bool, ()> {
+                self.0.push((path.to_vec(), key_range, "dict_key"));
+                Ok(true)
+            }
+            fn visit_dict_end(
+                &mut self,
+                path: &[PathElement<'a>],
+                range: Range<usize>,
+                _dict: TaggedItem<'a>,
+            ) -> Result<(), ()> {
+                self.0.push((path.to_vec(), range, "dict_end"));
+                Ok(())
+            }
+        }
+
+        let mut visitor = Visitor(Vec::new());
+        cbor.visit_paths(&mut visitor).unwrap();
+        let trace = visitor
+            .0
+            .iter()
+            .map(|(path, range, kind)| format!("{} {} {:?}", kind, render_pointer(path), range))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trace,
+            vec![
+                "array_begin  0..31",
+                "dict_begin /0 2..18",
+                "dict_key /0/a 3..5",
+                "simple /0/a 5..8",
+                "dict_key /0/3 8..9",
+                "array_begin /0/3 9..18",
+                "simple /0/3/0 11..13",
+                "dict_begin /0/3/1 13..18",
+                "dict_key /0/3/1/b 14..16",
+                "simple /0/3/1/b 16..17",
+                "dict_end /0/3/1 13..18",
+                "array_end /0/3 9..18",
+                "dict_end /0 2..18",
+                "array_begin /1 18..22",
+                "simple /1/0 19..20",
+                "simple /1/1 20..21",
+                "array_end /1 18..22",
+                "dict_begin /2 22..30",
+                "dict_end /2 22..30",
+                "simple /3 30..31",
+                "array_end  0..31",
+            ]
+        );
+
+        for (path, range, kind) in visitor.0 {
+            if kind != "dict_key" {
+                let item = cbor.index(path.iter().cloned()).unwrap();
+                assert_eq!(item.as_slice(), &bytes[range]);
+            }
+        }
+    }
+
+    #[test]
+    fn tags_and_spans() {
+        use crate::Visitor;
+        use std::ops::Range;
+
+        // [1(2("a")), 3({11(4): 5(6), 7(12): 13}), 7([8]), 9(10)]
+        let bytes = [
+            0x84, 0xc1, 0xc2, 0x61, b'a', 0xc3, 0xa2, 0xcb, 4, 0xc5, 6, 0xc7, 12, 13, 0xc7, 0x81,
+            8, 0xc9, 10,
+        ];
+        let cbor = Cbor::checked(&bytes).unwrap();
+
+        struct V(Vec<String>);
+        impl<'a> Visitor<'a, ()> for V {
+            fn visit_tag(&mut self, tag: u64, inner: TaggedItem<'a>) -> Result<bool, ()> {
+                self.0.push(format!("tag {} {}", tag, inner.cbor()));
+                // intercept tags 7 and 9
+                Ok(tag != 7 && tag != 9)
+            }
+            fn visit_simple_at(
+                &mut self,
+                item: TaggedItem<'a>,
+                span: Range<usize>,
+            ) -> Result<(), ()> {
+                self.0.push(format!("simple {} {:?}", item, span));
+                Ok(())
+            }
+            fn visit_array_begin_at(
+                &mut self,
+                array: TaggedItem<'a>,
+                _size: Option<u64>,
+                span: Range<usize>,
+            ) -> Result<bool, ()> {
+                self.0.push(format!("array_begin {:?}", span));
+                Ok(array.tags().next().is_none())
+            }
+            fn visit_array_end_at(
+                &mut self,
+                _array: TaggedItem<'a>,
+                span: Range<usize>,
+            ) -> Result<(), ()> {
+                self.0.push(format!("array_end {:?}", span));
+                Ok(())
+            }
+            fn visit_dict_begin_at(
+                &mut self,
+                _dict: TaggedItem<'a>,
+                _size: Option<u64>,
+                span: Range<usize>,
+            ) -> Result<bool, ()> {
+                self.0.push(format!("dict_begin {:?}", span));
+                Ok(true)
+            }
+            fn visit_dict_key_at(
+                &mut self,
+                _dict: TaggedItem<'a>,
+                key: TaggedItem<'a>,
+                _is_first: bool,
+                span: Range<usize>,
+            ) -> Result<bool, ()> {
+                self.0.push(format!("key {} {:?}", key, span));
+                Ok(true)
+            }
+            fn visit_dict_end_at(
+                &mut self,
+                _dict: TaggedItem<'a>,
+                span: Range<usize>,
+            ) -> Result<(), ()> {
+                self.0.push(format!("dict_end {:?}", span));
+                Ok(())
+            }
+        }
+
+        let mut v = V(Vec::new());
+        cbor.visit(&mut v).unwrap();
+        assert_eq!(
+            v.0,
+            vec![
+                "array_begin 0..19",
+                "tag 1 2(\"a\")",
+                "tag 2 \"a\"",
+                "simple 1(2(\"a\")) 1..5",
+                "tag 3 {11(4): 5(6), 7(12): 13}",
+                "dict_begin 5..14",
+                "tag 11 4",
+                "key 11(4) 7..9",
+                "tag 5 6",
+                "simple 5(6) 9..11",
+                // intercepting the tag of a key skips the whole entry
+                "tag 7 12",
+                "dict_end 5..14",
+                "tag 7 [8]",
+                "tag 9 10",
+                "array_end 0..19",
+            ]
+        );
+
+        // the spans also apply to visits of nested items
+        let mut v = V(Vec::new());
+        cbor.index([PathElement::Number(1)])
+            .unwrap()
+            .visit(&mut v)
+            .unwrap();
+        assert_eq!(
+            v.0,
+            vec![
+                "tag 3 {11(4): 5(6), 7(12): 13}",
+                "dict_begin 0..9",
+                "tag 11 4",
+                "key 11(4) 2..4",
+                "tag 5 6",
+                "simple 5(6) 4..6",
+                "tag 7 12",
+                "dict_end 0..9",
+            ]
+        );
+    }
 }
@@ -36,13 +36,13 @@ fn named_struct() {
     let x = X::read_cbor(bytes.as_ref()).unwrap();
     assert_eq!(x, X::new("hello", 42));
     let x =
-        X::read_cbor(Cbor::checked(&*b("a2 61 77 64 68 65 6c 6c 61 41 00")).unwrap()).unwrap_err();
+        X::read_cbor(Cbor::checked(&b("a2 61 77 64 68 65 6c 6c 61 41 00")).unwrap()).unwrap_err();
     assert_eq!(
         x,
         codec::CodecError::MissingField("x").with_ctx(|x| x.push('X'))
     );
     let x =
-        X::read_cbor(Cbor::checked(&*b("a3 61 78 64 68 65 6c 6c 61 79 18 2a 61 41 00")).unwrap())
+        X::read_cbor(Cbor::checked(&b("a3 61 78 64 68 65 6c 6c 61 79 18 2a 61 41 00")).unwrap())
             .unwrap();
     assert_eq!(x, X::new("hell", 42));
     assert_eq!(X::name(), "X");
@@ -58,7 +58,7 @@ fn tuple_struct() {
     assert_eq!(bytes.as_slice(), b("82 18 2a 63 73 74 72"));
     let x = X::read_cbor(bytes.as_ref()).unwrap();
     assert_eq!(x, X(42, s));
-    let e = X::read_cbor(Cbor::checked(&*b("81 18 2a")).unwrap()).unwrap_err();
+    let e = X::read_cbor(Cbor::checked(&b("81 18 2a")).unwrap()).unwrap_err();
     assert_eq!(
         e,
         codec::CodecError::TupleSize {
@@ -67,7 +67,7 @@ fn tuple_struct() {
         }
         .with_ctx(|x| x.push('X'))
     );
-    let x = X::read_cbor(Cbor::checked(&*b("83 17 60 00")).unwrap()).unwrap();
+    let x = X::read_cbor(Cbor::checked(&b("83 17 60 00")).unwrap()).unwrap();
     assert_eq!(x, X(23, String::new()));
 }
 
@@ -81,7 +81,7 @@ fn single_struct() {
         b("81 03")
     );
     assert_eq!(
-        X::read_cbor(Cbor::checked(&*b("82 13 00")).unwrap()).unwrap(),
+        X::read_cbor(Cbor::checked(&b("82 13 00")).unwrap()).unwrap(),
         X(19)
     );
 
@@ -91,7 +91,7 @@ fn single_struct() {
 
     assert_eq!(Y(3).write_cbor(CborBuilder::default()).as_slice(), b("03"));
     assert_eq!(
-        Y::read_cbor(Cbor::checked(&*b("13")).unwrap()).unwrap(),
+        Y::read_cbor(Cbor::checked(&b("13")).unwrap()).unwrap(),
         Y(19)
     );
 }
@@ -142,18 +142,18 @@ fn error() {
     assert_eq!(bytes.as_slice(), b("82 01 02"));
     assert_eq!(X::read_cbor(bytes.as_ref()).unwrap(), X(vec![1, 2]));
 
-    let err = X::read_cbor(Cbor::checked(&*b("82 21 02")).unwrap()).unwrap_err();
+    let err = X::read_cbor(Cbor::checked(&b("82 21 02")).unwrap()).unwrap_err();
     assert_eq!(err.to_string(), "error decoding u32 <- Vec<u32> <- X: codec error: out of range integral type conversion attempted");
 
-    let err = X::read_cbor(Cbor::checked(&*b("82 41 00 02")).unwrap()).unwrap_err();
+    let err = X::read_cbor(Cbor::checked(&b("82 41 00 02")).unwrap()).unwrap_err();
     assert_eq!(err.to_string(), "error decoding i128 <- u32 <- Vec<u32> <- X: type error when reading number: found byte string (tags: None)");
 
-    let err = X::read_cbor(Cbor::checked(&*b("82 c2 41 00 02")).unwrap()).unwrap_err();
+    let err = X::read_cbor(Cbor::checked(&b("82 c2 41 00 02")).unwrap()).unwrap_err();
     assert_eq!(
         err.to_string(),
         "error decoding i128 <- u32 <- Vec<u32> <- X: wrong number format (found big decimal)"
     );
 
-    let err = X::read_cbor(Cbor::checked(&*b("a1 01 02")).unwrap()).unwrap_err();
+    let err = X::read_cbor(Cbor::checked(&b("a1 01 02")).unwrap()).unwrap_err();
     assert_eq!(err.to_string(), "error decoding Vec<u32> <- X: type error when reading array: found dictionary (tags: None)");
 }